@@ -0,0 +1,14 @@
+// A donut spinning away while some slow work gets done.
+use forbidden_donut::DonutSpinner;
+use std::time::Duration;
+
+fn main() -> forbidden_donut::Result<()> {
+    let mut spinner = DonutSpinner::new(20, 10);
+    spinner.start();
+    for step in ["resolving", "downloading", "compiling", "linking"] {
+        spinner.set_message(&format!("{}...", step));
+        std::thread::sleep(Duration::from_secs(1));
+    }
+    spinner.set_message("done");
+    spinner.finish()
+}
@@ -0,0 +1,36 @@
+// A custom shader: the default Phong shading, in candy stripes that wind
+// around the donut over time.
+use forbidden_donut::caps::Capabilities;
+use forbidden_donut::color::Rgb;
+use forbidden_donut::{
+    Donut, FrameBuffer, Params, Phong, RenderTarget, Shade, Shader, Terminal, Vec3,
+};
+
+fn main() -> forbidden_donut::Result<()> {
+    let params = Params::default();
+    let mut donut = Donut::new();
+    donut.shader = Box::new(|n: Vec3, view: Vec3, light: Vec3, uv: (f32, f32), t: f32| {
+        let phong = Phong.shade(n, view, light, uv, t);
+        let stripe = ((uv.0 * 12.0 + uv.1 * 3.0 + t * 0.5) as i32) % 2 == 0;
+        Shade {
+            color: Some(if stripe {
+                Rgb(255, 64, 64)
+            } else {
+                Rgb(255, 255, 255)
+            }),
+            ..phong
+        }
+    });
+
+    let mut terminal = Terminal::new(std::io::stdout(), None, Capabilities::probe())?;
+    let mut framebuffer = FrameBuffer::new();
+    loop {
+        let (sx, sy) = terminal.dimensions();
+        framebuffer.clear(sx, sy);
+        donut.render(&params, &mut framebuffer);
+        donut.step(&params);
+        framebuffer.blit(&mut terminal, None);
+        terminal.present()?;
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::input::Input;
+use forbidden_donut::Params;
+use std::time::Instant;
+
+// Audio-reactive mode. Follows along with a WAV file in real time (play it in
+// your music player of choice alongside), running an FFT over the most recent
+// window of samples each frame to find the level and the beat.
+pub struct Audio {
+    samples: Vec<f32>,
+    rate: u32,
+    start: Instant,
+    // Running average of bass energy, to detect beats against.
+    bass_avg: f32,
+    // Overall loudness in [0, 1], and a pulse that jumps to 1 on each beat and
+    // decays in between.
+    pub level: f32,
+    pub pulse: f32,
+}
+
+const WINDOW: usize = 1024;
+
+type Complex = (f32, f32);
+
+// In-place iterative radix-2 FFT; buf.len() must be a power of two.
+fn fft(buf: &mut [Complex]) {
+    let n = buf.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buf.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (wr, wi) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+                let (ar, ai) = buf[start + k];
+                let (br, bi) = buf[start + k + len / 2];
+                let (tr, ti) = (br * wr - bi * wi, br * wi + bi * wr);
+                buf[start + k] = (ar + tr, ai + ti);
+                buf[start + k + len / 2] = (ar - tr, ai - ti);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+fn read_wav(path: &str) -> std::result::Result<(Vec<f32>, u32), hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<std::result::Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u32 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<std::result::Result<_, _>>()?
+        }
+    };
+    // Mix down to mono
+    let channels = spec.channels as usize;
+    let mono = interleaved
+        .chunks(channels)
+        .map(|c| c.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+impl Audio {
+    pub fn open(path: &str) -> std::result::Result<Audio, String> {
+        let (samples, rate) = read_wav(path).map_err(|e| format!("{}: {}", path, e))?;
+        if samples.len() <= WINDOW {
+            return Err(format!("{}: too short", path));
+        }
+        Ok(Audio {
+            samples,
+            rate,
+            start: Instant::now(),
+            bass_avg: 0.0,
+            level: 0.0,
+            pulse: 0.0,
+        })
+    }
+}
+
+impl Input for Audio {
+    // The donut swells and brightens on the beat, and spins with the level.
+    fn apply(&mut self, params: &mut Params) {
+        // Loop the file
+        let pos = (self.start.elapsed().as_secs_f32() * self.rate as f32) as usize
+            % (self.samples.len() - WINDOW);
+        let mut buf: Vec<Complex> = self.samples[pos..pos + WINDOW]
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| {
+                let hann =
+                    0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / WINDOW as f32).cos();
+                (s * hann, 0.0)
+            })
+            .collect();
+        fft(&mut buf);
+
+        let hz_per_bin = self.rate as f32 / WINDOW as f32;
+        let energy = |lo: f32, hi: f32| -> f32 {
+            let (lo, hi) = ((lo / hz_per_bin) as usize, (hi / hz_per_bin) as usize);
+            buf[lo.max(1)..=hi.min(WINDOW / 2)]
+                .iter()
+                .map(|(r, i)| r * r + i * i)
+                .sum::<f32>()
+                / WINDOW as f32
+        };
+        let bass = energy(20.0, 150.0);
+        let total = energy(20.0, 8000.0);
+
+        if bass > 1.5 * self.bass_avg && bass > 1e-3 {
+            self.pulse = 1.0;
+        } else {
+            self.pulse *= 0.85;
+        }
+        self.bass_avg = 0.9 * self.bass_avg + 0.1 * bass;
+        self.level = (total.sqrt() / 4.0).min(1.0);
+
+        params.r1 = 1.0 + 0.2 * self.pulse;
+        params.speed = 0.5 + 2.0 * self.level;
+        params.gain = 0.7 + 0.5 * self.pulse;
+    }
+}
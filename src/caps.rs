@@ -0,0 +1,247 @@
+// What the terminal we're drawing to can display.
+use crate::ramp;
+
+pub use crate::color::ColorDepth;
+
+impl ColorDepth {
+    // Best guess from the environment and terminfo, the same way most CLI
+    // tools do it.
+    pub fn detect() -> ColorDepth {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" || fancy_terminal() {
+            return ColorDepth::TrueColor;
+        }
+        let term = std::env::var("TERM").unwrap_or_default();
+        if let Some(colors) = crate::terminfo::colors(&term) {
+            return match colors {
+                0..=7 => ColorDepth::Mono,
+                8..=255 => ColorDepth::Ansi16,
+                256..=0xffffff => ColorDepth::Ansi256,
+                _ => ColorDepth::TrueColor,
+            };
+        }
+        if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else if term.contains("color")
+            || ["xterm", "screen", "tmux", "rxvt", "linux", "cygwin"]
+                .iter()
+                .any(|t| term.starts_with(t))
+        {
+            ColorDepth::Ansi16
+        } else {
+            ColorDepth::Mono
+        }
+    }
+}
+
+// Set to anything but empty to ask for no color (https://no-color.org).
+fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+// Terminals known to do truecolor and to have fonts with good Unicode coverage.
+fn fancy_terminal() -> bool {
+    let term = std::env::var("TERM").unwrap_or_default();
+    let program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+    term == "xterm-kitty"
+        || term == "xterm-ghostty"
+        || program == "WezTerm"
+        || program == "iTerm.app"
+        || std::env::var_os("KITTY_WINDOW_ID").is_some()
+}
+
+// Asks the terminal for its primary device attributes (DA1), returning the
+// attribute codes it answers with, or None if it doesn't answer quickly.
+#[cfg(unix)]
+fn query_da1() -> Option<Vec<u32>> {
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+
+    let mut tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    crossterm::terminal::enable_raw_mode().ok()?;
+    let mut response = Vec::new();
+    let answered = (|| {
+        tty.write_all(b"\x1b[c").ok()?;
+        tty.flush().ok()?;
+        let mut pollfd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        while response.len() < 64 {
+            if unsafe { libc::poll(&mut pollfd, 1, 200) } <= 0 {
+                return None;
+            }
+            let mut byte = [0u8];
+            if tty.read(&mut byte).ok()? == 0 {
+                return None;
+            }
+            response.push(byte[0]);
+            if byte[0] == b'c' {
+                return Some(());
+            }
+        }
+        None
+    })();
+    let _ = crossterm::terminal::disable_raw_mode();
+    answered?;
+    // ESC [ ? 6 2 ; 2 2 c
+    let body = std::str::from_utf8(&response).ok()?;
+    let body = body.strip_prefix("\x1b[?")?.strip_suffix('c')?;
+    Some(body.split(';').filter_map(|a| a.parse().ok()).collect())
+}
+
+#[cfg(not(unix))]
+fn query_da1() -> Option<Vec<u32>> {
+    None
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Capabilities {
+    pub color: ColorDepth,
+    // Can show (narrow) non-ASCII glyphs, i.e. is being fed UTF-8.
+    pub unicode: bool,
+    // Understands escape sequences. Legacy Windows consoles without virtual
+    // terminal processing don't, and have to be driven through the console API.
+    pub vt: bool,
+}
+
+impl Capabilities {
+    // Set DONUT_LEGACY_CONSOLE to get what a legacy Windows console would on
+    // any platform, for testing. NO_COLOR gets no color, whatever the
+    // terminal can do.
+    pub fn probe() -> Capabilities {
+        if std::env::var_os("DONUT_LEGACY_CONSOLE").is_some() {
+            return Capabilities::LEGACY_CONSOLE;
+        }
+        let mut caps = Capabilities::probe_platform();
+        if no_color() {
+            caps.color = ColorDepth::Mono;
+        }
+        caps
+    }
+
+    // Also asks the terminal itself, which takes a moment and needs it to be
+    // interactive. Only ever upgrades what the environment says.
+    pub fn probe_interactive() -> Capabilities {
+        let mut caps = Capabilities::probe();
+        if caps.vt && caps.color == ColorDepth::Mono && !no_color() {
+            // Attribute 22 is ANSI color
+            if query_da1().is_some_and(|attrs| attrs.contains(&22)) {
+                caps.color = ColorDepth::Ansi16;
+            }
+        }
+        caps
+    }
+
+    const LEGACY_CONSOLE: Capabilities = Capabilities {
+        color: ColorDepth::Mono,
+        unicode: false,
+        vt: false,
+    };
+
+    #[cfg(not(windows))]
+    fn probe_platform() -> Capabilities {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|v| std::env::var(v).ok())
+            .find(|v| !v.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        Capabilities {
+            color: ColorDepth::detect(),
+            unicode: locale.contains("utf-8") || locale.contains("utf8"),
+            vt: true,
+        }
+    }
+
+    #[cfg(windows)]
+    fn probe_platform() -> Capabilities {
+        // Turns on virtual terminal processing where the console has it
+        // (Windows 10+ conhost, Windows Terminal).
+        if !crossterm::ansi_support::supports_ansi() {
+            return Capabilities::LEGACY_CONSOLE;
+        }
+        let code_page = unsafe { windows_sys::Win32::System::Console::GetConsoleOutputCP() };
+        // Windows Terminal sets WT_SESSION, and does truecolor; conhost only
+        // gets 16 colors right.
+        let color = if std::env::var_os("WT_SESSION").is_some() {
+            ColorDepth::TrueColor
+        } else {
+            ColorDepth::Ansi16
+        };
+        Capabilities {
+            color,
+            unicode: code_page == 65001,
+            vt: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    Low,
+    Medium,
+    Ultra,
+}
+
+impl Preset {
+    // None for auto
+    pub fn parse(name: &str) -> Option<Option<Preset>> {
+        match name {
+            "auto" => Some(None),
+            "low" => Some(Some(Preset::Low)),
+            "medium" => Some(Some(Preset::Medium)),
+            "ultra" => Some(Some(Preset::Ultra)),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Low => "low",
+            Preset::Medium => "medium",
+            Preset::Ultra => "ultra",
+        }
+    }
+
+    // Plain ASCII on dumb terminals, truecolor and braille on the fancy ones,
+    // and the middle ground for everything else.
+    pub fn auto(caps: &Capabilities) -> Preset {
+        if caps.color == ColorDepth::Mono || !caps.vt {
+            Preset::Low
+        } else if caps.color == ColorDepth::TrueColor && caps.unicode && fancy_terminal() {
+            Preset::Ultra
+        } else {
+            Preset::Medium
+        }
+    }
+
+    pub fn max_color(self) -> ColorDepth {
+        match self {
+            Preset::Low => ColorDepth::Mono,
+            Preset::Medium => ColorDepth::Ansi256,
+            Preset::Ultra => ColorDepth::TrueColor,
+        }
+    }
+
+    pub fn ramp(self) -> &'static str {
+        match self {
+            Preset::Low | Preset::Medium => ramp::CLASSIC,
+            Preset::Ultra => ramp::lookup("braille"),
+        }
+    }
+
+    // Multiplier on the number of points drawn.
+    pub fn detail(self) -> f32 {
+        match self {
+            Preset::Low => 0.5,
+            Preset::Medium => 1.0,
+            Preset::Ultra => 1.5,
+        }
+    }
+}
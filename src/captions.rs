@@ -0,0 +1,119 @@
+use forbidden_donut::target::{is_wide, Cell, RenderTarget};
+use std::path::Path;
+
+// --captions FILE: subtitles in SubRip (.srt) form, shown at the bottom of
+// the screen over the donut, --pipe-video frames included. Cues go by the
+// frame count, not the clock, so they land on the same frames however fast
+// the video is made.
+pub struct Captions {
+    // Start and end in seconds, and the lines shown in between
+    cues: Vec<(f32, f32, Vec<String>)>,
+}
+
+impl Captions {
+    // Blocks of an optional number, a `00:00:01,000 --> 00:00:04,500` line
+    // and the text, separated by blank lines.
+    pub fn load(path: &Path) -> std::result::Result<Captions, String> {
+        let err = |line: usize, e: &str| format!("{}:{}: {}", path.display(), line, e);
+        let text = std::fs::read_to_string(path).map_err(|e| err(0, &e.to_string()))?;
+        let text = text.trim_start_matches('\u{feff}');
+        let mut cues = Vec::new();
+        let mut lines = text.lines().map(str::trim_end).enumerate().peekable();
+        loop {
+            while lines.next_if(|(_, l)| l.is_empty()).is_some() {}
+            let (mut i, mut line) = match lines.next() {
+                Some(line) => line,
+                None => break,
+            };
+            if !line.contains("-->") && line.chars().all(|c| c.is_ascii_digit()) {
+                (i, line) = lines
+                    .next()
+                    .ok_or_else(|| err(i + 1, "cue with no times"))?;
+            }
+            let (start, end) = line
+                .split_once("-->")
+                .and_then(|(start, end)| Some((timestamp(start)?, timestamp(end)?)))
+                .filter(|(start, end)| start < end)
+                .ok_or_else(|| err(i + 1, "bad cue times"))?;
+            let mut text = Vec::new();
+            while let Some((_, line)) = lines.next_if(|(_, l)| !l.is_empty()) {
+                text.push(line.to_string());
+            }
+            cues.push((start, end, text));
+        }
+        Ok(Captions { cues })
+    }
+
+    // The lines of every cue showing at this time, earliest first.
+    pub fn at(&self, seconds: f32) -> Vec<&str> {
+        let showing = self
+            .cues
+            .iter()
+            .filter(|(start, end, _)| (*start..*end).contains(&seconds));
+        showing
+            .flat_map(|(_, _, text)| text.iter().map(String::as_str))
+            .collect()
+    }
+}
+
+// `HH:MM:SS,mmm`, or with a `.` for the `,` as WebVTT has it.
+fn timestamp(s: &str) -> Option<f32> {
+    let mut fields = s.trim().splitn(3, ':');
+    let (h, m, s) = (fields.next()?, fields.next()?, fields.next()?);
+    let h: u32 = h.parse().ok()?;
+    let m: u32 = m.parse().ok()?;
+    let s: f32 = s.replace(',', ".").parse().ok()?;
+    (m < 60 && (0.0..60.0).contains(&s)).then(|| (h * 3600 + m * 60) as f32 + s)
+}
+
+// Columns a glyph takes on the screen.
+fn columns(glyph: char) -> usize {
+    if is_wide(glyph) {
+        2
+    } else {
+        1
+    }
+}
+
+// Where the lines go: centered, the last one just above the bottom row
+// (the title's), cut to fit the width, with a blank cell either side to set
+// them apart from the donut. Columns and rows, in cells, wide glyphs taking
+// two.
+fn layout(lines: &[&str], sx: usize, sy: usize) -> Vec<(usize, usize, String)> {
+    let rows = lines.len().min(sy.saturating_sub(2));
+    let top = sy.saturating_sub(1) - rows;
+    let lines = &lines[lines.len() - rows..];
+    let mut placed = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        let mut room = sx;
+        let line: String = format!(" {} ", line)
+            .chars()
+            .take_while(|&glyph| {
+                let fits = columns(glyph) <= room;
+                if fits {
+                    room -= columns(glyph);
+                }
+                fits
+            })
+            .collect();
+        placed.push((room / 2, top + row, line));
+    }
+    placed
+}
+
+pub fn draw(target: &mut dyn RenderTarget, lines: &[&str]) {
+    let (sx, sy) = target.dimensions();
+    for (mut x, y, line) in layout(lines, sx, sy) {
+        for glyph in line.chars() {
+            target.put(x, y, Cell { glyph, color: None });
+            if is_wide(glyph) {
+                let tail = Cell {
+                    glyph: Cell::TAIL,
+                    color: None,
+                };
+                target.put(x + 1, y, tail);
+            }
+            x += columns(glyph);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt;
+use std::path::PathBuf;
+
+// The config file, ~/.config/forbidden-donut/config.toml: defaults for the
+// command line flags, as `flag = value` (e.g. `ramp = "blocks"`, `fps = 30`,
+// `midi = true`). Only the bits of TOML it needs are supported: tables, and
+// strings, numbers, booleans and arrays and inline tables of those. Inline
+// tables can be JSON objects too, for the control socket.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    List(Vec<Value>),
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    // A table's value for `key`.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Str(s) => f.write_str(&quote(s)),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::List(items) => {
+                let items: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Table(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(k, v)| format!("{} = {}", k, v))
+                    .collect();
+                write!(f, "{{ {} }}", entries.join(", "))
+            }
+        }
+    }
+}
+
+// Tables in file order; the first is the top level, named "".
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    tables: Vec<(String, Vec<(String, Value)>)>,
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("forbidden-donut").join("config.toml"))
+}
+
+pub fn parse_value(s: &str) -> Option<Value> {
+    let s = s.trim();
+    if let Some(body) = s.strip_prefix('[') {
+        let body = body.strip_suffix(']')?;
+        let mut items = Vec::new();
+        let mut rest = body.trim();
+        while !rest.is_empty() {
+            let end = item_end(rest);
+            items.push(parse_value(&rest[..end])?);
+            rest = rest[end..].trim_start();
+            rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+        }
+        return Some(Value::List(items));
+    }
+    if let Some(body) = s.strip_prefix('{') {
+        let body = body.strip_suffix('}')?;
+        let mut entries = Vec::new();
+        let mut rest = body.trim();
+        while !rest.is_empty() {
+            let end = item_end(rest);
+            let (key, value) = split_key(&rest[..end])?;
+            entries.push((key, parse_value(value)?));
+            rest = rest[end..].trim_start();
+            rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+        }
+        return Some(Value::Table(entries));
+    }
+    if s.starts_with('"') {
+        return parse_string(s).map(Value::Str);
+    }
+    match s {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => s
+            .parse()
+            .map(Value::Int)
+            .or_else(|_| s.parse().map(Value::Float))
+            .ok(),
+    }
+}
+
+// Where the first array item in s ends: at a top-level comma, or the end.
+fn item_end(s: &str) -> usize {
+    let (mut depth, mut in_string, mut escaped) = (0, false, false);
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '[' | '{' if !in_string => depth += 1,
+            ']' | '}' if !in_string => depth -= 1,
+            ',' if !in_string && depth == 0 => return i,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+// Splits `key = value` or `"key": value`.
+fn split_key(item: &str) -> Option<(String, &str)> {
+    let item = item.trim();
+    let (mut in_string, mut escaped) = (false, false);
+    for (i, c) in item.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '=' | ':' if !in_string => {
+                let key = item[..i].trim();
+                let key = match key.starts_with('"') {
+                    true => parse_string(key)?,
+                    false => key.to_string(),
+                };
+                return Some((key, &item[i + 1..]));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_string(s: &str) -> Option<String> {
+    let body = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            c @ ('"' | '\\' | '/') => c,
+            'u' => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let mut code = u32::from_str_radix(&hex, 16).ok()?;
+                // Past the first 64K, JSON has a high surrogate then `\u`
+                // and a low one
+                if (0xd800..0xdc00).contains(&code) {
+                    let low: String = chars.by_ref().take(6).collect();
+                    let low = u32::from_str_radix(low.strip_prefix("\\u")?, 16).ok()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return None;
+                    }
+                    code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                }
+                std::char::from_u32(code)?
+            }
+            _ => return None,
+        });
+    }
+    Some(out)
+}
+
+// `s` as a basic string, which TOML and JSON write the same way: quotes and
+// backslashes escaped, control characters as `\uXXXX` (or their short
+// forms), and everything else as it is.
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c < ' ' || c == '\u{7f}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Whether `line` opens more arrays than it closes, minding brackets in
+// strings.
+fn unclosed(line: &str) -> bool {
+    let (mut depth, mut in_string, mut escaped) = (0, false, false);
+    for c in line.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '[' if !in_string => depth += 1,
+            ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+// Drops a trailing comment, minding '#'s in strings.
+fn strip_comment(line: &str) -> &str {
+    let (mut in_string, mut escaped) = (false, false);
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+impl Config {
+    pub fn parse(text: &str) -> std::result::Result<Config, String> {
+        let mut config = Config::default();
+        config.tables.push((String::new(), Vec::new()));
+        let mut lines = text.lines().enumerate();
+        while let Some((n, line)) = lines.next() {
+            let err = |what: &str| format!("line {}: {}", n + 1, what);
+            let mut line = strip_comment(line).trim().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                config.tables.push((name.trim().to_string(), Vec::new()));
+                continue;
+            }
+            // Arrays can carry on over several lines
+            while unclosed(&line) {
+                match lines.next() {
+                    Some((_, more)) => line.push_str(strip_comment(more)),
+                    None => return Err(err("unterminated array")),
+                }
+            }
+            let eq = line.find('=').ok_or_else(|| err("expected key = value"))?;
+            let key = line[..eq].trim().trim_matches('"').to_string();
+            let value = parse_value(&line[eq + 1..]).ok_or_else(|| err("bad value"))?;
+            config.tables.last_mut().unwrap().1.push((key, value));
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &std::path::Path) -> std::result::Result<Option<Config>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text)
+                .map(Some)
+                .map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn table(&self, name: &str) -> &[(String, Value)] {
+        self.tables
+            .iter()
+            .find(|(n, _)| n == name)
+            .map_or(&[], |(_, entries)| entries)
+    }
+
+    pub fn set(&mut self, table: &str, key: &str, value: Value) {
+        if !self.tables.iter().any(|(n, _)| n == table) {
+            self.tables.push((table.to_string(), Vec::new()));
+        }
+        let entries = &mut self.tables.iter_mut().find(|(n, _)| n == table).unwrap().1;
+        match entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => entries.push((key.to_string(), value)),
+        }
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, entries) in &self.tables {
+            if !name.is_empty() {
+                writeln!(f, "\n[{}]", name)?;
+            }
+            for (key, value) in entries {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
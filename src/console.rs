@@ -0,0 +1,204 @@
+use crate::options::{self, Options};
+use crate::pacer::Pacer;
+use crate::scene::Scene;
+use crossterm::event::{KeyCode, KeyEvent};
+use forbidden_donut::{CellBuffer, FrameBuffer, Params, RenderTarget, Vec3};
+use std::path::Path;
+
+// What a key did
+pub enum Handled {
+    No,
+    Yes,
+    Quit,
+}
+
+// A vi-style command line: `:` opens it on the bottom row, Enter runs what's
+// been typed and Esc drops it. The answer stays up until the next key.
+#[derive(Default)]
+pub struct Console {
+    line: Option<String>,
+    message: String,
+}
+
+// Everything the commands can change
+pub struct Live<'a> {
+    pub scene: &'a mut Scene,
+    pub params: &'a mut Params,
+    pub opts: &'a mut Options,
+    pub pacer: &'a mut Pacer,
+    pub paused: &'a mut bool,
+    // The last frame drawn
+    pub framebuffer: &'a FrameBuffer,
+}
+
+impl Console {
+    pub fn key(&mut self, key: &KeyEvent, live: &mut Live) -> Handled {
+        self.message.clear();
+        let line = match &mut self.line {
+            Some(line) => line,
+            None if key.code == KeyCode::Char(':') => {
+                self.line = Some(String::new());
+                return Handled::Yes;
+            }
+            None => return Handled::No,
+        };
+        match key.code {
+            KeyCode::Char(c) => line.push(c),
+            KeyCode::Backspace if line.is_empty() => self.line = None,
+            KeyCode::Backspace => {
+                line.pop();
+            }
+            KeyCode::Esc => self.line = None,
+            KeyCode::Enter => {
+                let line = self.line.take().unwrap_or_default();
+                let words: Vec<&str> = line.split_whitespace().collect();
+                match run(&words, live) {
+                    Ok(None) => return Handled::Quit,
+                    Ok(Some(message)) => self.message = message,
+                    Err(message) => self.message = message,
+                }
+            }
+            _ => {}
+        }
+        Handled::Yes
+    }
+
+    pub fn draw(&self, target: &mut dyn RenderTarget) {
+        let text = match &self.line {
+            Some(line) => format!(":{}_", line),
+            None if !self.message.is_empty() => self.message.clone(),
+            None => return,
+        };
+        let (sx, sy) = target.dimensions();
+        if sy == 0 {
+            return;
+        }
+        let text: String = text
+            .chars()
+            .chain(std::iter::repeat(' '))
+            .take(sx)
+            .collect();
+        target.put_str(0, sy - 1, &text, None);
+    }
+}
+
+// Runs one command, giving what to say about it, or None to quit.
+pub fn run(words: &[&str], live: &mut Live) -> std::result::Result<Option<String>, String> {
+    let line = words.join(" ");
+    let numbers = |words: &[&str]| -> std::result::Result<Vec<f32>, String> {
+        words
+            .iter()
+            .map(|w| w.parse().map_err(|_| format!("not a number: {}", w)))
+            .collect()
+    };
+    match *words {
+        [] => Ok(Some(String::new())),
+        ["q"] | ["quit"] => Ok(None),
+        ["pause"] | ["resume"] => {
+            *live.paused = words[0] == "pause";
+            Ok(Some(line))
+        }
+        ["screenshot", path] => {
+            screenshot(live.framebuffer, Path::new(path))
+                .map_err(|e| format!("{}: {}", path, e))?;
+            Ok(Some(format!("saved {}", path)))
+        }
+        ["set", "fps", fps] => {
+            let fps = fps
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or(format!("bad fps '{}'", fps))?;
+            live.opts.fps = fps;
+            live.pacer.set_fps(fps);
+            Ok(Some(format!("fps {}", fps)))
+        }
+        ["set", name, ref values @ ..] => {
+            set(live.params, name, &numbers(values)?)?;
+            Ok(Some(format!("{} {}", name, values.join(" "))))
+        }
+        ["light", "dir", ref values @ ..] => {
+            set(live.params, "light", &numbers(values)?)?;
+            Ok(Some(format!("light {}", values.join(" "))))
+        }
+        ["shape", shapes] => {
+            let shapes = options::parse_shapes(shapes)?;
+            live.scene.set_shapes(shapes.clone())?;
+            live.opts.morph = Some(shapes);
+            Ok(Some(line))
+        }
+        ["palette", name] => {
+            let previous = live.opts.palette.replace(name.to_string());
+            match live.opts.palette() {
+                Ok(palette) => live.scene.set_palette(palette),
+                Err(e) => {
+                    live.opts.palette = previous;
+                    return Err(e);
+                }
+            }
+            Ok(Some(line))
+        }
+        ["save", path] => {
+            live.opts.save_scene(Path::new(path), &settings(live.params))?;
+            Ok(Some(format!("saved {}", path)))
+        }
+        _ => Err(format!(
+            "unknown command '{}': try set NAME VALUE, light dir X Y Z, shape NAME, palette NAME, pause, resume, screenshot FILE, save FILE, q",
+            line
+        )),
+    }
+}
+
+// The frame as plain text, without color.
+fn screenshot(framebuffer: &FrameBuffer, path: &Path) -> std::io::Result<()> {
+    let sx = framebuffer.sx * framebuffer.columns();
+    let mut cells = CellBuffer::new(sx, framebuffer.sy);
+    framebuffer.blit(&mut cells, None);
+    std::fs::write(path, cells.text())
+}
+
+// Sets one of the scene's settings, by its name in the [scene] table.
+pub fn set(params: &mut Params, name: &str, values: &[f32]) -> std::result::Result<(), String> {
+    let vector = || match *values {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!("{} takes three numbers", name)),
+    };
+    let value = || match *values {
+        [x] => Ok(x),
+        _ => Err(format!("{} takes one number", name)),
+    };
+    match name {
+        "r1" => params.r1 = value()?,
+        "r2" => params.r2 = value()?,
+        "speed" => params.speed = value()?,
+        "gain" => params.gain = value()?,
+        "dist" => params.cam_dist = value()?.clamp(2.5, 12.0),
+        "spin" => params.spin = vector()?,
+        "light" => {
+            params.light_dir = vector()?
+                .try_normalize(1e-6)
+                .ok_or("the light needs a direction")?
+        }
+        _ => {
+            return Err(format!(
+                "unknown setting '{}' (fps, r1, r2, speed, gain, dist, spin, light)",
+                name
+            ))
+        }
+    }
+    Ok(())
+}
+
+// The scene's settings, for saving.
+fn settings(params: &Params) -> Vec<(&'static str, Vec<f32>)> {
+    let v = |v: Vec3| vec![v.x, v.y, v.z];
+    vec![
+        ("r1", vec![params.r1]),
+        ("r2", vec![params.r2]),
+        ("speed", vec![params.speed]),
+        ("gain", vec![params.gain]),
+        ("dist", vec![params.cam_dist]),
+        ("spin", v(params.spin)),
+        ("light", v(params.light_dir)),
+    ]
+}
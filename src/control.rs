@@ -0,0 +1,331 @@
+use crate::config::{self, Value};
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+
+// --control PATH: a Unix-domain socket (on Windows, a named pipe) taking
+// commands as JSON, one per line, for scripts and other programs to drive
+// the donut with, e.g.
+//
+//   {"cmd": "set", "name": "speed", "value": 2}
+//   {"cmd": "light", "dir": [1, 2, -1]}
+//   {"cmd": "shape", "shape": "sphere"}
+//   {"cmd": "palette", "name": "amber"}
+//   {"cmd": "pause"}, {"cmd": "resume"}, {"cmd": "quit"}
+//   {"cmd": "screenshot", "path": "frame.txt"}, {"cmd": "save", "path": "scene.toml"}
+//
+// Each gets a line back: {"ok": true, "message": "..."} or {"ok": false,
+// "error": "..."}. They're the console's commands underneath.
+pub struct Control {
+    listener: sys::Listener,
+    clients: Vec<Client>,
+}
+
+// Connections are dropped with more than this waiting on either side: a line
+// that long in, or replies that long not being read.
+const BACKLOG: usize = 64 * 1024;
+
+struct Client {
+    stream: sys::Stream,
+    // What's come in that isn't a whole line yet, and the replies that
+    // haven't gone out yet
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl Control {
+    pub fn open(path: &Path) -> std::io::Result<Control> {
+        Ok(Control {
+            listener: sys::Listener::bind(path)?,
+            clients: Vec::new(),
+        })
+    }
+
+    // Takes new connections and runs any commands that have come in with
+    // `run`, answering each. Says whether one of them was to quit.
+    pub fn serve(
+        &mut self,
+        mut run: impl FnMut(&[&str]) -> std::result::Result<Option<String>, String>,
+    ) -> bool {
+        while let Some(stream) = self.listener.accept() {
+            self.clients.push(Client {
+                stream,
+                input: Vec::new(),
+                output: Vec::new(),
+            });
+        }
+        let mut quit = false;
+        self.clients.retain_mut(|client| {
+            let mut buf = [0; 4096];
+            let open = loop {
+                if client.input.len() > BACKLOG {
+                    break true;
+                }
+                match client.stream.read(&mut buf) {
+                    Ok(0) => break false,
+                    Ok(n) => client.input.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break true,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(_) => break false,
+                }
+            };
+            while let Some(end) = client.input.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = client.input.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let reply = match words(&line) {
+                    Ok(words) => {
+                        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+                        run(&words)
+                    }
+                    Err(e) => Err(e),
+                };
+                let reply = match reply {
+                    Ok(None) => {
+                        quit = true;
+                        Value::Table(vec![("ok".to_string(), Value::Bool(true))])
+                    }
+                    Ok(Some(message)) => Value::Table(vec![
+                        ("ok".to_string(), Value::Bool(true)),
+                        ("message".to_string(), Value::Str(message)),
+                    ]),
+                    Err(e) => Value::Table(vec![
+                        ("ok".to_string(), Value::Bool(false)),
+                        ("error".to_string(), Value::Str(e)),
+                    ]),
+                };
+                client.output.extend_from_slice(json(&reply).as_bytes());
+                client.output.push(b'\n');
+            }
+            // Out as far as the socket takes, without waiting
+            while !client.output.is_empty() {
+                match client.stream.write(&client.output) {
+                    Ok(0) => return false,
+                    Ok(n) => drop(client.output.drain(..n)),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(_) => return false,
+                }
+            }
+            if client.input.len() > BACKLOG || client.output.len() > BACKLOG {
+                tracing::warn!("control: dropping a client too far behind");
+                return false;
+            }
+            open
+        });
+        quit
+    }
+}
+
+// A JSON command as the words of a console command.
+fn words(line: &str) -> std::result::Result<Vec<String>, String> {
+    let command = match config::parse_value(line) {
+        Some(command @ Value::Table(_)) => command,
+        _ => return Err("expected a JSON object".to_string()),
+    };
+    let field = |key: &str| -> std::result::Result<Vec<String>, String> {
+        match command.get(key) {
+            Some(Value::Str(s)) => Ok(vec![s.clone()]),
+            Some(Value::List(items)) => items.iter().map(number).collect(),
+            Some(v) => number(v).map(|n| vec![n]),
+            None => Err(format!("missing \"{}\"", key)),
+        }
+    };
+    let cmd = match command.get("cmd") {
+        Some(Value::Str(cmd)) => cmd.clone(),
+        _ => return Err("missing \"cmd\"".to_string()),
+    };
+    let mut words = vec![cmd.clone()];
+    match cmd.as_str() {
+        "set" => {
+            words.extend(field("name")?);
+            words.extend(field("value")?);
+        }
+        "light" => {
+            words.push("dir".to_string());
+            words.extend(field("dir")?);
+        }
+        "shape" => words.extend(field("shape")?),
+        "palette" => words.extend(field("name")?),
+        "screenshot" | "save" => words.extend(field("path")?),
+        _ => {}
+    }
+    Ok(words)
+}
+
+fn number(value: &Value) -> std::result::Result<String, String> {
+    match value {
+        Value::Int(_) | Value::Float(_) => Ok(value.to_string()),
+        _ => Err(format!("expected a number, not {}", value)),
+    }
+}
+
+// Replies only need strings and booleans, and the object around them.
+fn json(value: &Value) -> String {
+    match value {
+        Value::Table(entries) => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(k, v)| format!("{}: {}", config::quote(k), json(v)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        Value::Str(s) => config::quote(s),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+
+    pub type Stream = UnixStream;
+
+    pub struct Listener {
+        listener: UnixListener,
+        path: PathBuf,
+    }
+
+    impl Listener {
+        // Clears away a socket left over from a run that didn't get to tidy
+        // up, but nothing else.
+        pub fn bind(path: &Path) -> std::io::Result<Listener> {
+            if let Ok(meta) = std::fs::symlink_metadata(path) {
+                if meta.file_type().is_socket() && UnixStream::connect(path).is_err() {
+                    std::fs::remove_file(path)?;
+                }
+            }
+            let listener = UnixListener::bind(path)?;
+            listener.set_nonblocking(true)?;
+            Ok(Listener {
+                listener,
+                path: path.to_path_buf(),
+            })
+        }
+
+        pub fn accept(&mut self) -> Option<Stream> {
+            let (stream, _) = self.listener.accept().ok()?;
+            stream.set_nonblocking(true).ok()?;
+            Some(stream)
+        }
+    }
+
+    impl Drop for Listener {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+// Named pipes in non-blocking (PIPE_NOWAIT) mode: one instance waits for a
+// client, and once one connects it's handed over and another starts waiting.
+#[cfg(windows)]
+mod sys {
+    use std::io::{Error, ErrorKind};
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, GetLastError, ERROR_BROKEN_PIPE, ERROR_NO_DATA, ERROR_PIPE_CONNECTED, HANDLE,
+        INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{ReadFile, WriteFile, PIPE_ACCESS_DUPLEX};
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_NOWAIT, PIPE_READMODE_BYTE,
+        PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES,
+    };
+
+    pub struct Stream(HANDLE);
+
+    pub struct Listener {
+        // NUL-terminated UTF-16, as CreateNamedPipeW wants it
+        name: Vec<u16>,
+        waiting: Stream,
+    }
+
+    impl Listener {
+        // `path` is the pipe's name, with or without \\.\pipe\ in front.
+        pub fn bind(path: &Path) -> std::io::Result<Listener> {
+            let path = path.to_string_lossy();
+            let name = match path.starts_with(r"\\.\pipe\") {
+                true => path.to_string(),
+                false => format!(r"\\.\pipe\{}", path),
+            };
+            let name: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
+            let waiting = create(&name)?;
+            Ok(Listener { name, waiting })
+        }
+
+        pub fn accept(&mut self) -> Option<Stream> {
+            let connected = unsafe { ConnectNamedPipe(self.waiting.0, std::ptr::null_mut()) } != 0;
+            let error = unsafe { GetLastError() };
+            if !connected && error != ERROR_PIPE_CONNECTED && error != ERROR_NO_DATA {
+                return None;
+            }
+            let next = create(&self.name).ok()?;
+            Some(std::mem::replace(&mut self.waiting, next))
+        }
+    }
+
+    fn create(name: &[u16]) -> std::io::Result<Stream> {
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_NOWAIT,
+                PIPE_UNLIMITED_INSTANCES,
+                4096,
+                4096,
+                0,
+                std::ptr::null(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(Error::last_os_error());
+        }
+        Ok(Stream(handle))
+    }
+
+    impl std::io::Read for Stream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let mut n = 0;
+            let len = buf.len().min(u32::MAX as usize) as u32;
+            let ok =
+                unsafe { ReadFile(self.0, buf.as_mut_ptr(), len, &mut n, std::ptr::null_mut()) };
+            if ok != 0 {
+                return Ok(n as usize);
+            }
+            match unsafe { GetLastError() } {
+                ERROR_NO_DATA => Err(ErrorKind::WouldBlock.into()),
+                ERROR_BROKEN_PIPE => Ok(0),
+                _ => Err(Error::last_os_error()),
+            }
+        }
+    }
+
+    impl std::io::Write for Stream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let mut n = 0;
+            let len = buf.len().min(u32::MAX as usize) as u32;
+            let ok = unsafe { WriteFile(self.0, buf.as_ptr(), len, &mut n, std::ptr::null_mut()) };
+            match ok {
+                0 => Err(Error::last_os_error()),
+                _ => Ok(n as usize),
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Drop for Stream {
+        fn drop(&mut self) {
+            unsafe {
+                DisconnectNamedPipe(self.0);
+                CloseHandle(self.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::color::ColorDepth;
+use crate::color::{self, Rgb};
+use crate::target::{CellBuffer, Rect, RenderTarget};
+use alloc::format;
+use alloc::string::String;
+use core::ops::Range;
+
+// Escape sequences drawing the cells over whatever is on screen, for
+// terminals and terminal emulators that take ANSI input. Each row is
+// positioned explicitly so nothing depends on newline handling or wrapping.
+pub fn encode(cells: &CellBuffer, depth: ColorDepth, out: &mut String) {
+    encode_at(cells, (0, 0), depth, out);
+}
+
+// The same, with the cells' top left corner at `origin` on screen.
+pub fn encode_at(
+    cells: &CellBuffer,
+    (x0, y0): (usize, usize),
+    depth: ColorDepth,
+    out: &mut String,
+) {
+    let (_, sy) = cells.dimensions();
+    for y in 0..sy {
+        out.push_str(&format!("\x1b[{};{}H", y0 + y + 1, x0 + 1));
+        encode_row(cells, y, depth, out);
+    }
+}
+
+// Escape sequences redrawing just the rectangles of the cells given, as
+// Buffers::swap() finds, over the last frame on screen.
+pub fn encode_rects(
+    cells: &CellBuffer,
+    (x0, y0): (usize, usize),
+    rects: &[Rect],
+    depth: ColorDepth,
+    out: &mut String,
+) {
+    for rect in rects {
+        for y in rect.y..rect.y + rect.h {
+            out.push_str(&format!("\x1b[{};{}H", y0 + y + 1, x0 + rect.x + 1));
+            encode_span(cells, y, rect.x..rect.x + rect.w, depth, out);
+        }
+    }
+}
+
+// One row of cells where the cursor is, back to the default colors after.
+pub fn encode_row(cells: &CellBuffer, y: usize, depth: ColorDepth, out: &mut String) {
+    let (sx, _) = cells.dimensions();
+    encode_span(cells, y, 0..sx, depth, out);
+}
+
+// The same for some of the row's columns.
+pub fn encode_span(
+    cells: &CellBuffer,
+    y: usize,
+    columns: Range<usize>,
+    depth: ColorDepth,
+    out: &mut String,
+) {
+    let mut current: Option<Rgb> = None;
+    for x in columns {
+        let cell = cells.get(x, y);
+        let glyph = match cells.shown(x, y) {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+        let color = cell.color.filter(|_| depth != ColorDepth::Mono);
+        if color != current {
+            match color {
+                Some(c) => out.push_str(&c.sgr(depth).unwrap()),
+                None => out.push_str(color::RESET),
+            }
+            current = color;
+        }
+        out.push(glyph);
+    }
+    if current.is_some() {
+        out.push_str(color::RESET);
+    }
+}
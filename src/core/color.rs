@@ -0,0 +1,125 @@
+use alloc::format;
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use nalgebra::ComplexField;
+
+// How many colors a target can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    Mono,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    // None for auto
+    pub fn parse(name: &str) -> Option<Option<ColorDepth>> {
+        match name {
+            "auto" => Some(None),
+            "mono" => Some(Some(ColorDepth::Mono)),
+            "16" => Some(Some(ColorDepth::Ansi16)),
+            "256" => Some(Some(ColorDepth::Ansi256)),
+            "truecolor" => Some(Some(ColorDepth::TrueColor)),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorDepth::Mono => "mono",
+            ColorDepth::Ansi16 => "16",
+            ColorDepth::Ansi256 => "256",
+            ColorDepth::TrueColor => "truecolor",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+// xterm's default values for the 16 basic colors.
+const ANSI16: [Rgb; 16] = [
+    Rgb(0, 0, 0),
+    Rgb(205, 0, 0),
+    Rgb(0, 205, 0),
+    Rgb(205, 205, 0),
+    Rgb(0, 0, 238),
+    Rgb(205, 0, 205),
+    Rgb(0, 205, 205),
+    Rgb(229, 229, 229),
+    Rgb(127, 127, 127),
+    Rgb(255, 0, 0),
+    Rgb(0, 255, 0),
+    Rgb(255, 255, 0),
+    Rgb(92, 92, 255),
+    Rgb(255, 0, 255),
+    Rgb(0, 255, 255),
+    Rgb(255, 255, 255),
+];
+
+fn dist2(a: Rgb, b: Rgb) -> i32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32) * (x as i32 - y as i32);
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+impl Rgb {
+    // From "#rrggbb" (the # is optional).
+    pub fn from_hex(s: &str) -> Option<Rgb> {
+        let s = s.strip_prefix('#').unwrap_or(s);
+        if s.len() != 6 || !s.is_ascii() {
+            return None;
+        }
+        let c = |i: usize| u8::from_str_radix(&s[i..i + 2], 16).ok();
+        Some(Rgb(c(0)?, c(2)?, c(4)?))
+    }
+
+    // Linear blend; t = 0 gives self, t = 1 gives other.
+    pub fn lerp(self, other: Rgb, t: f32) -> Rgb {
+        let t = t.clamp(0.0, 1.0);
+        let l = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Rgb(l(self.0, other.0), l(self.1, other.1), l(self.2, other.2))
+    }
+
+    fn ansi16(self) -> usize {
+        (0..16).min_by_key(|&i| dist2(self, ANSI16[i])).unwrap()
+    }
+
+    fn ansi256(self) -> u8 {
+        // Nearest of the 6x6x6 cube and the grey ramp.
+        let level = |c: u8| match c {
+            0..=47 => 0,
+            48..=114 => 1,
+            _ => (c - 35) / 40,
+        };
+        let cube_value = |l: u8| if l == 0 { 0 } else { 55 + 40 * l };
+        let (r, g, b) = (level(self.0), level(self.1), level(self.2));
+        let cube = Rgb(cube_value(r), cube_value(g), cube_value(b));
+        let avg = (self.0 as i32 + self.1 as i32 + self.2 as i32) / 3;
+        let grey_ix = ((avg - 3) / 10).clamp(0, 23) as u8;
+        let grey_value = 8 + 10 * grey_ix;
+        let grey = Rgb(grey_value, grey_value, grey_value);
+        if dist2(self, grey) < dist2(self, cube) {
+            232 + grey_ix
+        } else {
+            16 + 36 * r + 6 * g + b
+        }
+    }
+
+    // Escape sequence selecting this as the foreground color, if the terminal
+    // can show color at all.
+    pub fn sgr(self, depth: ColorDepth) -> Option<String> {
+        match depth {
+            ColorDepth::Mono => None,
+            ColorDepth::Ansi16 => {
+                let ix = self.ansi16();
+                let code = if ix < 8 { 30 + ix } else { 90 + ix - 8 };
+                Some(format!("\x1b[{}m", code))
+            }
+            ColorDepth::Ansi256 => Some(format!("\x1b[38;5;{}m", self.ansi256())),
+            ColorDepth::TrueColor => Some(format!("\x1b[38;2;{};{};{}m", self.0, self.1, self.2)),
+        }
+    }
+}
+
+pub const RESET: &str = "\x1b[0m";
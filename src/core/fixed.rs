@@ -0,0 +1,225 @@
+// The renderer in 16.16 fixed point, for machines without a fast FPU (build
+// with `--features fixed`). Only the per-frame and per-ring setup use floats;
+// each point is integer adds and multiplies, a division for perspective and
+// an integer square root. Plain Phong shading is in integers too; other
+// shaders, palettes and debug views shade each point in floats as usual.
+use crate::framebuffer::FrameBuffer;
+use crate::render::{sphere_visible, Camera, Donut, Params, Sample, NEAR};
+use crate::shader::Shade;
+use crate::{Point, Vec3};
+
+type Fx = i32;
+type V3 = [Fx; 3];
+
+const ONE: Fx = 1 << 16;
+
+pub(crate) fn fx(x: f32) -> Fx {
+    (x * ONE as f32) as Fx
+}
+
+fn to_f32(x: Fx) -> f32 {
+    x as f32 / ONE as f32
+}
+
+fn mul(a: Fx, b: Fx) -> Fx {
+    ((a as i64 * b as i64) >> 16) as Fx
+}
+
+// a * x + b * y + c, each component
+fn combine(a: V3, x: Fx, b: V3, y: Fx, c: V3) -> V3 {
+    [
+        mul(a[0], x) + mul(b[0], y) + c[0],
+        mul(a[1], x) + mul(b[1], y) + c[1],
+        mul(a[2], x) + mul(b[2], y) + c[2],
+    ]
+}
+
+// In 32.32
+fn dot(a: V3, b: V3) -> i64 {
+    (0..3).map(|i| a[i] as i64 * b[i] as i64).sum()
+}
+
+fn isqrt(n: u64) -> u64 {
+    let (mut root, mut rem) = (0u64, n);
+    let mut bit = 1u64 << 62;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+fn vec(v: Vec3) -> V3 {
+    [fx(v.x), fx(v.y), fx(v.z)]
+}
+
+pub(crate) fn render(donut: &Donut, params: &Params, framebuffer: &mut FrameBuffer) {
+    let Params {
+        r1,
+        r2,
+        light_dir,
+        cam_dist,
+        gain,
+        ..
+    } = *params;
+    let (sx, sy) = (framebuffer.sx, framebuffer.sy);
+    let camera = Camera::new(params, sx, sy);
+    let object_eye = camera.eye * donut.transform;
+    if !sphere_visible(
+        &object_eye.transform_point(&Point::origin()),
+        r1.abs() + r2.abs(),
+        camera.reach,
+    ) {
+        return;
+    }
+
+    // Everything happens in eye space, where the camera is at the origin.
+    let light = vec(camera.eye.transform_vector(&light_dir));
+    let col = |i: usize| {
+        let m = &object_eye;
+        vec(Vec3::new(m[(0, i)], m[(1, i)], m[(2, i)]))
+    };
+    let (x_axis, y_axis, z_axis, origin) = (col(0), col(1), col(2), col(3));
+    let center = (fx(0.5 * sx as f32), fx(0.5 * sy as f32));
+    let zoom = (fx(camera.zoom.0), fx(camera.zoom.1));
+    let (near, gain) = (fx(NEAR), fx(gain));
+    let (r1, r2) = (fx(r1), fx(r2));
+    let phong = donut.palette.is_none() && donut.debug_view.is_none() && donut.shader.is_phong();
+    // Eye space directions back to world space, for shading in floats
+    let to_world = camera.eye.fixed_view::<3, 3>(0, 0).transpose();
+    let world = |v: V3| to_world * Vec3::new(to_f32(v[0]), to_f32(v[1]), to_f32(v[2]));
+    let (n1, n2) = (donut.n1 as f32, donut.n2 as f32);
+
+    let _span = tracing::trace_span!("fixed").entered();
+    let subdivisions = donut.subdivisions();
+    for (i1, &(cos1, sin1)) in subdivisions.fixed_rings.iter().enumerate() {
+        // The ring's own x axis, after turning about z
+        let ring_x = combine(x_axis, cos1, y_axis, sin1, [0; 3]);
+        let ring_center = combine(ring_x, r1, [0; 3], 0, origin);
+        let ring_center = Point::new(
+            to_f32(ring_center[0]),
+            to_f32(ring_center[1]),
+            to_f32(ring_center[2]),
+        );
+        if !sphere_visible(&ring_center, to_f32(r2).abs(), camera.reach) {
+            continue;
+        }
+
+        for (i2, &(cos2, sin2)) in subdivisions.fixed_circle.iter().enumerate() {
+            let p = combine(ring_x, mul(r2, cos2) + r1, z_axis, mul(r2, sin2), origin);
+            let mut n = combine(ring_x, cos2, z_axis, sin2, [0; 3]);
+
+            // Behind the camera or too close to it
+            let depth = -p[2];
+            if depth <= near {
+                continue;
+            }
+            let x = center.0 + (zoom.0 as i64 * p[0] as i64 / depth as i64) as Fx;
+            let y = center.1 - (zoom.1 as i64 * p[1] as i64 / depth as i64) as Fx;
+            if x < 0 || y < 0 || x >= sx as Fx * ONE || y >= sy as Fx * ONE {
+                continue;
+            }
+
+            // Facing the camera when the normal points back towards the origin
+            let mut facing = -dot(n, p);
+            if facing < 0 {
+                if !donut.two_sided {
+                    continue;
+                }
+                n = [-n[0], -n[1], -n[2]];
+                facing = -facing;
+            }
+
+            // Perspective depth, as the z-buffer expects
+            let z = ONE - (((near as i64) << 16) / depth as i64) as Fx;
+            let shade = if phong {
+                // Phong, as in shader.rs, with the unit vector to the viewer
+                // -p / |p|
+                let len = isqrt(dot(p, p) as u64).max(1) as i64;
+                let a = ((dot(n, light) >> 16) as Fx).max(0);
+                let nv = (facing / len) as Fx;
+                let lv = (-dot(light, p) / len) as Fx;
+                let r = mul(2 * a, nv) - lv;
+                let specular = mul(gain, mul(ONE / 4, mul(r, mul(r, r))));
+                let brightness = (mul(gain, mul(fx(0.75), a)) + specular).min(fx(0.99));
+                Shade {
+                    brightness: to_f32(brightness),
+                    specular: to_f32(specular),
+                    color: None,
+                }
+            } else {
+                let sample = Sample {
+                    p_screen: Vec3::new(to_f32(x), to_f32(y), to_f32(z)),
+                    n: world(n).normalize(),
+                    cam_vec: -world(p).normalize(),
+                    uv: (i1 as f32 / n1, i2 as f32 / n2),
+                    spread: 0.5 + (to_f32(depth) - cam_dist) / (2.0 * to_f32(r1 + r2).abs()),
+                };
+                donut.shade(&sample, light_dir, params.gain)
+            };
+            if shade.brightness > 0.0 {
+                let ix = framebuffer.dither(to_f32(x), sx);
+                let iy = framebuffer.dither(to_f32(y), sy);
+                framebuffer.poke_if(ix, iy, to_f32(z), shade);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Rgb;
+    use crate::render::DebugView;
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+
+    fn draw(donut: &Donut) -> Vec<(f32, Option<Rgb>)> {
+        let mut framebuffer = FrameBuffer::new();
+        framebuffer.seed(0);
+        framebuffer.clear(60, 30);
+        render(donut, &Params::default(), &mut framebuffer);
+        (0..framebuffer.sy)
+            .flat_map(|y| (0..framebuffer.sx).map(move |x| (x, y)))
+            .filter_map(|(x, y)| framebuffer.shading(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn shades_with_the_donuts_own_look() {
+        // Coarse, so debug builds render it quickly
+        let mut donut = Donut::new();
+        donut.n1 = 120;
+        donut.n2 = 48;
+
+        // Plain Phong stays in integers, uncolored
+        let phong = draw(&donut);
+        assert!(!phong.is_empty());
+        assert!(phong.iter().all(|&(_, color)| color.is_none()));
+
+        donut.shader = Box::new(|_: Vec3, _: Vec3, _: Vec3, _: (f32, f32), _: f32| Shade {
+            brightness: 0.5,
+            specular: 0.0,
+            color: Some(Rgb(10, 20, 30)),
+        });
+        let flat = draw(&donut);
+        assert!(!flat.is_empty());
+        assert!(flat
+            .iter()
+            .all(|&(level, color)| (level - 0.5).abs() < 1e-6 && color == Some(Rgb(10, 20, 30))));
+
+        donut.shader = Box::new(crate::shader::Phong);
+        donut.debug_view = Some(DebugView::Normals);
+        let normals = draw(&donut);
+        assert!(!normals.is_empty());
+        assert!(normals.iter().all(|&(_, color)| color.is_some()));
+    }
+}
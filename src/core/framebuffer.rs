@@ -0,0 +1,342 @@
+use crate::color::Rgb;
+use crate::palette::hue;
+use crate::ramp;
+use crate::shader::Shade;
+use crate::target::{is_wide, Cell, RenderTarget};
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use nalgebra::ComplexField;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Samples at most this fraction further from the camera than the nearest one
+// in a cell count towards its shade.
+const DEPTH_BAND: f32 = 0.03;
+
+// Cells averaging at least this much specular light are highlights, which
+// are fully lit at twice it.
+const HIGHLIGHT: f32 = 0.1;
+
+// Running totals of the samples that make up one cell.
+#[derive(Clone, Copy, Default)]
+struct Coverage {
+    brightness: f32,
+    specular: f32,
+    samples: u32,
+    rgb: [f32; 3],
+    colored: u32,
+}
+
+impl Coverage {
+    fn add(&mut self, shade: Shade) {
+        self.brightness += shade.brightness;
+        self.specular += shade.specular;
+        self.samples += 1;
+        if let Some(Rgb(r, g, b)) = shade.color {
+            self.rgb[0] += r as f32;
+            self.rgb[1] += g as f32;
+            self.rgb[2] += b as f32;
+            self.colored += 1;
+        }
+    }
+
+    fn color(&self) -> Option<Rgb> {
+        if self.colored == 0 {
+            return None;
+        }
+        let c = |sum: f32| (sum / self.colored as f32).round() as u8;
+        Some(Rgb(c(self.rgb[0]), c(self.rgb[1]), c(self.rgb[2])))
+    }
+}
+
+fn dither(rng: &mut Option<StdRng>, steady: bool, i: f32, clip: usize) -> usize {
+    let u = match rng {
+        _ if steady => 0.5,
+        Some(rng) => rng.gen::<f32>(),
+        #[cfg(feature = "std")]
+        None => rand::thread_rng().gen::<f32>(),
+        // Nothing to seed from without std, and any seed dithers as well
+        #[cfg(not(feature = "std"))]
+        None => rng.insert(StdRng::seed_from_u64(0)).gen::<f32>(),
+    } - 0.5;
+    let r = (i + u).round();
+    if r < 0.0 {
+        0
+    } else {
+        let r_i = r as usize;
+        if r_i >= clip {
+            clip - 1
+        } else {
+            r_i
+        }
+    }
+}
+
+// Glyphs and depth for each cell as the donut is rasterized, before they go
+// out to a render target.
+pub struct FrameBuffer {
+    // Glyphs for increasing brightness
+    pub ramp: Vec<char>,
+    // Glyphs for increasing specular highlight. When set, highlights are drawn
+    // with these, in white, instead of on the ramp.
+    pub highlights: Option<Vec<char>>,
+    brightness: Vec<char>,
+    colors: Vec<Option<Rgb>>,
+    z_buffer: Vec<f32>,
+    coverage: Vec<Coverage>,
+    // Samples that landed in each cell, hidden or not
+    overdraw: Vec<u32>,
+    // What motion blur leaves behind of earlier frames in each cell: how much
+    // is left, and its brightness and color. Kept from one frame to the next
+    // unless the size changes.
+    pub(crate) trail: Vec<(f32, f32, Option<Rgb>)>,
+    // Rounds to the nearest glyph without dithering: banding, but no
+    // shimmer as things move
+    pub steady: bool,
+    // Dithering noise; thread_rng unless seeded
+    rng: Option<StdRng>,
+    pub sx: usize,
+    pub sy: usize,
+}
+
+impl Default for FrameBuffer {
+    fn default() -> FrameBuffer {
+        FrameBuffer::new()
+    }
+}
+
+impl FrameBuffer {
+    pub fn new() -> FrameBuffer {
+        FrameBuffer {
+            sx: 0,
+            sy: 0,
+            ramp: ramp::CLASSIC.chars().collect(),
+            highlights: None,
+            brightness: Vec::new(),
+            colors: Vec::new(),
+            z_buffer: Vec::new(),
+            coverage: Vec::new(),
+            overdraw: Vec::new(),
+            trail: Vec::new(),
+            steady: false,
+            rng: None,
+        }
+    }
+
+    // Makes the dithering repeatable, so the same scene renders the same frames.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Some(StdRng::seed_from_u64(seed));
+    }
+
+    pub(crate) fn dither(&mut self, i: f32, clip: usize) -> usize {
+        dither(&mut self.rng, self.steady, i, clip)
+    }
+
+    // A seed for dithering done elsewhere (on the GPU), or None if steady.
+    #[cfg(feature = "gpu")]
+    pub(crate) fn dither_seed(&mut self) -> Option<u32> {
+        match &mut self.rng {
+            _ if self.steady => None,
+            Some(rng) => Some(rng.gen()),
+            None => Some(rand::thread_rng().gen()),
+        }
+    }
+
+    // Starts a frame for a target `sx` columns across, which is `sx` cells
+    // unless the ramp has wide glyphs, which take two columns each.
+    pub fn clear(&mut self, sx: usize, sy: usize) {
+        let sx = (sx / self.columns()).max(1);
+        if (sx, sy) != (self.sx, self.sy) {
+            self.trail.clear();
+        }
+        self.sx = sx;
+        self.sy = sy;
+        let size = self.sy * self.sx;
+        self.z_buffer.clear();
+        self.z_buffer.resize(size, 1.0);
+        self.coverage.clear();
+        self.coverage.resize(size, Coverage::default());
+        self.overdraw.clear();
+        self.overdraw.resize(size, 0);
+        self.brightness.clear();
+        self.brightness.resize(size, ' ');
+        self.colors.clear();
+        self.colors.resize(size, None);
+    }
+
+    // Adds a sample to the cell, which is shaded with the average of the
+    // samples near the front; z is depth in [0, 1], nearest first.
+    pub fn poke_if(&mut self, x: usize, y: usize, z: f32, shade: Shade) {
+        let ix = y * self.sx + x;
+        self.overdraw[ix] += 1;
+
+        // Perspective depth goes as 1 - near / distance, so distances compare
+        // by ratios of 1 - z.
+        let (near, nearest) = (1.0 - z, 1.0 - self.z_buffer[ix]);
+        if near < nearest * (1.0 - DEPTH_BAND) {
+            return;
+        }
+        if near * (1.0 - DEPTH_BAND) > nearest {
+            // Far enough in front to hide everything so far
+            self.coverage[ix] = Coverage::default();
+        }
+        self.z_buffer[ix] = self.z_buffer[ix].min(z);
+        self.coverage[ix].add(shade);
+        self.paint(ix);
+    }
+
+    // Picks a cell's glyph and color from the samples shading it.
+    fn paint(&mut self, ix: usize) {
+        let coverage = &self.coverage[ix];
+        let samples = coverage.samples as f32;
+        let (average, specular) = (coverage.brightness / samples, coverage.specular / samples);
+        let (glyphs, level, color) = match &self.highlights {
+            Some(highlights) if specular >= HIGHLIGHT => (
+                highlights,
+                specular / HIGHLIGHT - 1.0,
+                Some(Rgb(255, 255, 255)),
+            ),
+            _ => (&self.ramp, average, coverage.color()),
+        };
+        let n = glyphs.len();
+        let val_ix = dither(&mut self.rng, self.steady, level * (n as f32), n);
+        self.brightness[ix] = glyphs[val_ix];
+        self.colors[ix] = color;
+    }
+
+    // The average brightness and color of the samples shading a cell, or
+    // None if nothing landed there.
+    pub fn shading(&self, x: usize, y: usize) -> Option<(f32, Option<Rgb>)> {
+        let coverage = &self.coverage[y * self.sx + x];
+        (coverage.samples > 0).then(|| {
+            (
+                coverage.brightness / coverage.samples as f32,
+                coverage.color(),
+            )
+        })
+    }
+
+    // A cell's average brightness, color and depth, for post-processing, or
+    // None if nothing landed there.
+    pub(crate) fn cell(&self, ix: usize) -> Option<(f32, Option<Rgb>, f32)> {
+        let coverage = &self.coverage[ix];
+        (coverage.samples > 0).then(|| {
+            (
+                coverage.brightness / coverage.samples as f32,
+                coverage.color(),
+                self.z_buffer[ix],
+            )
+        })
+    }
+
+    // Shades a cell as if its samples averaged `level` brightness, keeping
+    // their highlights and color, for post-processing that later passes (and
+    // video) should see.
+    pub(crate) fn expose(&mut self, ix: usize, level: f32) {
+        let coverage = &mut self.coverage[ix];
+        if coverage.samples > 0 {
+            coverage.brightness = level * coverage.samples as f32;
+            self.paint(ix);
+        }
+    }
+
+    // Shades a cell with one sample of `level` and `color`, or empties it for
+    // None, and redraws it on the ramp, so later passes and video see it too.
+    pub(crate) fn reshade(&mut self, ix: usize, level: Option<f32>, color: Option<Rgb>) {
+        self.coverage[ix] = Coverage::default();
+        if let Some(level) = level {
+            self.coverage[ix].add(Shade {
+                brightness: level,
+                specular: 0.0,
+                color,
+            });
+        }
+        let n = self.ramp.len();
+        self.brightness[ix] = match level {
+            Some(level) => self.ramp[dither(&mut self.rng, self.steady, level * n as f32, n)],
+            None => ' ',
+        };
+        self.colors[ix] = color;
+    }
+
+    // Replaces the frame with a heat map of how many samples landed in each
+    // cell, from blue to red.
+    pub fn show_overdraw(&mut self) {
+        let most = self.overdraw.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let n = self.ramp.len();
+        for ix in 0..self.overdraw.len() {
+            if self.overdraw[ix] > 0 {
+                let level = self.overdraw[ix] as f32 / most;
+                let val_ix = dither(&mut self.rng, self.steady, level * (n as f32), n);
+                self.brightness[ix] = self.ramp[val_ix];
+                self.colors[ix] = Some(hue(0.67 * (1.0 - level)));
+            }
+        }
+    }
+
+    // Columns each cell takes: two if any glyph is wide, so they all line up.
+    pub fn columns(&self) -> usize {
+        let mut glyphs = self.ramp.iter().chain(self.highlights.iter().flatten());
+        if glyphs.any(|&glyph| is_wide(glyph)) {
+            2
+        } else {
+            1
+        }
+    }
+
+    // Copies every cell out to the target. Cells the shader didn't color get
+    // the tint.
+    pub fn blit(&self, target: &mut dyn RenderTarget, tint: Option<Rgb>) {
+        let columns = self.columns();
+        for y in 0..self.sy {
+            for x in 0..self.sx {
+                let ix = y * self.sx + x;
+                let glyph = self.brightness[ix];
+                let color = self.colors[ix].or(tint);
+                target.put(columns * x, y, Cell { glyph, color });
+                if columns == 2 {
+                    let tail = match is_wide(glyph) {
+                        true => Cell {
+                            glyph: Cell::TAIL,
+                            color,
+                        },
+                        false => Cell::BLANK,
+                    };
+                    target.put(2 * x + 1, y, tail);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shade(brightness: f32, color: Option<Rgb>) -> Shade {
+        Shade {
+            brightness,
+            specular: 0.0,
+            color,
+        }
+    }
+
+    #[test]
+    fn coverage_averages_samples_in_a_cell() {
+        let mut framebuffer = FrameBuffer::new();
+        framebuffer.clear(4, 2);
+        framebuffer.poke_if(1, 1, 0.5, shade(0.2, Some(Rgb(100, 0, 0))));
+        framebuffer.poke_if(1, 1, 0.5, shade(0.6, Some(Rgb(200, 0, 40))));
+        let (level, color) = framebuffer.shading(1, 1).unwrap();
+        assert!((level - 0.4).abs() < 1e-6);
+        assert_eq!(color, Some(Rgb(150, 0, 20)));
+        assert_eq!(framebuffer.shading(0, 1), None);
+
+        // Samples well behind don't count, and ones well in front hide the
+        // rest
+        framebuffer.poke_if(1, 1, 0.9, shade(0.9, None));
+        assert!((framebuffer.shading(1, 1).unwrap().0 - 0.4).abs() < 1e-6);
+        framebuffer.poke_if(1, 1, 0.1, shade(0.8, None));
+        assert_eq!(framebuffer.shading(1, 1), Some((0.8, None)));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::color::Rgb;
+use crate::target::{is_wide, Cell, RenderTarget};
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Frames as pixels, for video: each cell a block of `cell` pixels. Glyphs
+// from the ramp light the whole block by where they are on it, so the donut
+// shows as solid shading rather than letters; text is drawn in a 5x7 font.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    // Rows of RGB triples, top first
+    pub rgb: Vec<u8>,
+    // Pixels across and down each cell
+    pub cell: (usize, usize),
+    // The glyphs shading cells, as the framebuffer has them
+    pub ramp: Vec<char>,
+    pub highlights: Option<Vec<char>>,
+}
+
+impl Image {
+    // An image of `sx` by `sy` cells.
+    pub fn new((sx, sy): (usize, usize), cell: (usize, usize)) -> Image {
+        let (width, height) = (sx * cell.0, sy * cell.1);
+        Image {
+            width,
+            height,
+            rgb: vec![0; 3 * width * height],
+            cell,
+            ramp: Vec::new(),
+            highlights: None,
+        }
+    }
+
+    // How lit a glyph is, if it's one the framebuffer shades with.
+    fn level(&self, glyph: char) -> Option<f32> {
+        if let Some(i) = self.ramp.iter().position(|&g| g == glyph) {
+            return Some(i as f32 / (self.ramp.len() - 1).max(1) as f32);
+        }
+        let mut highlights = self.highlights.iter().flatten();
+        highlights.any(|&g| g == glyph).then_some(1.0)
+    }
+
+    // Fills `w` cells across from (x, y), pixel by pixel, with `lit` picking
+    // the color of each pixel from its place in the first cell.
+    fn fill(&mut self, (x, y): (usize, usize), w: usize, lit: impl Fn(usize, usize) -> [u8; 3]) {
+        let (left, top) = (x * self.cell.0, y * self.cell.1);
+        let right = (left + w * self.cell.0).min(self.width);
+        for py in top..top + self.cell.1 {
+            for px in left..right {
+                let ix = 3 * (py * self.width + px);
+                self.rgb[ix..ix + 3].copy_from_slice(&lit(px - left, py - top));
+            }
+        }
+    }
+
+    // A glyph from the font, in its color (white by default) on black.
+    fn letter(&mut self, x: usize, y: usize, glyph: char, color: Option<Rgb>) {
+        let Rgb(r, g, b) = color.unwrap_or(Rgb(255, 255, 255));
+        let columns = glyph_columns(glyph);
+        // The glyph in the middle of the cell, doubled in height
+        let inset = self.cell.0.saturating_sub(5) / 2;
+        self.fill((x, y), 1, |px, py| {
+            let (gx, gy) = (px.wrapping_sub(inset), py / 2);
+            match gx < 5 && gy < 7 && columns[gx] >> gy & 1 != 0 {
+                true => [r, g, b],
+                false => [0; 3],
+            }
+        });
+    }
+}
+
+impl RenderTarget for Image {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width / self.cell.0, self.height / self.cell.1)
+    }
+
+    // Shading glyphs light their cell (both, if wide) in their color, or
+    // white; anything else is drawn as a letter.
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        let (sx, sy) = self.dimensions();
+        if x >= sx || y >= sy || cell.glyph == Cell::TAIL {
+            return;
+        }
+        match self.level(cell.glyph) {
+            Some(level) => {
+                let Rgb(r, g, b) = cell.color.unwrap_or(Rgb(255, 255, 255));
+                let l = |c: u8| (c as f32 * level) as u8;
+                let w = if is_wide(cell.glyph) { 2 } else { 1 };
+                self.fill((x, y), w, |_, _| [l(r), l(g), l(b)]);
+            }
+            None => self.letter(x, y, cell.glyph, cell.color),
+        }
+    }
+
+    // Text is always letters, even where they're on the ramp too.
+    fn put_str(&mut self, x: usize, y: usize, s: &str, color: Option<Rgb>) {
+        let (sx, sy) = self.dimensions();
+        for (i, glyph) in s.chars().enumerate() {
+            if x + i < sx && y < sy {
+                self.letter(x + i, y, glyph, color);
+            }
+        }
+    }
+
+    fn present(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+// Each glyph's five columns, left first, bit 0 at the top; anything outside
+// printable ASCII is a `?`.
+fn glyph_columns(c: char) -> [u8; 5] {
+    let ix = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    FONT[ix]
+}
+
+#[rustfmt::skip]
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00], // ' ' !
+    [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7f, 0x14, 0x7f, 0x14], // " #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62], // $ %
+    [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00], // & '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1c, 0x00], // ( )
+    [0x14, 0x08, 0x3e, 0x08, 0x14], [0x08, 0x08, 0x3e, 0x08, 0x08], // * +
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], // , -
+    [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02], // . /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00], // 0 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4b, 0x31], // 2 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39], // 4 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03], // 6 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1e], // 8 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00], // : ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14], // < =
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06], // > ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], [0x7e, 0x11, 0x11, 0x11, 0x7e], // @ A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22], // B C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], [0x7f, 0x49, 0x49, 0x49, 0x41], // D E
+    [0x7f, 0x09, 0x09, 0x09, 0x01], [0x3e, 0x41, 0x49, 0x49, 0x7a], // F G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00], // H I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41], // J K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], [0x7f, 0x02, 0x0c, 0x02, 0x7f], // L M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e], // N O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e], // P Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31], // R S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], [0x3f, 0x40, 0x40, 0x40, 0x3f], // T U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x3f, 0x40, 0x38, 0x40, 0x3f], // V W
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x07, 0x08, 0x70, 0x08, 0x07], // X Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x00], // Z [
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7f, 0x00], // \ ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40], // ^ _
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78], // ` a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20], // b c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], [0x38, 0x54, 0x54, 0x54, 0x18], // d e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], [0x0c, 0x52, 0x52, 0x52, 0x3e], // f g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00], // h i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], [0x7f, 0x10, 0x28, 0x44, 0x00], // j k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x18, 0x04, 0x78], // l m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], // n o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7c], // p q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20], // r s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], [0x3c, 0x40, 0x40, 0x20, 0x7c], // t u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], [0x3c, 0x40, 0x30, 0x40, 0x3c], // v w
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0c, 0x50, 0x50, 0x50, 0x3c], // x y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], // z {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00], // | }
+    [0x08, 0x04, 0x08, 0x10, 0x08],                                 // ~
+];
@@ -0,0 +1,625 @@
+use super::render::{sphere_visible, Camera, Sample, NEAR};
+use crate::framebuffer::FrameBuffer;
+use crate::render::{Donut, Params};
+use crate::{Point, Vec3};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::f32::consts::{PI, TAU};
+#[cfg(not(feature = "std"))]
+use nalgebra::{ComplexField, RealField};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Triangles as loaded: shared vertices, and their normals if the file had
+// them.
+struct Triangles {
+    positions: Vec<Point>,
+    normals: Option<Vec<Vec3>>,
+    faces: Vec<[usize; 3]>,
+}
+
+// A model loaded from an OBJ, STL or PLY file, drawn like the donut as points
+// on its surface: every vertex, plus points scattered over triangles too big
+// for their corners to cover. Scaled to fit the unit sphere, then drawn as
+// big as the donut.
+pub struct Mesh {
+    // Points on the surface, and their normals, in object space
+    pub points: Vec<(Point, Vec3)>,
+}
+
+impl Mesh {
+    // Reads a mesh, in whichever format the bytes are in. `samples` is about
+    // how many points cover the surface, as for the donut's n1 * n2.
+    pub fn parse(bytes: &[u8], samples: usize) -> Result<Mesh, String> {
+        let mut triangles = if bytes.starts_with(b"ply") {
+            ply(bytes)?
+        } else if binary_stl(bytes) {
+            stl_binary(bytes)?
+        } else {
+            let text = core::str::from_utf8(bytes).map_err(|_| "not a mesh file".to_string())?;
+            if text.trim_start().starts_with("solid") && text.contains("facet") {
+                stl_ascii(text)?
+            } else {
+                obj(text)?
+            }
+        };
+        if triangles.faces.is_empty() {
+            return Err("no triangles".to_string());
+        }
+        triangles.fit();
+        let normals = match triangles.normals.take() {
+            Some(normals) => normals,
+            None => triangles.vertex_normals(),
+        };
+        let mut points = triangles.scatter(&normals, samples);
+        // Scans can have millions of vertices, far more than show
+        if points.len() > samples + samples / 2 {
+            let before = points.len();
+            points = decimate(points, triangles.area(), samples);
+            tracing::info!("mesh decimated from {} points to {}", before, points.len());
+        }
+        Ok(Mesh { points })
+    }
+
+    // Draws the mesh with the donut's transform, shader, palette and debug
+    // view.
+    pub fn render(&self, look: &Donut, params: &Params, framebuffer: &mut FrameBuffer) {
+        if !look.visible {
+            return;
+        }
+        let (sx, sy) = (framebuffer.sx, framebuffer.sy);
+        let camera = Camera::new(params, sx, sy);
+        let radius = (params.r1 + params.r2).abs();
+        let object_eye = camera.eye * look.transform;
+        if !sphere_visible(
+            &object_eye.transform_point(&Point::origin()),
+            radius,
+            camera.reach,
+        ) {
+            return;
+        }
+        let to_clip = camera.clipspace * look.transform;
+        let scale = 0.5 * min(sx, sy) as f32;
+
+        let _span = tracing::trace_span!("mesh").entered();
+        for &(p, n) in &self.points {
+            let p = Point::from(p.coords * radius);
+            let p_clip = to_clip * p.to_homogeneous();
+            if p_clip.w <= NEAR {
+                continue;
+            }
+            let ndc = p_clip.xyz() / p_clip.w;
+            let depth = 0.5 * (ndc.z + 1.0);
+            if depth > 1.0 {
+                continue;
+            }
+            let p_screen = Vec3::new(
+                0.5 * sx as f32 + scale * ndc.x,
+                0.5 * sy as f32 - scale * ndc.y,
+                depth,
+            );
+            if p_screen.x < 0.0
+                || p_screen.y < 0.0
+                || p_screen.x >= sx as f32
+                || p_screen.y >= sy as f32
+            {
+                continue;
+            }
+            let p_world = look.transform.transform_point(&p);
+            let mut n = look.transform.transform_vector(&n).normalize();
+            let cam_vec = (camera.position - p_world.coords).normalize();
+            if cam_vec.dot(&n) < 0.0 {
+                if !look.two_sided {
+                    continue;
+                }
+                n = -n;
+            }
+            let sample = Sample {
+                p_screen,
+                n,
+                cam_vec,
+                uv: (0.5 + n.y.atan2(n.x) / TAU, 0.5 + n.z.asin() / PI),
+                spread: 0.5 + (p_clip.w - params.cam_dist) / (2.0 * radius.max(1e-3)),
+            };
+            let shade = look.shade(&sample, params.light_dir, params.gain);
+            if shade.brightness > 0.0 {
+                let ix = framebuffer.dither(p_screen.x, sx);
+                let iy = framebuffer.dither(p_screen.y, sy);
+                framebuffer.poke_if(ix, iy, depth, shade);
+            }
+        }
+    }
+}
+
+impl Triangles {
+    // Centers the mesh on its bounding box and scales it into the unit
+    // sphere.
+    fn fit(&mut self) {
+        let first = self.positions[0];
+        let (lo, hi) = self
+            .positions
+            .iter()
+            .fold((first, first), |(lo, hi), p| (lo.inf(p), hi.sup(p)));
+        let center = nalgebra::center(&lo, &hi);
+        let reach = self
+            .positions
+            .iter()
+            .map(|p| (p - center).norm())
+            .fold(0.0, f32::max)
+            .max(1e-6);
+        for p in &mut self.positions {
+            *p = Point::from((*p - center) / reach);
+        }
+    }
+
+    // Each vertex's normal, from the faces around it, bigger ones counting
+    // for more.
+    fn vertex_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![Vec3::zeros(); self.positions.len()];
+        for &[a, b, c] in &self.faces {
+            let (pa, pb, pc) = (self.positions[a], self.positions[b], self.positions[c]);
+            // Twice the area, pointing out of the front
+            let face = (pb - pa).cross(&(pc - pa));
+            for i in [a, b, c] {
+                normals[i] += face;
+            }
+        }
+        normals
+            .into_iter()
+            .map(|n| n.try_normalize(1e-12).unwrap_or_else(Vec3::z))
+            .collect()
+    }
+
+    fn face_area(&self, [a, b, c]: [usize; 3]) -> f32 {
+        let (pa, pb, pc) = (self.positions[a], self.positions[b], self.positions[c]);
+        0.5 * (pb - pa).cross(&(pc - pa)).norm()
+    }
+
+    fn area(&self) -> f32 {
+        self.faces.iter().map(|&face| self.face_area(face)).sum()
+    }
+
+    // Every vertex, then random points on each triangle in proportion to how
+    // much bigger it is than its share of `samples`.
+    fn scatter(&self, normals: &[Vec3], samples: usize) -> Vec<(Point, Vec3)> {
+        let density = samples as f32 / self.area().max(1e-9);
+        let mut points: Vec<(Point, Vec3)> = self
+            .positions
+            .iter()
+            .copied()
+            .zip(normals.iter().copied())
+            .collect();
+        // The same points every run
+        let mut rng = StdRng::seed_from_u64(0);
+        for face in &self.faces {
+            // Its corners are worth about half a point, shared as they are
+            let wanted = self.face_area(*face) * density - 0.5;
+            let mut n = wanted as usize;
+            if rng.gen::<f32>() < wanted - n as f32 {
+                n += 1;
+            }
+            let [a, b, c] = *face;
+            for _ in 0..n {
+                let (mut u, mut v) = (rng.gen::<f32>(), rng.gen::<f32>());
+                if u + v > 1.0 {
+                    (u, v) = (1.0 - u, 1.0 - v);
+                }
+                let w = 1.0 - u - v;
+                let p = Point::from(
+                    self.positions[a].coords * w
+                        + self.positions[b].coords * u
+                        + self.positions[c].coords * v,
+                );
+                let n = (normals[a] * w + normals[b] * u + normals[c] * v)
+                    .try_normalize(1e-12)
+                    .unwrap_or(normals[a]);
+                points.push((p, n));
+            }
+        }
+        points
+    }
+}
+
+// Thins out points to about `samples` by grid clustering: the points in each
+// cube of a grid, facing the same way, become one at their average. The grid
+// starts as fine as `samples` points spread evenly over `area` would be, and
+// coarsens until few enough are left.
+fn decimate(points: Vec<(Point, Vec3)>, area: f32, samples: usize) -> Vec<(Point, Vec3)> {
+    let mut size = (area / samples.max(1) as f32).sqrt().max(1e-6);
+    loop {
+        // Sums of positions and normals in each cube, split by which axis and
+        // way the normals mostly point so the two sides of thin walls stay
+        // apart
+        let mut cubes = BTreeMap::new();
+        for &(p, n) in &points {
+            let cube = |x: f32| (x / size).floor() as i32;
+            let (axis, _) = n.iamax_full();
+            let facing = 2 * axis as i32 + (n[axis] < 0.0) as i32;
+            let sums = cubes
+                .entry([cube(p.x), cube(p.y), cube(p.z), facing])
+                .or_insert((Vec3::zeros(), Vec3::zeros(), 0));
+            sums.0 += p.coords;
+            sums.1 += n;
+            sums.2 += 1;
+        }
+        if cubes.len() <= samples + samples / 2 {
+            return cubes
+                .into_values()
+                .map(|(p, n, count)| {
+                    let n = n.try_normalize(1e-12).unwrap_or_else(Vec3::z);
+                    (Point::from(p / count as f32), n)
+                })
+                .collect();
+        }
+        size *= 1.25;
+    }
+}
+
+fn number(word: Option<&str>) -> Result<f32, String> {
+    let word = word.ok_or("missing number")?;
+    word.parse().map_err(|_| format!("bad number '{}'", word))
+}
+
+// Wavefront OBJ: `v` and `vn` lines, and `f` lines of polygons (fanned out
+// into triangles) with 1-based or negative indices, as `v`, `v/vt` or
+// `v/vt/vn`. Normals are kept only if every corner has one.
+fn obj(text: &str) -> Result<Triangles, String> {
+    let (mut positions, mut normals) = (Vec::new(), Vec::new());
+    // Each distinct position and normal pair a corner uses
+    let mut corners: BTreeMap<(usize, Option<usize>), usize> = BTreeMap::new();
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let err = |e: String| format!("line {}: {}", line_no + 1, e);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => {
+                let mut xyz = || number(words.next()).map_err(err);
+                positions.push(Point::new(xyz()?, xyz()?, xyz()?));
+            }
+            Some("vn") => {
+                let mut xyz = || number(words.next()).map_err(err);
+                normals.push(Vec3::new(xyz()?, xyz()?, xyz()?));
+            }
+            Some("f") => {
+                let index = |word: Option<&str>, count: usize| -> Result<Option<usize>, String> {
+                    let word = match word.filter(|w| !w.is_empty()) {
+                        Some(word) => word,
+                        None => return Ok(None),
+                    };
+                    let i: isize = word.parse().map_err(|_| format!("bad index '{}'", word))?;
+                    let i = if i < 0 { count as isize + i } else { i - 1 };
+                    if i < 0 || i as usize >= count {
+                        return Err(format!("index {} out of range", word));
+                    }
+                    Ok(Some(i as usize))
+                };
+                let mut polygon = Vec::new();
+                for corner in words {
+                    let mut parts = corner.split('/');
+                    let p = index(parts.next(), positions.len())
+                        .map_err(err)?
+                        .ok_or_else(|| err("face corner with no vertex".to_string()))?;
+                    let n = index(parts.nth(1), normals.len()).map_err(err)?;
+                    let next = vertices.len();
+                    let vertex = *corners.entry((p, n)).or_insert(next);
+                    if vertex == next {
+                        vertices.push((p, n));
+                    }
+                    polygon.push(vertex);
+                }
+                for i in 2..polygon.len() {
+                    faces.push([polygon[0], polygon[i - 1], polygon[i]]);
+                }
+            }
+            _ => {}
+        }
+    }
+    let normals = vertices
+        .iter()
+        .map(|&(_, n)| n.map(|n| normals[n]))
+        .collect::<Option<Vec<Vec3>>>()
+        .map(|normals| normals.into_iter().map(|n| n.normalize()).collect());
+    Ok(Triangles {
+        positions: vertices.iter().map(|&(p, _)| positions[p]).collect(),
+        normals,
+        faces,
+    })
+}
+
+// STL's facets each have their own corners; corners in the same place are
+// joined up so normals come out smooth across them.
+fn weld(corners: Vec<Point>) -> Triangles {
+    let mut index: BTreeMap<[u32; 3], usize> = BTreeMap::new();
+    let mut positions = Vec::new();
+    let ids: Vec<usize> = corners
+        .into_iter()
+        .map(|p| {
+            let next = positions.len();
+            let id = *index
+                .entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+                .or_insert(next);
+            if id == next {
+                positions.push(p);
+            }
+            id
+        })
+        .collect();
+    Triangles {
+        positions,
+        normals: None,
+        faces: ids.chunks_exact(3).map(|f| [f[0], f[1], f[2]]).collect(),
+    }
+}
+
+// Binary STL: an 80 byte header, a triangle count, then 50 bytes for each.
+// Text STL files can start "solid" too, so the size has to match up.
+fn binary_stl(bytes: &[u8]) -> bool {
+    bytes.len() >= 84 && {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]);
+        bytes.len() == 84 + 50 * count as usize
+    }
+}
+
+fn stl_binary(bytes: &[u8]) -> Result<Triangles, String> {
+    let f32_at =
+        |at: usize| f32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    let mut corners = Vec::new();
+    for facet in (84..bytes.len()).step_by(50) {
+        // After the facet normal, which is left to the vertex normals
+        for corner in 0..3 {
+            let at = facet + 12 + 12 * corner;
+            corners.push(Point::new(f32_at(at), f32_at(at + 4), f32_at(at + 8)));
+        }
+    }
+    Ok(weld(corners))
+}
+
+// Text STL: `vertex x y z` lines, three to a facet.
+fn stl_ascii(text: &str) -> Result<Triangles, String> {
+    let mut corners = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        if words.next() == Some("vertex") {
+            let mut xyz =
+                || number(words.next()).map_err(|e| format!("line {}: {}", line_no + 1, e));
+            corners.push(Point::new(xyz()?, xyz()?, xyz()?));
+        }
+    }
+    if corners.len() % 3 != 0 {
+        return Err("facet without three vertices".to_string());
+    }
+    Ok(weld(corners))
+}
+
+// A PLY property: its name, and its type, or for lists the count's type and
+// the items'.
+struct Property {
+    name: String,
+    kind: &'static str,
+    list: Option<&'static str>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// PLY scalar types, by either of their names, as (canonical name, size).
+fn ply_type(name: &str) -> Result<(&'static str, usize), String> {
+    Ok(match name {
+        "char" | "int8" => ("char", 1),
+        "uchar" | "uint8" => ("uchar", 1),
+        "short" | "int16" => ("short", 2),
+        "ushort" | "uint16" => ("ushort", 2),
+        "int" | "int32" => ("int", 4),
+        "uint" | "uint32" => ("uint", 4),
+        "float" | "float32" => ("float", 4),
+        "double" | "float64" => ("double", 8),
+        _ => return Err(format!("unknown PLY type '{}'", name)),
+    })
+}
+
+// Reads PLY values one at a time, from text or binary.
+enum Values<'a> {
+    Ascii(core::str::SplitWhitespace<'a>),
+    Binary {
+        bytes: &'a [u8],
+        at: usize,
+        big: bool,
+    },
+}
+
+impl Values<'_> {
+    fn next(&mut self, kind: &str) -> Result<f64, String> {
+        match self {
+            Values::Ascii(words) => {
+                let word = words.next().ok_or("PLY data ends early")?;
+                word.parse().map_err(|_| format!("bad number '{}'", word))
+            }
+            Values::Binary { bytes, at, big } => {
+                let (_, size) = ply_type(kind)?;
+                let raw = bytes.get(*at..*at + size).ok_or("PLY data ends early")?;
+                *at += size;
+                let mut b = [0u8; 8];
+                b[..size].copy_from_slice(raw);
+                if *big {
+                    b[..size].reverse();
+                }
+                Ok(match kind {
+                    "char" => b[0] as i8 as f64,
+                    "uchar" => b[0] as f64,
+                    "short" => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    "ushort" => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    "int" => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    "uint" => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    "float" => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f64::from_le_bytes(b),
+                })
+            }
+        }
+    }
+}
+
+// PLY, text or binary either way round: the header lists the elements and
+// their properties, then the data follows in that order. Vertices need x, y
+// and z, and normals are kept if they have nx, ny and nz; faces are polygons
+// in `vertex_indices` (or `vertex_index`).
+fn ply(bytes: &[u8]) -> Result<Triangles, String> {
+    let end = bytes
+        .windows(10)
+        .position(|w| w == b"end_header")
+        .ok_or("PLY header without end_header")?;
+    let body = end
+        + bytes[end..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or("empty PLY")?
+        + 1;
+    let header = core::str::from_utf8(&bytes[..body]).map_err(|_| "bad PLY header")?;
+    let mut format = "";
+    let mut elements: Vec<Element> = Vec::new();
+    for line in header.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            ["format", f, _] => format = f,
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("bad count '{}'", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or("PLY property before any element")?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: ply_type(item)?.0,
+                    list: Some(ply_type(count)?.0),
+                });
+            }
+            ["property", kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or("PLY property before any element")?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: ply_type(kind)?.0,
+                    list: None,
+                });
+            }
+            _ => {}
+        }
+    }
+    let data = &bytes[body..];
+    let mut values = match format {
+        "ascii" => Values::Ascii(
+            core::str::from_utf8(data)
+                .map_err(|_| "bad PLY data")?
+                .split_whitespace(),
+        ),
+        "binary_little_endian" => Values::Binary {
+            bytes: data,
+            at: 0,
+            big: false,
+        },
+        "binary_big_endian" => Values::Binary {
+            bytes: data,
+            at: 0,
+            big: true,
+        },
+        _ => return Err(format!("unknown PLY format '{}'", format)),
+    };
+
+    let (mut positions, mut normals, mut faces) = (Vec::new(), Vec::new(), Vec::new());
+    let mut has_normals = false;
+    for element in &elements {
+        let find = |name: &str| element.properties.iter().position(|p| p.name == name);
+        let xyz = [find("x"), find("y"), find("z")];
+        let nxyz = [find("nx"), find("ny"), find("nz")];
+        let indices = find("vertex_indices").or_else(|| find("vertex_index"));
+        if element.name == "vertex" {
+            has_normals = nxyz.iter().all(Option::is_some);
+        }
+        for _ in 0..element.count {
+            let mut scalars = vec![0.0f64; element.properties.len()];
+            let mut polygon = Vec::new();
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.list {
+                    Some(count_kind) => {
+                        let count = values.next(count_kind)? as usize;
+                        for _ in 0..count {
+                            let v = values.next(property.kind)?;
+                            if Some(i) == indices {
+                                polygon.push(v as usize);
+                            }
+                        }
+                    }
+                    None => scalars[i] = values.next(property.kind)?,
+                }
+            }
+            let get = |ix: Option<usize>| ix.map_or(0.0, |ix| scalars[ix] as f32);
+            match element.name.as_str() {
+                "vertex" => {
+                    positions.push(Point::new(get(xyz[0]), get(xyz[1]), get(xyz[2])));
+                    normals.push(Vec3::new(get(nxyz[0]), get(nxyz[1]), get(nxyz[2])));
+                }
+                "face" => {
+                    if let Some(&bad) = polygon.iter().find(|&&v| v >= positions.len()) {
+                        return Err(format!("face with vertex {} out of range", bad));
+                    }
+                    for i in 2..polygon.len() {
+                        faces.push([polygon[0], polygon[i - 1], polygon[i]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(Triangles {
+        positions,
+        normals: has_normals.then(|| {
+            normals
+                .into_iter()
+                .map(|n| n.try_normalize(1e-12).unwrap_or_else(Vec3::z))
+                .collect()
+        }),
+        faces,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimate_thins_out_a_dense_mesh_keeping_sides_apart() {
+        // Both sides of a unit square, far thinner than the grid, each with
+        // 100 by 100 points
+        let mut points = vec![];
+        for i in 0..100 {
+            for j in 0..100 {
+                let (x, y) = (i as f32 / 100.0, j as f32 / 100.0);
+                points.push((Point::new(x, y, 0.001), Vec3::z()));
+                points.push((Point::new(x, y, -0.001), -Vec3::z()));
+            }
+        }
+        let samples = 200;
+        let points = decimate(points, 2.0, samples);
+        assert!(points.len() <= samples + samples / 2, "{}", points.len());
+        assert!(points.len() >= samples / 2, "{}", points.len());
+        // Still facing out on each side, not averaged away to nothing
+        let up = points.iter().filter(|(_, n)| *n == Vec3::z()).count();
+        let down = points.iter().filter(|(_, n)| *n == -Vec3::z()).count();
+        assert_eq!(up + down, points.len());
+        assert_eq!(up, down);
+        assert!(points.iter().all(|(p, n)| p.z * n.z > 0.0));
+    }
+}
@@ -0,0 +1,180 @@
+use super::render::{Camera, Sample, FAR, NEAR};
+use crate::framebuffer::FrameBuffer;
+use crate::render::{Donut, Params};
+use crate::{Point, Vec3};
+use alloc::vec::Vec;
+use core::f32::consts::{PI, TAU};
+#[cfg(not(feature = "std"))]
+use nalgebra::{ComplexField, RealField};
+
+// Blobs moving on their own Lissajous paths about the origin
+pub struct Ball {
+    pub radius: f32,
+    // Amplitude, frequency and phase along each axis
+    pub amplitude: Vec3,
+    pub frequency: Vec3,
+    pub phase: Vec3,
+    // Hidden blobs add nothing to the field
+    pub visible: bool,
+}
+
+// Steps along each ray looking for the surface, then halvings to pin it down
+const STEP: f32 = 0.04;
+const BISECTIONS: usize = 6;
+
+// Several blobs whose fields add up, drawn where the total reaches 1, so they
+// bulge towards each other and merge. Rendered by marching a ray through
+// each cell rather than from points on the surface.
+pub struct Metaballs {
+    pub balls: Vec<Ball>,
+    // Seconds of movement so far
+    pub t: f32,
+}
+
+impl Metaballs {
+    pub fn new(n: usize) -> Metaballs {
+        let balls = (0..n)
+            .map(|i| {
+                // Spread out by the golden angle so no two move alike
+                let g = 2.4 * i as f32;
+                Ball {
+                    radius: 0.35 + 0.08 * (i % 3) as f32,
+                    amplitude: Vec3::new(0.9, 0.6, 0.7),
+                    frequency: Vec3::new(
+                        0.7 + 0.13 * (i % 4) as f32,
+                        0.9 + 0.1 * (i % 3) as f32,
+                        0.5 + 0.17 * (i % 2) as f32,
+                    ),
+                    phase: Vec3::new(g, 2.0 * g, 3.0 * g),
+                    visible: true,
+                }
+            })
+            .collect();
+        Metaballs { balls, t: 0.0 }
+    }
+
+    // Where a blob is now.
+    pub fn center(&self, ball: &Ball) -> Point {
+        let at = |i: usize| ball.amplitude[i] * (ball.frequency[i] * self.t + ball.phase[i]).sin();
+        Point::new(at(0), at(1), at(2))
+    }
+
+    fn centers(&self) -> Vec<(Point, f32)> {
+        self.balls
+            .iter()
+            .filter(|b| b.visible)
+            .map(|b| (self.center(b), b.radius))
+            .collect()
+    }
+
+    // The radius about the origin everything's inside.
+    pub fn bound(&self) -> f32 {
+        bound(&self.centers())
+    }
+
+    // Moves the blobs on by a frame.
+    pub fn step(&mut self, params: &Params) {
+        self.t += 0.05 * params.speed;
+    }
+
+    // Draws the blobs with the donut's shader, palette and debug view.
+    pub fn render(&self, look: &Donut, params: &Params, framebuffer: &mut FrameBuffer) {
+        let (sx, sy) = (framebuffer.sx, framebuffer.sy);
+        let camera = Camera::new(params, sx, sy);
+        let to_world = match camera.eye.try_inverse() {
+            Some(m) => m,
+            None => return,
+        };
+        let balls = self.centers();
+        if balls.is_empty() {
+            return;
+        }
+        let field = |p: &Point| -> f32 {
+            balls
+                .iter()
+                .map(|(c, r)| r * r / ((p - c).norm_squared() + 1e-6))
+                .sum()
+        };
+        let bound = bound(&balls);
+        let eye = Point::from(camera.position);
+        let center = Vec3::new(0.5 * sx as f32, 0.5 * sy as f32, 0.0);
+
+        let _span = tracing::trace_span!("raymarch").entered();
+        for y in 0..sy {
+            for x in 0..sx {
+                let dir = Vec3::new(
+                    (x as f32 + 0.5 - center.x) / camera.zoom.0,
+                    (center.y - y as f32 - 0.5) / camera.zoom.1,
+                    -1.0,
+                );
+                let dir = to_world.transform_vector(&dir).normalize();
+                // Where the ray crosses the bounding sphere
+                let to_origin = Point::origin() - eye;
+                let mid = to_origin.dot(&dir);
+                let miss = to_origin.norm_squared() - mid * mid - bound * bound;
+                if miss > 0.0 {
+                    continue;
+                }
+                let half = (-miss).sqrt();
+                let (mut near, far) = ((mid - half).max(NEAR), (mid + half).min(FAR));
+
+                let mut hit = None;
+                while near < far {
+                    let next = near + STEP;
+                    if field(&(eye + dir * next)) >= 1.0 {
+                        let (mut lo, mut hi) = (near, next);
+                        for _ in 0..BISECTIONS {
+                            let m = 0.5 * (lo + hi);
+                            if field(&(eye + dir * m)) >= 1.0 {
+                                hi = m;
+                            } else {
+                                lo = m;
+                            }
+                        }
+                        hit = Some(hi);
+                        break;
+                    }
+                    near = next;
+                }
+                let distance = match hit {
+                    Some(distance) => distance,
+                    None => continue,
+                };
+
+                let p = eye + dir * distance;
+                // The field falls off outwards, so the normal is down its
+                // gradient.
+                let n = balls
+                    .iter()
+                    .map(|(c, r)| {
+                        let d = p - c;
+                        d * (r * r / (d.norm_squared().powi(2) + 1e-6))
+                    })
+                    .sum::<Vec3>()
+                    .normalize();
+                let p_clip = camera.clipspace * p.to_homogeneous();
+                let depth = 0.5 * (p_clip.z / p_clip.w + 1.0);
+                let sample = Sample {
+                    p_screen: Vec3::new(x as f32, y as f32, depth),
+                    n,
+                    cam_vec: -dir,
+                    uv: (0.5 + n.y.atan2(n.x) / TAU, 0.5 + n.z.asin() / PI),
+                    spread: 0.5 + (distance - params.cam_dist) / (2.0 * bound.max(1e-3)),
+                };
+                let shade = look.shade(&sample, params.light_dir, params.gain);
+                if shade.brightness > 0.0 {
+                    framebuffer.poke_if(x, y, depth, shade);
+                }
+            }
+        }
+    }
+}
+
+// Everything's inside this sphere about the origin: each blob's field reaches
+// further the more of them there are.
+fn bound(balls: &[(Point, f32)]) -> f32 {
+    balls
+        .iter()
+        .map(|(c, r)| (c - Point::origin()).norm() + r * (balls.len() as f32).sqrt())
+        .fold(0.0, f32::max)
+}
@@ -0,0 +1,17 @@
+// The renderer itself: math, shading and the framebuffer, down to the cells
+// handed to a render target. Needs only `alloc`, so it builds without std for
+// embedded targets driving character LCDs or serial consoles.
+pub mod ansi;
+pub mod color;
+pub(crate) mod fixed;
+pub mod framebuffer;
+pub mod image;
+pub mod mesh;
+pub mod metaballs;
+pub mod palette;
+pub mod post;
+pub mod ramp;
+pub mod render;
+pub mod shader;
+pub mod shape;
+pub mod target;
@@ -0,0 +1,99 @@
+use crate::color::Rgb;
+use crate::Vec3;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use nalgebra::{ComplexField, RealField};
+
+// Colors the donut where the shader doesn't, from how lit each point is or
+// where it is.
+#[derive(Clone, Debug)]
+pub enum Palette {
+    // Lookup table from brightness, darkest first
+    Gradient(Vec<Rgb>),
+    // Hue from which way the surface faces
+    Normal,
+    // Hue from near (red) to far (blue)
+    Depth,
+}
+
+const LUT_SIZE: usize = 64;
+
+pub const BUILTIN: &[(&str, &[Rgb])] = &[
+    (
+        "amber",
+        &[Rgb(40, 16, 0), Rgb(255, 176, 0), Rgb(255, 232, 170)],
+    ),
+    (
+        "green",
+        &[Rgb(0, 40, 0), Rgb(51, 255, 51), Rgb(210, 255, 210)],
+    ),
+    (
+        "synthwave",
+        &[
+            Rgb(46, 0, 90),
+            Rgb(200, 0, 170),
+            Rgb(255, 90, 110),
+            Rgb(255, 210, 90),
+        ],
+    ),
+];
+
+// Fully saturated color for a hue in [0, 1), red through green and blue.
+pub(crate) fn hue(h: f32) -> Rgb {
+    let h = (h - h.floor()) * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let c = |v: f32| (255.0 * v).round() as u8;
+    Rgb(c(r), c(g), c(b))
+}
+
+impl Palette {
+    // Evenly spaced stops, blended into a lookup table.
+    pub fn gradient(stops: &[Rgb]) -> Palette {
+        let lut = (0..LUT_SIZE)
+            .map(|i| {
+                let t = i as f32 / (LUT_SIZE - 1) as f32 * (stops.len() - 1) as f32;
+                let ix = (t as usize).min(stops.len().saturating_sub(2));
+                match stops.get(ix + 1) {
+                    Some(&next) => stops[ix].lerp(next, t - ix as f32),
+                    None => stops[ix],
+                }
+            })
+            .collect();
+        Palette::Gradient(lut)
+    }
+
+    pub fn builtin(name: &str) -> Option<Palette> {
+        match name {
+            "rainbow" => Some(Palette::Normal),
+            "depth" => Some(Palette::Depth),
+            _ => BUILTIN
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, stops)| Palette::gradient(stops)),
+        }
+    }
+
+    // The color for a point with the given brightness and world space normal,
+    // at `depth` from the front of the donut (0) to the back (1).
+    pub fn color(&self, brightness: f32, normal: Vec3, depth: f32) -> Rgb {
+        match self {
+            Palette::Gradient(lut) => {
+                let ix = (brightness.clamp(0.0, 1.0) * (lut.len() - 1) as f32).round();
+                lut[ix as usize]
+            }
+            Palette::Normal => {
+                let h = normal.z.atan2(normal.x) / (2.0 * core::f32::consts::PI);
+                hue(h + 0.25 * normal.y)
+            }
+            Palette::Depth => hue(0.67 * depth.clamp(0.0, 1.0)),
+        }
+    }
+}
@@ -0,0 +1,474 @@
+// Effects on a finished frame, before it goes out to a target.
+use super::render::{FAR, NEAR};
+use crate::color::Rgb;
+use crate::framebuffer::FrameBuffer;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use nalgebra::ComplexField;
+
+// How many cells across things blur at twice or half the focal distance
+const APERTURE: f32 = 4.0;
+
+// Blur circles get no bigger than this, in cells across
+const MAX_BLUR: f32 = 6.0;
+
+// Blurred cells show once at least this much of one covers them
+const COVERED: f32 = 0.3;
+
+// Trails fade out once this little is left of them
+const FADED: f32 = 0.2;
+
+// How much the screen bulges, and how bright every other row is
+const BARREL: f32 = 0.08;
+const SCANLINE: f32 = 0.6;
+
+// Phosphors keep this much of their glow from frame to frame, unless motion
+// blur says otherwise
+pub const PHOSPHOR: f32 = 0.5;
+
+// Glow shows in empty cells once there's this much of it
+const GLOWING: f32 = 0.15;
+
+// Auto-exposure's histogram: bins across [0, 1], the fractions of lit cells
+// left out at the dark and bright ends, the narrowest range it'll stretch
+// over the whole ramp, and how far it moves towards the frame's range each
+// frame
+const BINS: usize = 64;
+const DARKEST: f32 = 0.02;
+const BRIGHTEST: f32 = 0.02;
+const NARROWEST: f32 = 0.25;
+const ADAPT: f32 = 0.1;
+
+// Distance from the camera of a depth in [0, 1], undoing the perspective.
+fn distance(z: f32) -> f32 {
+    let ndc = 2.0 * z - 1.0;
+    2.0 * FAR * NEAR / ((FAR + NEAR) - ndc * (FAR - NEAR))
+}
+
+// Depth of field: cells further from `focus` (a distance from the camera)
+// spread out over a wider circle. Each cell scatters its shade over its
+// circle, so things out of focus bleed into the background too.
+pub fn depth_of_field(framebuffer: &mut FrameBuffer, focus: f32) {
+    let (sx, sy) = (framebuffer.sx, framebuffer.sy);
+    // Sums of brightness, cover, color and colored cover, and whether
+    // anything blurred landed there
+    let mut brightness = vec![0.0f32; sx * sy];
+    let mut cover = vec![0.0f32; sx * sy];
+    let mut rgb = vec![[0.0f32; 3]; sx * sy];
+    let mut colored = vec![0.0f32; sx * sy];
+    let mut blurred = vec![false; sx * sy];
+    for y in 0..sy {
+        for x in 0..sx {
+            let (level, color, z) = match framebuffer.cell(y * sx + x) {
+                Some(cell) => cell,
+                None => continue,
+            };
+            let radius = (APERTURE * (1.0 - focus / distance(z)).abs()).min(MAX_BLUR);
+            // Cells are about twice as tall as wide
+            let (rx, ry) = (radius as isize, (radius / 2.0) as isize);
+            let mut spots = vec![];
+            for dy in -ry..=ry {
+                for dx in -rx..=rx {
+                    let (u, v) = (
+                        dx as f32 / radius.max(1.0),
+                        dy as f32 / (radius / 2.0).max(1.0),
+                    );
+                    let (x, y) = (x as isize + dx, y as isize + dy);
+                    if u * u + v * v <= 1.0
+                        && (0..sx as isize).contains(&x)
+                        && (0..sy as isize).contains(&y)
+                    {
+                        spots.push(y as usize * sx + x as usize);
+                    }
+                }
+            }
+            let weight = 1.0 / spots.len().max(1) as f32;
+            for &ix in &spots {
+                brightness[ix] += level * weight;
+                cover[ix] += weight;
+                if let Some(Rgb(r, g, b)) = color {
+                    rgb[ix][0] += r as f32 * weight;
+                    rgb[ix][1] += g as f32 * weight;
+                    rgb[ix][2] += b as f32 * weight;
+                    colored[ix] += weight;
+                }
+                blurred[ix] |= spots.len() > 1;
+            }
+        }
+    }
+    for ix in 0..sx * sy {
+        // In focus and with nothing blurred over it, as drawn
+        if !blurred[ix] {
+            continue;
+        }
+        let color = (colored[ix] > 0.0).then(|| {
+            let c = |sum: f32| (sum / colored[ix]).round() as u8;
+            Rgb(c(rgb[ix][0]), c(rgb[ix][1]), c(rgb[ix][2]))
+        });
+        // Fading out at the edges of the circles, where less covers them
+        let level = (cover[ix] >= COVERED).then(|| brightness[ix] / cover[ix].max(1.0));
+        framebuffer.reshade(ix, level, color);
+    }
+}
+
+// Blends each cell's brightness with what was there before, `persistence`
+// of it carrying over from frame to frame, giving what now shows in each
+// cell: its brightness and color, or None once nothing's left.
+fn persist(framebuffer: &mut FrameBuffer, persistence: f32) -> Vec<Option<(f32, Option<Rgb>)>> {
+    let size = framebuffer.sx * framebuffer.sy;
+    let mut trail = core::mem::take(&mut framebuffer.trail);
+    trail.resize(size, (0.0, 0.0, None));
+    let shown = trail
+        .iter_mut()
+        .enumerate()
+        .map(
+            |(ix, (left, brightness, color))| match framebuffer.cell(ix) {
+                Some((level, now, _)) => {
+                    let before = persistence * *left;
+                    *brightness = level + before * (*brightness - level);
+                    *left = 1.0;
+                    *color = now;
+                    Some((*brightness, now))
+                }
+                None => {
+                    *left *= persistence;
+                    (*left >= FADED).then_some((*left * *brightness, *color))
+                }
+            },
+        )
+        .collect();
+    framebuffer.trail = trail;
+    shown
+}
+
+// Motion blur: each cell's brightness is blended with what was there before,
+// `persistence` of it (in [0, 1)) carrying over from frame to frame, so
+// moving edges leave fading trails behind them.
+pub fn motion_blur(framebuffer: &mut FrameBuffer, persistence: f32) {
+    for (ix, shown) in persist(framebuffer, persistence).into_iter().enumerate() {
+        if let Some((level, color)) = shown {
+            framebuffer.reshade(ix, Some(level), color);
+        }
+    }
+}
+
+// An old monitor: phosphors that glow on for a while after (`persistence`,
+// as for motion blur), every other row dimmer, and the picture bulging out
+// towards the middle of a curved screen, leaving the corners dark.
+pub fn crt(framebuffer: &mut FrameBuffer, persistence: f32) {
+    let (sx, sy) = (framebuffer.sx, framebuffer.sy);
+    let shown = persist(framebuffer, persistence);
+    for y in 0..sy {
+        for x in 0..sx {
+            // From -1 to 1 across the screen, then out to where on the flat
+            // picture this part of the curved one shows
+            let u = 2.0 * (x as f32 + 0.5) / sx as f32 - 1.0;
+            let v = 2.0 * (y as f32 + 0.5) / sy as f32 - 1.0;
+            let bulge = 1.0 + BARREL * (u * u + v * v);
+            let (u, v) = (u * bulge, v * bulge);
+            let from = if u.abs() < 1.0 && v.abs() < 1.0 {
+                let fx = ((u + 1.0) * 0.5 * sx as f32) as usize;
+                let fy = ((v + 1.0) * 0.5 * sy as f32) as usize;
+                shown[fy.min(sy - 1) * sx + fx.min(sx - 1)]
+            } else {
+                None
+            };
+            let dim = if y % 2 == 1 { SCANLINE } else { 1.0 };
+            let (level, color) = match from {
+                Some((level, color)) => (Some(dim * level), color),
+                None => (None, None),
+            };
+            framebuffer.reshade(y * sx + x, level, color);
+        }
+    }
+}
+
+// Blurs a grid of values with a tent `rx` cells either side across and `ry`
+// up and down, one direction at a time.
+fn blur(values: &[f32], sx: usize, sy: usize, rx: usize, ry: usize) -> Vec<f32> {
+    let pass = |values: &[f32], r: usize, step: usize, len: usize, lines: usize, stride: usize| {
+        let weight = |d: usize| (r + 1 - d) as f32 / ((r + 1) * (r + 1)) as f32;
+        let mut out = vec![0.0; values.len()];
+        for line in 0..lines {
+            for i in 0..len {
+                let at = |i: usize| values[line * stride + i * step];
+                let mut sum = weight(0) * at(i);
+                for d in 1..=r {
+                    if i >= d {
+                        sum += weight(d) * at(i - d);
+                    }
+                    if i + d < len {
+                        sum += weight(d) * at(i + d);
+                    }
+                }
+                out[line * stride + i * step] = sum;
+            }
+        }
+        out
+    };
+    let across = pass(values, rx, 1, sx, sy, sx);
+    pass(&across, ry, sx, sy, sx, 1)
+}
+
+// Bloom: cells brighter than `threshold` glow, spreading their light `radius`
+// rows (and twice as many columns, cells being tall) around them.
+pub fn bloom(framebuffer: &mut FrameBuffer, threshold: f32, radius: usize) {
+    let (sx, sy) = (framebuffer.sx, framebuffer.sy);
+    // How far over the threshold each cell is, and its color weighted by that
+    let mut glow = vec![0.0f32; sx * sy];
+    let mut rgb = [
+        vec![0.0f32; sx * sy],
+        vec![0.0f32; sx * sy],
+        vec![0.0f32; sx * sy],
+    ];
+    let mut colored = vec![0.0f32; sx * sy];
+    for ix in 0..sx * sy {
+        if let Some((level, color, _)) = framebuffer.cell(ix) {
+            let over = ((level - threshold) / (1.0 - threshold).max(1e-3)).clamp(0.0, 1.0);
+            glow[ix] = over;
+            if let Some(Rgb(r, g, b)) = color {
+                rgb[0][ix] = r as f32 * over;
+                rgb[1][ix] = g as f32 * over;
+                rgb[2][ix] = b as f32 * over;
+                colored[ix] = over;
+            }
+        }
+    }
+    let blurred = |values: &[f32]| blur(values, sx, sy, 2 * radius, radius);
+    let glow = blurred(&glow);
+    let rgb = [blurred(&rgb[0]), blurred(&rgb[1]), blurred(&rgb[2])];
+    let colored = blurred(&colored);
+    for ix in 0..sx * sy {
+        if glow[ix] <= 0.0 {
+            continue;
+        }
+        match framebuffer.cell(ix) {
+            Some((level, color, _)) => {
+                framebuffer.reshade(ix, Some((level + glow[ix]).min(1.0)), color)
+            }
+            None if glow[ix] >= GLOWING => {
+                let color = (colored[ix] > 0.0).then(|| {
+                    let c = |i: usize| (rgb[i][ix] / colored[ix]).round() as u8;
+                    Rgb(c(0), c(1), c(2))
+                });
+                framebuffer.reshade(ix, Some(glow[ix].min(1.0)), color);
+            }
+            None => {}
+        }
+    }
+}
+
+// Auto-exposure: the range of brightness lit cells cover (less a few
+// outliers at either end), stretched over the whole ramp. The range follows
+// each frame's a little at a time, so it eases rather than flickers.
+#[derive(Default)]
+pub struct Exposure {
+    // The darkest and brightest levels shown, once there's been a frame
+    range: Option<(f32, f32)>,
+}
+
+impl Exposure {
+    pub fn apply(&mut self, framebuffer: &mut FrameBuffer) {
+        let size = framebuffer.sx * framebuffer.sy;
+        let mut histogram = [0usize; BINS];
+        let mut lit = 0;
+        for ix in 0..size {
+            if let Some((level, _, _)) = framebuffer.cell(ix) {
+                let bin = (level.clamp(0.0, 1.0) * BINS as f32) as usize;
+                histogram[bin.min(BINS - 1)] += 1;
+                lit += 1;
+            }
+        }
+        if lit == 0 {
+            return;
+        }
+        // Where the first `fraction` of the lit cells end: the first bin
+        // that takes the count past it
+        let past = |fraction: f32| {
+            let mut seen = 0;
+            histogram
+                .iter()
+                .position(|&n| {
+                    seen += n;
+                    seen as f32 > fraction * lit as f32
+                })
+                .unwrap_or(BINS - 1)
+        };
+        let low = past(DARKEST) as f32 / BINS as f32;
+        let high = (past(1.0 - BRIGHTEST) + 1) as f32 / BINS as f32;
+        let (low, high) = match self.range {
+            Some((was_low, was_high)) => (
+                was_low + ADAPT * (low - was_low),
+                was_high + ADAPT * (high - was_high),
+            ),
+            None => (low, high),
+        };
+        self.range = Some((low, high));
+
+        let span = (high - low).max(NARROWEST);
+        for ix in 0..size {
+            if let Some((level, _, _)) = framebuffer.cell(ix) {
+                framebuffer.expose(ix, ((level - low) / span).clamp(0.0, 1.0));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::Shade;
+
+    // Depth in [0, 1] of something `distance` from the camera.
+    fn depth(distance: f32) -> f32 {
+        let ndc = ((FAR + NEAR) - 2.0 * FAR * NEAR / distance) / (FAR - NEAR);
+        (ndc + 1.0) / 2.0
+    }
+
+    // Starts a frame `sx` by `sy` with a block of cells at `level`, from
+    // (x0, y0) up to (x1, y1), at `distance` from the camera.
+    fn draw(
+        framebuffer: &mut FrameBuffer,
+        (sx, sy): (usize, usize),
+        (x0, y0, x1, y1): (usize, usize, usize, usize),
+        level: f32,
+        distance: f32,
+    ) {
+        framebuffer.clear(sx, sy);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let shade = Shade {
+                    brightness: level,
+                    specular: 0.0,
+                    color: None,
+                };
+                framebuffer.poke_if(x, y, depth(distance), shade);
+            }
+        }
+    }
+
+    fn frame(
+        size: (usize, usize),
+        block: (usize, usize, usize, usize),
+        level: f32,
+        distance: f32,
+    ) -> FrameBuffer {
+        let mut framebuffer = FrameBuffer::new();
+        draw(&mut framebuffer, size, block, level, distance);
+        framebuffer
+    }
+
+    fn level(framebuffer: &FrameBuffer, x: usize, y: usize) -> Option<f32> {
+        framebuffer.shading(x, y).map(|(level, _)| level)
+    }
+
+    #[test]
+    fn depth_of_field_blurs_what_is_out_of_focus() {
+        assert!((distance(depth(3.0)) - 3.0).abs() < 1e-2);
+        let block = (8, 3, 14, 7);
+        let mut framebuffer = frame((24, 10), block, 0.8, 3.0);
+        depth_of_field(&mut framebuffer, 3.0);
+        assert_eq!(level(&framebuffer, 10, 5), Some(0.8));
+        assert_eq!(level(&framebuffer, 14, 5), None);
+
+        // At half the focal distance, the block spreads into the background,
+        // fading out towards its edges
+        let mut framebuffer = frame((24, 10), block, 0.8, 3.0);
+        depth_of_field(&mut framebuffer, 6.0);
+        let (middle, edge) = (level(&framebuffer, 10, 5), level(&framebuffer, 14, 5));
+        assert!(
+            edge.unwrap() > 0.0 && edge < middle,
+            "{:?} {:?}",
+            edge,
+            middle
+        );
+        assert_eq!(level(&framebuffer, 22, 5), None);
+    }
+
+    #[test]
+    fn motion_blur_leaves_a_fading_trail() {
+        let mut framebuffer = frame((24, 10), (2, 3, 6, 7), 0.8, 3.0);
+        motion_blur(&mut framebuffer, 0.5);
+        assert_eq!(level(&framebuffer, 4, 5), Some(0.8));
+
+        // Moved on, what was there is left at half its brightness, then a
+        // quarter, then gone
+        for (frame_no, &expected) in [Some(40.0), Some(20.0), None].iter().enumerate() {
+            let x0 = 10 + frame_no;
+            draw(&mut framebuffer, (24, 10), (x0, 3, x0 + 4, 7), 0.8, 3.0);
+            motion_blur(&mut framebuffer, 0.5);
+            // As a percentage
+            let left = level(&framebuffer, 4, 5).map(|l| (l * 100.0).round());
+            assert_eq!(left, expected, "{}", frame_no);
+            assert_eq!(level(&framebuffer, x0 + 3, 5), Some(0.8));
+        }
+    }
+
+    #[test]
+    fn bloom_spreads_bright_cells_into_their_neighbors() {
+        let mut framebuffer = frame((24, 10), (4, 4, 8, 6), 1.0, 3.0);
+        let shade = Shade {
+            brightness: 0.3,
+            specular: 0.0,
+            color: None,
+        };
+        framebuffer.poke_if(16, 4, depth(3.0), shade);
+        bloom(&mut framebuffer, 0.5, 1);
+        // Glowing around the bright block, more so nearer it
+        let (near, far) = (level(&framebuffer, 8, 4), level(&framebuffer, 9, 4));
+        assert!(near.unwrap() > far.unwrap_or(0.0), "{:?} {:?}", near, far);
+        assert_eq!(level(&framebuffer, 10, 4), None);
+        assert_eq!(level(&framebuffer, 4, 3).map(|l| l > 0.0), Some(true));
+        // But not around the dim cell
+        assert_eq!(level(&framebuffer, 16, 4), Some(0.3));
+        assert_eq!(level(&framebuffer, 17, 4), None);
+    }
+
+    #[test]
+    fn crt_dims_every_other_row_darkens_the_corners_and_glows_on() {
+        let mut framebuffer = frame((24, 10), (0, 0, 24, 10), 0.8, 3.0);
+        crt(&mut framebuffer, 0.5);
+        // As a percentage
+        let at =
+            |framebuffer: &FrameBuffer, x, y| level(framebuffer, x, y).map(|l| (l * 100.0).round());
+        assert_eq!(at(&framebuffer, 12, 4), Some(80.0));
+        assert_eq!(at(&framebuffer, 12, 5), Some(48.0));
+        assert_eq!(at(&framebuffer, 0, 0), None);
+        assert_eq!(at(&framebuffer, 23, 9), None);
+
+        // With the picture gone, the phosphors fade rather than go dark
+        draw(&mut framebuffer, (24, 10), (0, 0, 0, 0), 0.0, 3.0);
+        crt(&mut framebuffer, 0.5);
+        assert_eq!(at(&framebuffer, 12, 4), Some(40.0));
+        assert_eq!(at(&framebuffer, 12, 5), Some(24.0));
+    }
+
+    #[test]
+    fn auto_exposure_stretches_a_dim_scene_over_the_ramp() {
+        // A ramp from 0.1 to 0.35 across the screen
+        let mut framebuffer = FrameBuffer::new();
+        framebuffer.clear(24, 10);
+        for y in 0..10 {
+            for x in 0..24 {
+                let shade = Shade {
+                    brightness: 0.1 + 0.25 * x as f32 / 23.0,
+                    specular: 0.0,
+                    color: None,
+                };
+                framebuffer.poke_if(x, y, depth(3.0), shade);
+            }
+        }
+        let mean = |framebuffer: &FrameBuffer| {
+            let levels = (0..24).filter_map(|x| level(framebuffer, x, 0));
+            levels.sum::<f32>() / 24.0
+        };
+        let before = mean(&framebuffer);
+        let mut exposure = Exposure::default();
+        exposure.apply(&mut framebuffer);
+        let after = mean(&framebuffer);
+        assert!((after - 0.5).abs() < 0.1, "{} to {}", before, after);
+        assert!(level(&framebuffer, 0, 0).unwrap() < 0.05);
+        assert!(level(&framebuffer, 23, 0).unwrap() > 0.9);
+    }
+}
@@ -0,0 +1,39 @@
+#[cfg(feature = "std")]
+use crate::caps::Capabilities;
+
+// Glyph ramps, darkest first.
+pub const CLASSIC: &str = "-~+*=;%#$@";
+
+pub const RAMPS: &[(&str, &str)] = &[
+    ("classic", CLASSIC),
+    ("dots", ".:-=+*#%@"),
+    ("blocks", "░▒▓█"),
+    ("shades", "·∙•●"),
+    ("braille", "⠁⠃⠇⡇⣇⣧⣷⣿"),
+    // Full-width, by how many strokes each takes
+    ("kanji", "・一二三言語警鬱"),
+];
+
+// Just dark and light, as far apart as ASCII goes, for --high-contrast.
+pub const HIGH_CONTRAST: &str = ".@";
+
+// Glyphs for specular highlights with --highlights, weakest first.
+pub const HIGHLIGHTS: &str = "oO0@";
+
+// A named ramp, or else the glyphs themselves.
+pub fn lookup(name: &str) -> &str {
+    RAMPS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map_or(name, |(_, glyphs)| glyphs)
+}
+
+// The ramp if the terminal can show all of it, otherwise the ASCII classic.
+#[cfg(feature = "std")]
+pub fn fallback<'a>(ramp: &'a str, caps: &Capabilities) -> &'a str {
+    if caps.unicode || ramp.is_ascii() {
+        ramp
+    } else {
+        CLASSIC
+    }
+}
@@ -0,0 +1,590 @@
+use super::fixed::fx;
+use crate::color::Rgb;
+use crate::framebuffer::FrameBuffer;
+use crate::palette::Palette;
+use crate::shader::{Phong, Shade, Shader};
+use crate::shape::Morph;
+use crate::{Mat4, Point, Vec3};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::{RefCell, RefMut};
+use core::cmp::{max, min};
+#[cfg(not(feature = "std"))]
+use nalgebra::ComplexField;
+
+// Scene parameters, which modes like --sysmon drive from outside each frame.
+#[derive(Clone, Copy)]
+pub struct Params {
+    // Radii of torus
+    pub r1: f32,
+    pub r2: f32,
+    // Rotation per frame about x, y, and the torus' own axis
+    pub spin: Vec3,
+    pub light_dir: Vec3,
+    pub cam_dist: f32,
+    // The camera's vertical field of view, in radians
+    pub fov: f32,
+    // Camera yaw and pitch about the origin
+    pub orbit: (f32, f32),
+    // An extra turn about world axes on the next step, e.g. from dragging
+    // with the mouse; whatever sets it resets it
+    pub nudge: Mat4,
+    // Multipliers on the rotation rate and on the shading
+    pub speed: f32,
+    pub gain: f32,
+    pub tint: Option<Rgb>,
+}
+
+impl Default for Params {
+    fn default() -> Params {
+        Params {
+            r1: 1.0,
+            r2: 0.45,
+            spin: Vec3::new(0.1, -0.05, 0.03),
+            light_dir: Vec3::new(1.0, 5.0, -3.0).normalize(),
+            cam_dist: 4.0,
+            fov: core::f32::consts::FRAC_PI_4,
+            orbit: (0.0, 0.0),
+            nudge: Mat4::identity(),
+            speed: 1.0,
+            gain: 1.0,
+            tint: None,
+        }
+    }
+}
+
+// How far back the camera is for auto-framing, in scene radii: where the
+// donut has always been seen from.
+const PERSPECTIVE: f32 = 2.75;
+
+impl Params {
+    // Frames a scene `radius` about the origin to fill `fraction` of a screen
+    // sx by sy cells: the camera as far back as it has always been from the
+    // donut, for its size, and the field of view whatever that then takes.
+    pub fn frame(&mut self, radius: f32, sx: usize, sy: usize, fraction: f32) {
+        let radius = radius.abs().max(1e-3);
+        self.cam_dist = PERSPECTIVE * radius;
+        // How far the scene reaches across per unit of depth
+        let reach = radius / (self.cam_dist * self.cam_dist - radius * radius).sqrt();
+        // ... and the screen, in its narrower direction, per unit of
+        // tan(fov / 2), as Camera::new projects it
+        let (narrow, wide) = (min(sx, sy).max(1) as f32, max(sx, sy).max(1) as f32);
+        let screen = (sx as f32 * narrow / wide).min(sy as f32) / narrow;
+        self.fov = 2.0 * (reach / (fraction * screen).max(1e-3)).atan();
+    }
+}
+
+// False color pictures of what the rasterizer sees, in place of shading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugView {
+    // Colored by the normal's direction
+    Normals,
+    // Brighter nearer the front of the donut
+    Depth,
+    // Red along the rings and green around them
+    Uv,
+    // Colored by how many samples landed in each cell
+    Overdraw,
+}
+
+impl DebugView {
+    pub fn parse(name: &str) -> Option<DebugView> {
+        match name {
+            "normals" => Some(DebugView::Normals),
+            "depth" => Some(DebugView::Depth),
+            "uv" => Some(DebugView::Uv),
+            "overdraw" => Some(DebugView::Overdraw),
+            _ => None,
+        }
+    }
+
+    // The shade for a point, or None to shade it normally (overdraw is
+    // painted over the frame afterwards).
+    fn shade(self, n: Vec3, view: Vec3, uv: (f32, f32), spread: f32) -> Option<Shade> {
+        let c = |v: f32| (255.0 * v.clamp(0.0, 1.0)).round() as u8;
+        let facing = 0.2 + 0.79 * n.dot(&view).max(0.0);
+        let (brightness, color) = match self {
+            DebugView::Normals => {
+                let rgb = (n + Vec3::new(1.0, 1.0, 1.0)) * 0.5;
+                (facing, Some(Rgb(c(rgb.x), c(rgb.y), c(rgb.z))))
+            }
+            DebugView::Depth => ((1.0 - spread).clamp(0.01, 0.99), None),
+            DebugView::Uv => (facing, Some(Rgb(c(uv.0), c(uv.1), 0))),
+            DebugView::Overdraw => return None,
+        };
+        Some(Shade {
+            brightness,
+            specular: 0.0,
+            color,
+        })
+    }
+}
+
+// A point on the surface that made it to the screen, waiting to be shaded.
+pub(crate) struct Sample {
+    pub p_screen: Vec3,
+    // World space normal, and unit vector towards the camera
+    pub n: Vec3,
+    pub cam_vec: Vec3,
+    pub uv: (f32, f32),
+    pub spread: f32,
+}
+
+// Depth range of the camera; nearer and farther points aren't drawn.
+pub(crate) const NEAR: f32 = 0.1;
+pub(crate) const FAR: f32 = 1000.0;
+
+// Where the scene is seen from, for a screen sx by sy cells.
+pub(crate) struct Camera {
+    // World to eye space, which looks down -z
+    pub eye: Mat4,
+    // ... and on to clip space
+    pub clipspace: Mat4,
+    // Camera position in world space
+    pub position: Vec3,
+    // Screen coordinates are the center of the screen plus zoom times eye
+    // space x and -y over depth.
+    pub zoom: (f32, f32),
+    // How far the screen (plus a cell for dithering) reaches per unit depth
+    pub reach: (f32, f32),
+}
+
+impl Camera {
+    pub fn new(params: &Params, sx: usize, sy: usize) -> Camera {
+        let Params {
+            cam_dist,
+            fov,
+            orbit,
+            ..
+        } = *params;
+        let view = Mat4::from_euler_angles(orbit.1, orbit.0, 0.0);
+        let aspect = (min(sx, sy) as f32) / (max(sx, sy) as f32);
+        // Eye space has the origin cam_dist in front; turning half way round
+        // about y keeps the picture the way it's always been.
+        let eye = Mat4::new_translation(&Vec3::new(0.0, 0.0, -cam_dist))
+            * Mat4::from_euler_angles(0.0, core::f32::consts::PI, 0.0)
+            * view;
+        let projection = Mat4::new_perspective(aspect, fov, NEAR, FAR);
+        let scale = 0.5 * min(sx, sy) as f32;
+        let zoom = (scale * projection[(0, 0)], scale * projection[(1, 1)]);
+        Camera {
+            eye,
+            clipspace: projection * eye,
+            position: view
+                .transpose()
+                .transform_vector(&Vec3::new(0.0, 0.0, -cam_dist)),
+            zoom,
+            reach: (
+                (0.5 * sx as f32 + 1.0) / zoom.0,
+                (0.5 * sy as f32 + 1.0) / zoom.1,
+            ),
+        }
+    }
+}
+
+// Whether any of a sphere in eye space could land on screen, where the screen
+// reaches `reach` across and up per unit of depth.
+pub(crate) fn sphere_visible(center: &Point, radius: f32, reach: (f32, f32)) -> bool {
+    let depth = -center.z;
+    if depth + radius <= NEAR || depth - radius > FAR {
+        return false;
+    }
+    // Distances outside each side plane of the frustum
+    let outside =
+        |offset: f32, reach: f32| (offset.abs() - depth * reach) / (1.0 + reach * reach).sqrt();
+    outside(center.x, reach.0) < radius && outside(center.y, reach.1) < radius
+}
+
+// Everything about the torus' subdivisions that doesn't change from frame to
+// frame: the rotation of each ring about the axis, and the cosine and sine
+// of each step around the rings.
+#[derive(Default)]
+pub(crate) struct Subdivisions {
+    pub rings: Vec<Mat4>,
+    pub circle: Vec<(f32, f32)>,
+    // Cosine and sine of each ring's angle, and around the rings, in fixed point
+    pub fixed_rings: Vec<(i32, i32)>,
+    pub fixed_circle: Vec<(i32, i32)>,
+}
+
+impl Subdivisions {
+    fn new(n1: usize, n2: usize) -> Subdivisions {
+        let two_pi = 2.0 * core::f32::consts::PI;
+        let phi1 = |i1: usize| two_pi * (i1 as f32) / (n1 as f32);
+        let circle: Vec<_> = (0..n2)
+            .map(|i2| {
+                let phi2 = two_pi * (i2 as f32) / n2 as f32;
+                (phi2.cos(), phi2.sin())
+            })
+            .collect();
+        let fixed = |(cos, sin): (f32, f32)| (fx(cos), fx(sin));
+        Subdivisions {
+            rings: (0..n1)
+                .map(|i1| Mat4::from_euler_angles(0.0, 0.0, phi1(i1)))
+                .collect(),
+            fixed_rings: (0..n1)
+                .map(|i1| fixed((phi1(i1).cos(), phi1(i1).sin())))
+                .collect(),
+            fixed_circle: circle.iter().copied().map(fixed).collect(),
+            circle,
+        }
+    }
+}
+
+// Frames are nominally this far apart, for animating shaders.
+const FRAME_TIME: f32 = 0.05;
+
+// The torus, and where it's got to in its tumbling.
+pub struct Donut {
+    pub transform: Mat4,
+    pub shader: Box<dyn Shader + Send>,
+    // Colors points the shader leaves uncolored
+    pub palette: Option<Palette>,
+    // Seconds of animation so far
+    pub t: f32,
+    // Subdivisions of torus
+    pub n1: usize,
+    pub n2: usize,
+    // Draw the far side of the surface too, lit as if seen from the front,
+    // instead of culling it
+    pub two_sided: bool,
+    pub debug_view: Option<DebugView>,
+    // Partway to some other shape, instead of the torus
+    pub morph: Option<Morph>,
+    // Hidden donuts draw nothing but still move
+    pub visible: bool,
+    // Rebuilt whenever n1 or n2 change
+    subdivisions: RefCell<Subdivisions>,
+}
+
+impl Default for Donut {
+    fn default() -> Donut {
+        Donut::new()
+    }
+}
+
+impl Donut {
+    pub fn new() -> Donut {
+        Donut {
+            transform: Mat4::identity(),
+            shader: Box::new(Phong),
+            palette: None,
+            t: 0.0,
+            n1: 500,
+            n2: 200,
+            two_sided: false,
+            debug_view: None,
+            morph: None,
+            visible: true,
+            subdivisions: RefCell::default(),
+        }
+    }
+
+    // Advances the tumbling by one frame.
+    pub fn step(&mut self, params: &Params) {
+        let Params {
+            spin, speed, nudge, ..
+        } = *params;
+        self.transform = nudge * self.transform;
+        self.transform *= Mat4::from_euler_angles(0.0, 0.0, spin.z * speed);
+        self.transform *= Mat4::from_euler_angles(spin.x * speed, spin.y * speed, 0.0);
+        self.t += FRAME_TIME;
+    }
+
+    pub fn render(&self, params: &Params, framebuffer: &mut FrameBuffer) {
+        if !self.visible {
+            return;
+        }
+        // The fixed-point path only knows the torus
+        if cfg!(feature = "fixed") && self.morph.is_none() {
+            super::fixed::render(self, params, framebuffer);
+        } else {
+            self.render_float(params, framebuffer);
+        }
+    }
+
+    // The subdivision tables, brought up to date with n1 and n2.
+    pub(crate) fn subdivisions(&self) -> RefMut<'_, Subdivisions> {
+        let mut subdivisions = self.subdivisions.borrow_mut();
+        if subdivisions.rings.len() != self.n1 || subdivisions.circle.len() != self.n2 {
+            *subdivisions = Subdivisions::new(self.n1, self.n2);
+        }
+        subdivisions
+    }
+
+    fn render_float(&self, params: &Params, framebuffer: &mut FrameBuffer) {
+        let Params {
+            r1,
+            r2,
+            light_dir,
+            cam_dist,
+            gain,
+            ..
+        } = *params;
+        let (n1, n2) = (self.n1, self.n2);
+        let (sx, sy) = (framebuffer.sx, framebuffer.sy);
+
+        let camera = Camera::new(params, sx, sy);
+        let Camera {
+            clipspace, reach, ..
+        } = camera;
+        let scale = 0.5 * min(sx, sy) as f32;
+        // Skip the whole donut, or whole rings, when they're off screen.
+        let object_eye = camera.eye * self.transform;
+        if !sphere_visible(
+            &object_eye.transform_point(&Point::origin()),
+            r1.abs() + r2.abs(),
+            reach,
+        ) {
+            return;
+        }
+        let cam_pos = camera.position;
+
+        // For each voxel, compute screenspace position, then (if it's on screen
+        // and facing us) lighting, then (maybe) draw.
+        let span = tracing::trace_span!("transform").entered();
+        let mut samples = Vec::new();
+        let subdivisions = self.subdivisions();
+        for (i1, rot) in subdivisions.rings.iter().enumerate() {
+            // Other shapes' rings aren't circles about the ring center, so
+            // work in object space for them.
+            let ring_world = match self.morph {
+                Some(_) => self.transform,
+                None => {
+                    let ring_center =
+                        object_eye.transform_point(&rot.transform_point(&Point::new(r1, 0.0, 0.0)));
+                    if !sphere_visible(&ring_center, r2.abs(), reach) {
+                        continue;
+                    }
+                    // Ring to world space (isometry)
+                    self.transform * rot
+                }
+            };
+            // ... and on to clip space
+            let ring_clip = clipspace * ring_world;
+
+            for (i2, &(cos2, sin2)) in subdivisions.circle.iter().enumerate() {
+                // Compute screenspace position + worldspace normal (for lighting)
+                let (p_world, p_clip, mut n) = {
+                    // cp = circle point; cn = circle normal.
+                    let (cp, cn) = match &self.morph {
+                        Some(morph) => {
+                            morph.sample(i1 as f32 / n1 as f32, i2 as f32 / n2 as f32, r1, r2)
+                        }
+                        None => (
+                            Point::new(r2 * cos2 + r1, 0.0, r2 * sin2),
+                            Vec3::new(cos2, 0.0, sin2),
+                        ),
+                    };
+
+                    let p2 = ring_world.transform_point(&cp);
+                    let n2 = ring_world.transform_vector(&cn);
+                    // p3 goes to clip space (homogenous)
+                    let p3 = ring_clip * cp.to_homogeneous();
+                    // Technically, n2 should still be normalized
+                    (p2, p3, n2.normalize())
+                };
+
+                // Behind the camera or too close to it
+                if p_clip.w <= NEAR {
+                    continue;
+                }
+                let ndc = p_clip.xyz() / p_clip.w;
+                // 0 at the near plane, 1 at the far one
+                let depth = 0.5 * (ndc.z + 1.0);
+                if depth > 1.0 {
+                    continue;
+                }
+                // Rows count down the screen
+                let p_screen = Vec3::new(
+                    0.5 * sx as f32 + scale * ndc.x,
+                    0.5 * sy as f32 - scale * ndc.y,
+                    depth,
+                );
+
+                // Unit vector pointing from p_world to the camera
+                let cam_vec = (cam_pos - (p_world - Point::origin())).normalize();
+                // Facing away from the camera
+                if cam_vec.dot(&n) < 0.0 {
+                    if !self.two_sided {
+                        continue;
+                    }
+                    n = -n;
+                }
+
+                if !(p_screen.x < 0.0
+                    || p_screen.y < 0.0
+                    || p_screen.x >= sx as f32
+                    || p_screen.y >= sy as f32)
+                {
+                    samples.push(Sample {
+                        p_screen,
+                        n,
+                        cam_vec,
+                        uv: (i1 as f32 / n1 as f32, i2 as f32 / n2 as f32),
+                        // From the front of the donut to the back
+                        spread: 0.5 + (p_clip.w - cam_dist) / (2.0 * (r1 + r2).abs()),
+                    });
+                }
+            }
+        }
+        drop(span);
+
+        let _span = tracing::trace_span!("shade").entered();
+        for sample in samples {
+            let shade = self.shade(&sample, light_dir, gain);
+            if shade.brightness > 0.0 {
+                let p_screen = sample.p_screen;
+                let ix = framebuffer.dither(p_screen.x, sx);
+                let iy = framebuffer.dither(p_screen.y, sy);
+                framebuffer.poke_if(ix, iy, p_screen.z, shade);
+            }
+        }
+        if self.debug_view == Some(DebugView::Overdraw) {
+            framebuffer.show_overdraw();
+        }
+    }
+
+    // Shades a sample with the debug view, or else the shader and palette.
+    pub(crate) fn shade(&self, sample: &Sample, light_dir: Vec3, gain: f32) -> Shade {
+        let Sample {
+            n,
+            cam_vec,
+            uv,
+            spread,
+            ..
+        } = *sample;
+        let debug = self
+            .debug_view
+            .and_then(|v| v.shade(n, cam_vec, uv, spread));
+        debug.unwrap_or_else(|| {
+            let shade = self.shader.shade(n, cam_vec, light_dir, uv, self.t);
+            let light = (gain * shade.brightness).min(0.99);
+            let color = match &self.palette {
+                Some(palette) if shade.color.is_none() => Some(palette.color(light, n, spread)),
+                _ => shade.color,
+            };
+            Shade {
+                brightness: light,
+                specular: gain * shade.specular,
+                color,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A coarse donut, so debug builds render it quickly.
+    fn donut() -> Donut {
+        Donut {
+            n1: 120,
+            n2: 48,
+            ..Donut::new()
+        }
+    }
+
+    fn render(donut: &Donut, params: &Params) -> FrameBuffer {
+        let mut framebuffer = FrameBuffer::new();
+        framebuffer.seed(0);
+        framebuffer.clear(60, 30);
+        donut.render(params, &mut framebuffer);
+        framebuffer
+    }
+
+    // Brightness of each cell something landed in.
+    fn lit(framebuffer: &FrameBuffer) -> Vec<f32> {
+        (0..framebuffer.sy)
+            .flat_map(|y| (0..framebuffer.sx).map(move |x| (x, y)))
+            .filter_map(|(x, y)| framebuffer.shading(x, y))
+            .map(|(level, _)| level)
+            .collect()
+    }
+
+    #[test]
+    fn camera_inside_the_torus() {
+        // Tipped over onto the ring's center line, inside the tube, where
+        // only the inside of the surface shows
+        let params = Params {
+            cam_dist: 1.0,
+            orbit: (0.0, core::f32::consts::FRAC_PI_2),
+            ..Params::default()
+        };
+        let donut = Donut {
+            two_sided: true,
+            ..donut()
+        };
+        let levels = lit(&render(&donut, &params));
+        assert!(!levels.is_empty());
+        assert!(levels.iter().all(|level| level.is_finite()));
+    }
+
+    #[test]
+    fn wide_glyphs_in_a_single_column() {
+        // Too narrow for even one wide glyph, but still a column of cells
+        let mut framebuffer = FrameBuffer::new();
+        framebuffer.ramp = crate::ramp::RAMPS
+            .iter()
+            .find(|(name, _)| *name == "kanji")
+            .map(|(_, glyphs)| glyphs.chars().collect())
+            .unwrap();
+        framebuffer.clear(1, 30);
+        assert_eq!((framebuffer.sx, framebuffer.sy), (1, 30));
+        donut().render(&Params::default(), &mut framebuffer);
+        let levels = lit(&framebuffer);
+        assert!(!levels.is_empty());
+        assert!(levels.iter().all(|level| level.is_finite()));
+    }
+
+    #[test]
+    fn off_screen_donut_draws_nothing() {
+        let params = Params::default();
+        let aside = Donut {
+            transform: Mat4::new_translation(&Vec3::new(20.0, 0.0, 0.0)),
+            ..donut()
+        };
+        assert!(lit(&render(&aside, &params)).is_empty());
+        assert!(!lit(&render(&donut(), &params)).is_empty());
+
+        // The whole donut and its near rings are culled before any sampling
+        let camera = Camera::new(&params, 60, 30);
+        let eye = camera.eye * aside.transform;
+        assert!(!sphere_visible(
+            &eye.transform_point(&Point::origin()),
+            params.r1 + params.r2,
+            camera.reach
+        ));
+        let behind = Point::new(0.0, 0.0, 1.0);
+        assert!(!sphere_visible(&behind, 0.5, camera.reach));
+    }
+
+    #[test]
+    fn two_sided_draws_the_back_of_an_open_mesh() {
+        use crate::mesh::Mesh;
+        // A square, facing the camera unless turned over
+        let square = b"v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nf 1 2 3 4\n";
+        let mesh = Mesh::parse(square, 2000).unwrap();
+        let params = Params::default();
+        let draw = |turned: bool, two_sided: bool| {
+            let look = Donut {
+                transform: match turned {
+                    true => Mat4::from_euler_angles(core::f32::consts::PI, 0.0, 0.0),
+                    false => Mat4::identity(),
+                },
+                two_sided,
+                ..donut()
+            };
+            let mut framebuffer = FrameBuffer::new();
+            framebuffer.seed(0);
+            framebuffer.clear(60, 30);
+            mesh.render(&look, &params, &mut framebuffer);
+            lit(&framebuffer).len()
+        };
+        let (front, back) = (draw(false, false), draw(true, false));
+        assert_eq!(front.min(back), 0);
+        assert!(front.max(back) > 0);
+        assert!(draw(false, true) > 0 && draw(true, true) > 0);
+    }
+}
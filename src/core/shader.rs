@@ -0,0 +1,60 @@
+use crate::color::Rgb;
+use crate::Vec3;
+
+// What a shader decides for one point on the surface. Points with no
+// brightness aren't drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shade {
+    pub brightness: f32,
+    // How much of the brightness is specular highlight
+    pub specular: f32,
+    // None leaves the point in the default (or tint) color.
+    pub color: Option<Rgb>,
+}
+
+// Shading for a point given its unit normal, unit vectors towards the viewer
+// and the light, its surface coordinates (each in [0, 1)) and the time in
+// seconds. Plain closures with the same signature work as shaders too.
+pub trait Shader {
+    fn shade(&self, normal: Vec3, view: Vec3, light: Vec3, uv: (f32, f32), t: f32) -> Shade;
+
+    // Whether this is the default Phong shading, which fixed-point builds
+    // do in integers; anything else they shade as usual, in floats.
+    fn is_phong(&self) -> bool {
+        false
+    }
+}
+
+impl<F: Fn(Vec3, Vec3, Vec3, (f32, f32), f32) -> Shade> Shader for F {
+    fn shade(&self, normal: Vec3, view: Vec3, light: Vec3, uv: (f32, f32), t: f32) -> Shade {
+        self(normal, view, light, uv, t)
+    }
+}
+
+fn relu(x: f32) -> f32 {
+    if x >= 0.0 {
+        x
+    } else {
+        0.0
+    }
+}
+
+// The default: Phong-ish diffuse plus a cubed specular term.
+pub struct Phong;
+
+impl Shader for Phong {
+    fn shade(&self, n: Vec3, view: Vec3, light: Vec3, _uv: (f32, f32), _t: f32) -> Shade {
+        let a = relu(n.dot(&light));
+        let r = 2.0 * a * n.dot(&view) - light.dot(&view);
+        let specular = 0.25 * r * r * r;
+        Shade {
+            brightness: 0.75 * a + specular,
+            specular,
+            color: None,
+        }
+    }
+
+    fn is_phong(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{Point, Vec3};
+use alloc::vec::Vec;
+use core::f32::consts::TAU;
+#[cfg(not(feature = "std"))]
+use nalgebra::ComplexField;
+
+// Surfaces the donut can turn into, all over the same (u, v) grid as the
+// torus: u along the rings, v around them, both in [0, 1). Each fits inside
+// the torus' bounding sphere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Torus,
+    // The torus with no hole: each ring a great circle, so v goes round
+    // twice over
+    Sphere,
+    // A tube around a trefoil knot
+    Knot,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 3] = [Shape::Torus, Shape::Sphere, Shape::Knot];
+
+    pub fn parse(name: &str) -> Option<Shape> {
+        match name {
+            "torus" => Some(Shape::Torus),
+            "sphere" => Some(Shape::Sphere),
+            "knot" => Some(Shape::Knot),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shape::Torus => "torus",
+            Shape::Sphere => "sphere",
+            Shape::Knot => "knot",
+        }
+    }
+
+    // Point and unit normal at (u, v), in object space.
+    pub fn sample(self, u: f32, v: f32, r1: f32, r2: f32) -> (Point, Vec3) {
+        let (cos_v, sin_v) = ((TAU * v).cos(), (TAU * v).sin());
+        match self {
+            Shape::Torus | Shape::Sphere => {
+                let (cos_u, sin_u) = ((TAU * u).cos(), (TAU * u).sin());
+                let (r1, r2) = match self {
+                    Shape::Torus => (r1, r2),
+                    _ => (0.0, r1 + r2),
+                };
+                let n = Vec3::new(cos_v * cos_u, cos_v * sin_u, sin_v);
+                (Point::new(r1 * cos_u, r1 * sin_u, 0.0) + r2 * n, n)
+            }
+            Shape::Knot => {
+                // The (2, 3) torus knot, wound round a torus of radii r1 and
+                // 0.6 r2, with a tube of 0.4 r2 around it, framed by its
+                // tangent, principal normal and binormal.
+                let a = 0.6 * r2;
+                let curve = |u: f32| {
+                    let phi = TAU * u;
+                    let r = r1 + a * (3.0 * phi).cos();
+                    Vec3::new(
+                        r * (2.0 * phi).cos(),
+                        r * (2.0 * phi).sin(),
+                        a * (3.0 * phi).sin(),
+                    )
+                };
+                let h = 1e-3;
+                let (before, at, after) = (curve(u - h), curve(u), curve(u + h));
+                let tangent = (after - before).normalize();
+                let bend = after - 2.0 * at + before;
+                let normal = (bend - bend.dot(&tangent) * tangent).normalize();
+                let binormal = tangent.cross(&normal);
+                let n = cos_v * normal + sin_v * binormal;
+                (Point::from(at + 0.4 * r2 * n), n)
+            }
+        }
+    }
+}
+
+// Partway from one shape to another: t = 0 is all `from`, 1 all `to`.
+#[derive(Clone, Copy, Debug)]
+pub struct Morph {
+    pub from: Shape,
+    pub to: Shape,
+    pub t: f32,
+}
+
+impl Morph {
+    // Blends the points and normals at (u, v). Blended normals are shorter
+    // than unit length (or even zero, where the two face opposite ways), so
+    // they're renormalized, falling back to the nearer shape's.
+    pub fn sample(&self, u: f32, v: f32, r1: f32, r2: f32) -> (Point, Vec3) {
+        let (p0, n0) = self.from.sample(u, v, r1, r2);
+        if self.t <= 0.0 || self.from == self.to {
+            return (p0, n0);
+        }
+        let (p1, n1) = self.to.sample(u, v, r1, r2);
+        let p = p0 + (p1 - p0) * self.t;
+        let n = n0.lerp(&n1, self.t);
+        let n = match n.try_normalize(1e-4) {
+            Some(n) => n,
+            None if self.t < 0.5 => n0,
+            None => n1,
+        };
+        (p, n)
+    }
+}
+
+// Morphs through a list of shapes and back to the first, holding each for
+// `hold` seconds then easing into the next over `blend`.
+pub struct Timeline {
+    pub shapes: Vec<Shape>,
+    pub hold: f32,
+    pub blend: f32,
+}
+
+impl Timeline {
+    pub fn new(shapes: Vec<Shape>) -> Timeline {
+        Timeline {
+            shapes,
+            hold: 3.0,
+            blend: 2.0,
+        }
+    }
+
+    // Where it's got to `t` seconds in.
+    pub fn at(&self, t: f32) -> Morph {
+        let n = self.shapes.len().max(1);
+        let period = self.hold + self.blend;
+        let step = t.max(0.0) / period;
+        let ix = step as usize % n;
+        let into = (step - step.floor()) * period - self.hold;
+        let t = (into / self.blend).clamp(0.0, 1.0);
+        let get = |i: usize| self.shapes.get(i % n).copied().unwrap_or(Shape::Torus);
+        Morph {
+            from: get(ix),
+            to: get(ix + 1),
+            // Smoothstep, so shapes ease in and out
+            t: t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use crate::color::Rgb;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cell {
+    pub glyph: char,
+    pub color: Option<Rgb>,
+}
+
+impl Cell {
+    pub const BLANK: Cell = Cell {
+        glyph: ' ',
+        color: None,
+    };
+
+    // Stands in the column after a wide glyph, which covers it.
+    pub const TAIL: char = '\0';
+}
+
+// Whether a glyph takes two columns: CJK, Hangul, full-width forms and most
+// emoji.
+pub fn is_wide(glyph: char) -> bool {
+    let c = glyph as u32;
+    (0x1100..=0x115f).contains(&c)
+        || (0x2e80..=0xa4cf).contains(&c)
+        || (0xac00..=0xd7a3).contains(&c)
+        || (0xf900..=0xfaff).contains(&c)
+        || (0xfe30..=0xfe4f).contains(&c)
+        || (0xff00..=0xff60).contains(&c)
+        || (0xffe0..=0xffe6).contains(&c)
+        || (0x1f300..=0x1f64f).contains(&c)
+        || (0x1f900..=0x1f9ff).contains(&c)
+        || (0x20000..=0x3fffd).contains(&c)
+}
+
+// Anywhere a frame can be drawn: the terminal, a buffer in memory, ... Frames
+// are drawn by putting every cell, then presenting.
+pub trait RenderTarget {
+    // Size in cells; may change between frames (e.g. the terminal resizing).
+    fn dimensions(&self) -> (usize, usize);
+    // Cells outside the dimensions are ignored.
+    fn put(&mut self, x: usize, y: usize, cell: Cell);
+    fn present(&mut self) -> crate::Result<()>;
+
+    fn put_str(&mut self, x: usize, y: usize, s: &str, color: Option<Rgb>) {
+        for (i, glyph) in s.chars().enumerate() {
+            self.put(x + i, y, Cell { glyph, color });
+        }
+    }
+}
+
+// A fixed-size grid of cells in memory, for embedding or inspecting frames.
+#[derive(Clone)]
+pub struct CellBuffer {
+    sx: usize,
+    sy: usize,
+    cells: Vec<Cell>,
+}
+
+impl CellBuffer {
+    pub fn new(sx: usize, sy: usize) -> CellBuffer {
+        CellBuffer {
+            sx,
+            sy,
+            cells: vec![Cell::BLANK; sx * sy],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Cell {
+        self.cells[y * self.sx + x]
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.iter_mut().for_each(|c| *c = Cell::BLANK);
+    }
+
+    // What to write out for a cell: nothing for the tail of a wide glyph,
+    // and a space for half a wide glyph left after something else was put
+    // over the other half, so every row still takes its width in columns.
+    pub fn shown(&self, x: usize, y: usize) -> Option<char> {
+        let glyph = self.get(x, y).glyph;
+        if glyph == Cell::TAIL {
+            let covered = x > 0 && is_wide(self.get(x - 1, y).glyph);
+            return (!covered).then_some(' ');
+        }
+        if is_wide(glyph) && (x + 1 >= self.sx || self.get(x + 1, y).glyph != Cell::TAIL) {
+            return Some(' ');
+        }
+        Some(glyph)
+    }
+
+    // The glyphs, one line per row.
+    pub fn text(&self) -> String {
+        let mut s = String::with_capacity((self.sx + 1) * self.sy);
+        for y in 0..self.sy {
+            s.extend((0..self.sx).filter_map(|x| self.shown(x, y)));
+            s.push('\n');
+        }
+        s
+    }
+
+    // FNV-1a over the glyphs and colors: stable across runs and Rust versions,
+    // for comparing frames against known-good ones.
+    pub fn hash(&self) -> u64 {
+        let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for &b in bytes {
+                h = (h ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        feed(&(self.sx as u32).to_le_bytes());
+        feed(&(self.sy as u32).to_le_bytes());
+        for cell in &self.cells {
+            feed(&(cell.glyph as u32).to_le_bytes());
+            match cell.color {
+                Some(Rgb(r, g, b)) => feed(&[1, r, g, b]),
+                None => feed(&[0]),
+            }
+        }
+        h
+    }
+}
+
+impl RenderTarget for CellBuffer {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.sx, self.sy)
+    }
+
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        if x < self.sx && y < self.sy {
+            self.cells[y * self.sx + x] = cell;
+        }
+    }
+
+    fn present(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+// A rectangle of cells: `w` columns across from `x`, `h` rows down from `y`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+// Changed cells this close together in a row go out as one run: moving the
+// cursor past the ones in between costs about as much as drawing them.
+const GAP: usize = 4;
+
+// Double buffering for targets that send only what changed: each frame is put
+// into the back buffer, then swap() makes it the front, giving the
+// rectangles where it differs from the frame before.
+pub struct Buffers {
+    front: CellBuffer,
+    back: CellBuffer,
+    // Nothing's been shown yet, or what has can't be trusted, so the next
+    // frame is damaged all over
+    stale: bool,
+}
+
+impl Buffers {
+    pub fn new(sx: usize, sy: usize) -> Buffers {
+        Buffers {
+            front: CellBuffer::new(sx, sy),
+            back: CellBuffer::new(sx, sy),
+            stale: true,
+        }
+    }
+
+    // The frame last swapped in, for drawing what's damaged.
+    pub fn front(&self) -> &CellBuffer {
+        &self.front
+    }
+
+    // Starts afresh at a new size, if it is one.
+    pub fn resize(&mut self, sx: usize, sy: usize) {
+        if (sx, sy) != self.back.dimensions() {
+            *self = Buffers::new(sx, sy);
+        }
+    }
+
+    // Damages the whole of the next frame, e.g. after the screen's cleared.
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    // Whether the next swap damages everything.
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    // Makes the frame put since the last swap the front, and starts the next
+    // one blank. Returns what changed, top to bottom: runs of cells in a row,
+    // with rows changed in the same columns one after another as one
+    // rectangle. Runs take in both halves of any wide glyph they touch.
+    pub fn swap(&mut self) -> Vec<Rect> {
+        let (sx, sy) = self.back.dimensions();
+        let mut damage: Vec<Rect> = Vec::new();
+        let mut add = |rect: Rect| match damage.last_mut() {
+            Some(last) if (last.x, last.w, last.y + last.h) == (rect.x, rect.w, rect.y) => {
+                last.h += 1
+            }
+            _ => damage.push(rect),
+        };
+        if self.stale {
+            if sx > 0 {
+                (0..sy).for_each(|y| {
+                    add(Rect {
+                        x: 0,
+                        y,
+                        w: sx,
+                        h: 1,
+                    })
+                });
+            }
+        } else {
+            let wide = |x: usize, y: usize| {
+                is_wide(self.front.get(x, y).glyph) || is_wide(self.back.get(x, y).glyph)
+            };
+            for y in 0..sy {
+                // Runs of changed cells, from start up to end
+                let mut runs: Vec<(usize, usize)> = Vec::new();
+                for x in (0..sx).filter(|&x| self.front.get(x, y) != self.back.get(x, y)) {
+                    match runs.last_mut() {
+                        Some((_, end)) if x - *end < GAP => *end = x + 1,
+                        _ => runs.push((x, x + 1)),
+                    }
+                }
+                for (mut start, mut end) in runs {
+                    if start > 0 && wide(start - 1, y) {
+                        start -= 1;
+                    }
+                    if end < sx && wide(end - 1, y) {
+                        end += 1;
+                    }
+                    add(Rect {
+                        x: start,
+                        y,
+                        w: end - start,
+                        h: 1,
+                    });
+                }
+            }
+        }
+        self.stale = false;
+        core::mem::swap(&mut self.front, &mut self.back);
+        self.back.clear();
+        damage
+    }
+}
+
+impl RenderTarget for Buffers {
+    fn dimensions(&self) -> (usize, usize) {
+        self.back.dimensions()
+    }
+
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        self.back.put(x, y, cell);
+    }
+
+    // Nothing to show it on; see swap().
+    fn present(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Puts a frame of `glyphs` at their columns and rows, the rest blank, and
+    // swaps it in.
+    fn frame(buffers: &mut Buffers, glyphs: &[(usize, usize, char)]) -> Vec<Rect> {
+        for &(x, y, glyph) in glyphs {
+            buffers.put(x, y, Cell { glyph, color: None });
+        }
+        buffers.swap()
+    }
+
+    fn rect(x: usize, y: usize, w: usize, h: usize) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    #[test]
+    fn swap_damages_only_what_changed() {
+        let mut buffers = Buffers::new(20, 5);
+        assert_eq!(frame(&mut buffers, &[(3, 1, '#')]), [rect(0, 0, 20, 5)]);
+        assert_eq!(frame(&mut buffers, &[(3, 1, '#')]), []);
+        // Moved: where it was and where it is
+        assert_eq!(
+            frame(&mut buffers, &[(12, 1, '#')]),
+            [rect(3, 1, 1, 1), rect(12, 1, 1, 1)]
+        );
+        // Close changes go as one run, and runs in the same columns of
+        // consecutive rows as one rectangle
+        let glyphs = [
+            (12, 1, '#'),
+            (5, 2, '@'),
+            (7, 2, '@'),
+            (5, 3, '-'),
+            (7, 3, '-'),
+        ];
+        assert_eq!(frame(&mut buffers, &glyphs), [rect(5, 2, 3, 2)]);
+        // Both halves of a wide glyph, changing either
+        let mut buffers = Buffers::new(20, 5);
+        let wide = [(4, 0, '字'), (5, 0, Cell::TAIL)];
+        frame(&mut buffers, &wide);
+        assert_eq!(frame(&mut buffers, &wide[..1]), [rect(4, 0, 2, 1)]);
+    }
+
+    #[test]
+    fn resizing_or_invalidating_damages_everything() {
+        let mut buffers = Buffers::new(20, 5);
+        frame(&mut buffers, &[(3, 1, '#')]);
+        buffers.resize(20, 5);
+        assert_eq!(frame(&mut buffers, &[(3, 1, '#')]), []);
+        buffers.resize(10, 4);
+        assert_eq!(buffers.dimensions(), (10, 4));
+        assert_eq!(frame(&mut buffers, &[(3, 1, '#')]), [rect(0, 0, 10, 4)]);
+        assert_eq!(frame(&mut buffers, &[(3, 1, '#')]), []);
+        buffers.invalidate();
+        assert_eq!(frame(&mut buffers, &[(3, 1, '#')]), [rect(0, 0, 10, 4)]);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::input::Input;
+use forbidden_donut::color::Rgb;
+use forbidden_donut::{Params, Vec3};
+use std::f32::consts::{PI, TAU};
+
+// --daylight SECONDS: the light goes round the scene once a day, rising on
+// one side, over the top and setting on the other, then dimly round
+// underneath for the night. Low in the sky it's tinted warm, at night cool.
+pub struct Daylight {
+    // The sun's angle from the right-hand horizon, and how far it goes a frame
+    angle: f32,
+    step: f32,
+    // What it last scaled the gain by, to undo before scaling it anew
+    light: f32,
+}
+
+// The sun's path leans this far towards the camera from straight overhead
+const TILT: f32 = 0.5;
+// How bright night is, and how far above or below the horizon the sun is
+// (as the sine of its elevation) when day turns to night
+const NIGHT: f32 = 0.35;
+const TWILIGHT: f32 = 0.25;
+const NOON: Rgb = Rgb(255, 250, 235);
+const DUSK: Rgb = Rgb(255, 140, 60);
+const MIDNIGHT: Rgb = Rgb(110, 130, 255);
+
+impl Daylight {
+    pub fn new(seconds: f32, fps: u32) -> Daylight {
+        Daylight {
+            // Mid morning
+            angle: PI / 6.0,
+            step: TAU / (seconds * fps as f32),
+            light: 1.0,
+        }
+    }
+}
+
+impl Input for Daylight {
+    fn apply(&mut self, params: &mut Params) {
+        let (sin, cos) = self.angle.sin_cos();
+        let up = Vec3::new(0.0, TILT.cos(), -TILT.sin());
+        params.light_dir = (cos * Vec3::x() + sin * up).normalize();
+
+        let t = ((sin + TWILIGHT) / (2.0 * TWILIGHT)).clamp(0.0, 1.0);
+        let day = t * t * (3.0 - 2.0 * t);
+        let light = NIGHT + (1.0 - NIGHT) * day;
+        params.gain *= light / self.light;
+        self.light = light;
+        params.tint = Some(if sin >= 0.0 {
+            DUSK.lerp(NOON, sin / 0.5)
+        } else {
+            DUSK.lerp(MIDNIGHT, -sin / TWILIGHT)
+        });
+        self.angle = (self.angle + self.step) % TAU;
+    }
+}
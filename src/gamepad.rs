@@ -0,0 +1,54 @@
+use crate::input::Input;
+use forbidden_donut::Params;
+use gilrs::{Axis, Button, Gilrs};
+
+// Left stick tumbles the torus, right stick orbits the camera, and the
+// triggers zoom in (right) and out (left), using the first connected pad.
+pub struct Gamepad {
+    gilrs: Gilrs,
+    // What the left stick added to the spin rates last frame
+    stick: (f32, f32),
+}
+
+const DEADZONE: f32 = 0.15;
+
+impl Gamepad {
+    pub fn open() -> std::result::Result<Gamepad, String> {
+        let gilrs = Gilrs::new().map_err(|e| e.to_string())?;
+        Ok(Gamepad {
+            gilrs,
+            stick: (0.0, 0.0),
+        })
+    }
+}
+
+impl Input for Gamepad {
+    fn apply(&mut self, params: &mut Params) {
+        // Drain events so the cached gamepad state is current
+        while self.gilrs.next_event().is_some() {}
+
+        if let Some((_, pad)) = self.gilrs.gamepads().next() {
+            let axis = |a: Axis| {
+                let v = pad.value(a);
+                if v.abs() < DEADZONE {
+                    0.0
+                } else {
+                    v
+                }
+            };
+            let trigger = |b: Button| pad.button_data(b).map_or(0.0, |d| d.value());
+
+            let stick = (0.15 * axis(Axis::LeftStickY), 0.15 * axis(Axis::LeftStickX));
+            params.spin.x += stick.0 - self.stick.0;
+            params.spin.y += stick.1 - self.stick.1;
+            self.stick = stick;
+
+            let (yaw, pitch) = &mut params.orbit;
+            *yaw += 0.05 * axis(Axis::RightStickX);
+            *pitch = (*pitch + 0.05 * axis(Axis::RightStickY)).clamp(-1.5, 1.5);
+
+            let zoom = trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2);
+            params.cam_dist = (params.cam_dist - 0.1 * zoom).clamp(2.5, 12.0);
+        }
+    }
+}
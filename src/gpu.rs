@@ -0,0 +1,310 @@
+// The `gpu` feature: donuts transformed, shaded and depth tested in compute
+// shaders (gpu.wgsl), for millions of samples or dozens of donuts a frame.
+// What comes back is a few running totals per cell, which the framebuffer
+// turns into glyphs as usual. Only the plain torus with Phong shading runs
+// there; morphing donuts and debug views are drawn on the CPU instead.
+use crate::framebuffer::FrameBuffer;
+use crate::palette::Palette;
+use crate::render::{sphere_visible, Camera};
+use crate::shader::Shade;
+use crate::{Donut, Params, Point, Vec3};
+use std::future::Future;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+// What each cell comes back as, in u32s: see Cell in gpu.wgsl
+const CELL_WORDS: usize = 8;
+const ONE: f32 = 1024.0;
+// The low bits of each cell's nearest depth say which donut it was, so at
+// most this many go in a frame
+const OBJECT_BITS: u32 = 0xff;
+const WORKGROUP: u32 = 64;
+const MAX_GROUPS: u32 = 65535;
+// As z = 1, the far plane, where the framebuffer starts too
+const FARTHEST: u32 = 0x3f80_0000;
+
+pub struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    layout: wgpu::BindGroupLayout,
+    depth: wgpu::ComputePipeline,
+    shade: wgpu::ComputePipeline,
+    // The cells, and a copy of them to read back, for a screen this size
+    cells: Option<(wgpu::Buffer, wgpu::Buffer, (usize, usize))>,
+    // Uniforms for each donut queued, bound with the cells
+    slots: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    // The donuts queued for this frame: samples, and their palettes
+    queued: Vec<(u32, Option<Palette>)>,
+}
+
+impl Gpu {
+    // The first adapter wgpu finds (WGPU_BACKEND and WGPU_ADAPTER_NAME pick
+    // another).
+    pub fn new() -> Result<Gpu, String> {
+        let instance =
+            wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle_from_env());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .map_err(|e| e.to_string())?;
+        let (device, queue) = block_on(adapter.request_device(&wgpu::DeviceDescriptor::default()))
+            .map_err(|e| e.to_string())?;
+        tracing::info!("gpu: {}", adapter.get_info().name);
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("donut"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let buffer = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                buffer(0, wgpu::BufferBindingType::Uniform),
+                buffer(1, wgpu::BufferBindingType::Storage { read_only: false }),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[Some(&layout)],
+            immediate_size: 0,
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        Ok(Gpu {
+            depth: pipeline("depth"),
+            shade: pipeline("shade"),
+            device,
+            queue,
+            layout,
+            cells: None,
+            slots: Vec::new(),
+            queued: Vec::new(),
+        })
+    }
+
+    // Queues a donut for finish(), or draws it on the CPU if it's something
+    // the GPU doesn't do.
+    pub fn draw(&mut self, donut: &Donut, params: &Params, framebuffer: &mut FrameBuffer) {
+        if !donut.visible {
+            return;
+        }
+        let queued = self.queued.len();
+        if donut.morph.is_some() || donut.debug_view.is_some() || queued > OBJECT_BITS as usize {
+            donut.render(params, framebuffer);
+            return;
+        }
+        let (sx, sy) = (framebuffer.sx, framebuffer.sy);
+        if sx == 0 || sy == 0 {
+            return;
+        }
+        let camera = Camera::new(params, sx, sy);
+        let radius = (params.r1 + params.r2).abs();
+        let center = (camera.eye * donut.transform).transform_point(&Point::origin());
+        if !sphere_visible(&center, radius, camera.reach) {
+            return;
+        }
+        self.resize(sx, sy);
+
+        let seed = framebuffer.dither_seed();
+        let vec4 = |v: Vec3, w: f32| [v.x, v.y, v.z, w];
+        let mut floats = Vec::with_capacity(48);
+        floats.extend_from_slice((camera.clipspace * donut.transform).as_slice());
+        floats.extend_from_slice(donut.transform.as_slice());
+        floats.extend(vec4(camera.position, params.cam_dist));
+        floats.extend(vec4(params.light_dir, params.gain));
+        let scale = 0.5 * sx.min(sy) as f32;
+        floats.extend([sx as f32, sy as f32, scale, radius]);
+        floats.extend([params.r1, params.r2, donut.n1 as f32, donut.n2 as f32]);
+        let mut words: Vec<u32> = floats.iter().map(|x| x.to_bits()).collect();
+        words.extend([
+            donut.two_sided as u32,
+            seed.unwrap_or(0),
+            seed.is_some() as u32,
+            queued as u32,
+        ]);
+
+        if self.slots.len() == queued {
+            let uniforms = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: (words.len() * 4) as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = self.bind(&uniforms);
+            self.slots.push((uniforms, bind_group));
+        }
+        self.queue
+            .write_buffer(&self.slots[queued].0, 0, &bytes(&words));
+        let samples = (donut.n1 * donut.n2) as u32;
+        self.queued.push((samples, donut.palette.clone()));
+    }
+
+    // Runs the donuts queued and shades the framebuffer with them.
+    pub fn finish(&mut self, framebuffer: &mut FrameBuffer) {
+        let queued = std::mem::take(&mut self.queued);
+        let (cells, readback, (sx, sy)) = match &self.cells {
+            Some(cells) if !queued.is_empty() => cells,
+            _ => return,
+        };
+        let size = sx * sy;
+        let mut clear = vec![0; size * CELL_WORDS];
+        for cell in clear.chunks_mut(CELL_WORDS) {
+            cell[0] = FARTHEST;
+        }
+        self.queue.write_buffer(cells, 0, &bytes(&clear));
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            // All the depths first, so every donut is shaded against the
+            // nearest of them all
+            for pipeline in [&self.depth, &self.shade] {
+                pass.set_pipeline(pipeline);
+                for ((samples, _), (_, bind_group)) in queued.iter().zip(&self.slots) {
+                    let groups = samples.div_ceil(WORKGROUP);
+                    pass.set_bind_group(0, bind_group, &[]);
+                    pass.dispatch_workgroups(
+                        groups.min(MAX_GROUPS),
+                        groups.div_ceil(MAX_GROUPS),
+                        1,
+                    );
+                }
+            }
+        }
+        encoder.copy_buffer_to_buffer(cells, 0, readback, 0, None);
+        self.queue.submit([encoder.finish()]);
+
+        let (done, mapped) = mpsc::channel();
+        readback.map_async(wgpu::MapMode::Read, .., move |result| {
+            done.send(result).ok();
+        });
+        let polled = self.device.poll(wgpu::PollType::wait_indefinitely());
+        if let Err(e) = polled.map_err(|e| e.to_string()).and_then(|_| {
+            let result = mapped.recv().map_err(|e| e.to_string())?;
+            result.map_err(|e| e.to_string())
+        }) {
+            tracing::warn!("gpu: {}", e);
+            return;
+        }
+        let totals: Vec<u32> = match readback.get_mapped_range(..) {
+            Ok(view) => view
+                .chunks_exact(4)
+                .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+                .collect(),
+            Err(e) => {
+                tracing::warn!("gpu: {}", e);
+                Vec::new()
+            }
+        };
+        readback.unmap();
+
+        for (ix, cell) in totals.chunks_exact(CELL_WORDS).enumerate() {
+            let samples = cell[1];
+            if samples == 0 {
+                continue;
+            }
+            let average = |total: u32| total as i32 as f32 / ONE / samples as f32;
+            let brightness = cell[2] as f32 / ONE / samples as f32;
+            let n = Vec3::new(average(cell[4]), average(cell[5]), average(cell[6]));
+            let n = n.try_normalize(1e-6).unwrap_or(n);
+            let spread = average(cell[7]);
+            let palette = &queued[(cell[0] & OBJECT_BITS) as usize].1;
+            let shade = Shade {
+                brightness,
+                specular: average(cell[3]),
+                color: palette
+                    .as_ref()
+                    .map(|palette| palette.color(brightness, n, spread)),
+            };
+            let z = f32::from_bits(cell[0] & !OBJECT_BITS);
+            framebuffer.poke_if(ix % sx, ix / sx, z, shade);
+        }
+    }
+
+    // Buffers for a screen sx by sy cells, if they aren't already.
+    fn resize(&mut self, sx: usize, sy: usize) {
+        if matches!(self.cells, Some((_, _, size)) if size == (sx, sy)) {
+            return;
+        }
+        let size = (sx * sy * CELL_WORDS * 4) as u64;
+        let buffer = |usage| {
+            self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        use wgpu::BufferUsages as Usage;
+        let cells = buffer(Usage::STORAGE | Usage::COPY_DST | Usage::COPY_SRC);
+        let readback = buffer(Usage::MAP_READ | Usage::COPY_DST);
+        self.cells = Some((cells, readback, (sx, sy)));
+        // The old bind groups are for the old cells
+        let slots = std::mem::take(&mut self.slots);
+        self.slots = slots
+            .into_iter()
+            .map(|(uniforms, _)| {
+                let bind_group = self.bind(&uniforms);
+                (uniforms, bind_group)
+            })
+            .collect();
+    }
+
+    fn bind(&self, uniforms: &wgpu::Buffer) -> wgpu::BindGroup {
+        let cells = &self.cells.as_ref().expect("cells before binding").0;
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: cells.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
+
+fn bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+// Runs a future on this thread. wgpu's native futures are ready as soon as
+// they're polled, or wake us when they are.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(std::thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
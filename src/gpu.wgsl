@@ -0,0 +1,174 @@
+// The donut on the GPU, as Donut::render_float does it on the CPU: one
+// invocation per sample, in two passes. `depth` finds the nearest sample in
+// each cell, then `shade` adds up the ones near enough to it, for the CPU to
+// average and turn into glyphs.
+
+struct Object {
+    // Object to clip space, and to world space
+    clipspace: mat4x4<f32>,
+    world: mat4x4<f32>,
+    // Camera position, and its distance from the origin
+    camera: vec4<f32>,
+    // Unit vector towards the light, and the gain
+    light: vec4<f32>,
+    // Columns, rows, cells per unit of normalized device coordinates, and
+    // r1 + r2
+    screen: vec4<f32>,
+    // r1, r2, n1, n2
+    torus: vec4<f32>,
+    // Two sided, the dithering seed, whether to dither, and which object
+    // this is
+    flags: vec4<u32>,
+}
+
+// Running totals for a cell. The nearest depth's low bits hold which object
+// it belongs to; brightness and the rest are in 1/1024ths.
+struct Cell {
+    nearest: atomic<u32>,
+    samples: atomic<u32>,
+    brightness: atomic<u32>,
+    specular: atomic<i32>,
+    nx: atomic<i32>,
+    ny: atomic<i32>,
+    nz: atomic<i32>,
+    spread: atomic<i32>,
+}
+
+@group(0) @binding(0) var<uniform> object: Object;
+@group(0) @binding(1) var<storage, read_write> cells: array<Cell>;
+
+const TAU: f32 = 6.2831853;
+const NEAR: f32 = 0.1;
+// As framebuffer.rs
+const DEPTH_BAND: f32 = 0.03;
+const OBJECT_BITS: u32 = 0xffu;
+const ONE: f32 = 1024.0;
+
+struct Point {
+    drawn: bool,
+    cell: u32,
+    // Depth with the object in its low bits
+    key: u32,
+    n: vec3<f32>,
+    light: f32,
+    specular: f32,
+    spread: f32,
+}
+
+fn hash(x: u32) -> u32 {
+    var h = x;
+    h ^= h >> 16u;
+    h *= 0x7feb352du;
+    h ^= h >> 15u;
+    h *= 0x846ca68bu;
+    h ^= h >> 16u;
+    return h;
+}
+
+// In [-0.5, 0.5)
+fn noise(h: u32) -> f32 {
+    return f32(h >> 8u) / 16777216.0 - 0.5;
+}
+
+// The sample's cell and shading, if it's on screen, facing us and lit.
+fn sample(i: u32) -> Point {
+    var out: Point;
+    out.drawn = false;
+    let n1 = u32(object.torus.z);
+    let n2 = u32(object.torus.w);
+    if i >= n1 * n2 {
+        return out;
+    }
+    let phi1 = TAU * f32(i / n2) / f32(n1);
+    let phi2 = TAU * f32(i % n2) / f32(n2);
+    let r1 = object.torus.x;
+    let r2 = object.torus.y;
+    let ring = vec2<f32>(cos(phi1), sin(phi1));
+    let local = vec4<f32>((r1 + r2 * cos(phi2)) * ring, r2 * sin(phi2), 1.0);
+    let normal = vec4<f32>(cos(phi2) * ring, sin(phi2), 0.0);
+
+    let clip = object.clipspace * local;
+    if clip.w <= NEAR {
+        return out;
+    }
+    let ndc = clip.xyz / clip.w;
+    let z = 0.5 * (ndc.z + 1.0);
+    if z > 1.0 {
+        return out;
+    }
+    let p_world = (object.world * local).xyz;
+    var n = normalize((object.world * normal).xyz);
+    let view = normalize(object.camera.xyz - p_world);
+    if dot(view, n) < 0.0 {
+        if object.flags.x == 0u {
+            return out;
+        }
+        n = -n;
+    }
+    let sx = object.screen.x;
+    let sy = object.screen.y;
+    let scale = object.screen.z;
+    var p = vec2<f32>(0.5 * sx + scale * ndc.x, 0.5 * sy - scale * ndc.y);
+    if p.x < 0.0 || p.y < 0.0 || p.x >= sx || p.y >= sy {
+        return out;
+    }
+
+    // Phong, as shader.rs
+    let light = object.light.xyz;
+    let gain = object.light.w;
+    let a = max(dot(n, light), 0.0);
+    let r = 2.0 * a * dot(n, view) - dot(light, view);
+    let specular = 0.25 * r * r * r;
+    out.light = min(gain * (0.75 * a + specular), 0.99);
+    if out.light <= 0.0 {
+        return out;
+    }
+    out.specular = gain * specular;
+
+    if object.flags.z != 0u {
+        let h = hash(i ^ object.flags.y);
+        p += vec2<f32>(noise(h), noise(hash(h)));
+    }
+    let cell = clamp(round(p), vec2<f32>(0.0), vec2<f32>(sx - 1.0, sy - 1.0));
+    out.cell = u32(cell.y) * u32(sx) + u32(cell.x);
+    out.key = (bitcast<u32>(z) & ~OBJECT_BITS) | object.flags.w;
+    out.n = n;
+    out.spread = 0.5 + (clip.w - object.camera.w) / (2.0 * object.screen.w);
+    out.drawn = true;
+    return out;
+}
+
+// Workgroups are laid out in rows, for more than fit in one dimension.
+fn index(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.x + id.y * groups.x * 64u;
+}
+
+@compute @workgroup_size(64)
+fn depth(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let p = sample(index(id, groups));
+    if p.drawn {
+        atomicMin(&cells[p.cell].nearest, p.key);
+    }
+}
+
+@compute @workgroup_size(64)
+fn shade(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let p = sample(index(id, groups));
+    if !p.drawn {
+        return;
+    }
+    let cell = &cells[p.cell];
+    // Depths compare by ratios of 1 - z, as in FrameBuffer::poke_if
+    let nearest = 1.0 - bitcast<f32>(atomicLoad(&(*cell).nearest) & ~OBJECT_BITS);
+    let near = 1.0 - bitcast<f32>(p.key & ~OBJECT_BITS);
+    if near < nearest * (1.0 - DEPTH_BAND) {
+        return;
+    }
+    atomicAdd(&(*cell).samples, 1u);
+    atomicAdd(&(*cell).brightness, u32(p.light * ONE));
+    atomicAdd(&(*cell).specular, i32(p.specular * ONE));
+    atomicAdd(&(*cell).nx, i32(p.n.x * ONE));
+    atomicAdd(&(*cell).ny, i32(p.n.y * ONE));
+    atomicAdd(&(*cell).nz, i32(p.n.z * ONE));
+    atomicAdd(&(*cell).spread, i32(p.spread * ONE));
+}
@@ -0,0 +1,50 @@
+use crate::options::Options;
+use crossterm::event::Event;
+use forbidden_donut::Params;
+
+// Something outside the renderer that drives the scene: system load, audio,
+// MIDI knobs, gamepads... Each gets a turn to update the parameters before
+// every frame, in the order they were opened, after seeing any keyboard and
+// mouse events that came in.
+pub trait Input {
+    fn event(&mut self, _event: &Event, _params: &mut Params) {}
+    fn apply(&mut self, params: &mut Params);
+}
+
+pub fn open(opts: &Options) -> std::result::Result<Vec<Box<dyn Input>>, String> {
+    let mut inputs: Vec<Box<dyn Input>> = Vec::new();
+    if opts.sysmon {
+        inputs.push(Box::new(crate::sysmon::SysMon::new()));
+    }
+    if let Some(seed) = opts.random {
+        inputs.push(Box::new(crate::random::Path::new(seed, opts.fps)));
+    }
+    if let Some(seconds) = opts.daylight {
+        inputs.push(Box::new(crate::daylight::Daylight::new(seconds, opts.fps)));
+    }
+    if let Some(path) = &opts.audio {
+        #[cfg(feature = "audio")]
+        inputs.push(Box::new(crate::audio::Audio::open(path)?));
+        #[cfg(not(feature = "audio"))]
+        return Err(format!(
+            "--audio {} needs a build with `--features audio`",
+            path
+        ));
+    }
+    if opts.midi {
+        #[cfg(feature = "midi")]
+        inputs.push(Box::new(crate::midi::Midi::open()?));
+        #[cfg(not(feature = "midi"))]
+        return Err("--midi needs a build with `--features midi`".to_string());
+    }
+    if opts.mouse {
+        inputs.push(Box::new(crate::mouse::Mouse::new(opts.viewport)));
+    }
+    if opts.gamepad {
+        #[cfg(feature = "gamepad")]
+        inputs.push(Box::new(crate::gamepad::Gamepad::open()?));
+        #[cfg(not(feature = "gamepad"))]
+        return Err("--gamepad needs a build with `--features gamepad`".to_string());
+    }
+    Ok(inputs)
+}
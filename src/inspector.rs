@@ -0,0 +1,112 @@
+use crate::scene::Scene;
+use crossterm::event::{KeyCode, KeyEvent};
+use forbidden_donut::{Params, RenderTarget};
+
+// Tab lists what's in the scene over the top left of the frame: where each
+// object is, how it's turned and what it's made of. Up and down pick one,
+// space hides or shows it, left and right pick one of its numbers and + and
+// - change it.
+#[derive(Default)]
+pub struct Inspector {
+    open: bool,
+    // Which object, and which of its properties
+    selected: usize,
+    property: usize,
+}
+
+impl Inspector {
+    // Acts on a key, if it's one of the inspector's, and says whether it was.
+    pub fn key(&mut self, key: &KeyEvent, scene: &mut Scene, params: &mut Params) -> bool {
+        if key.code == KeyCode::Tab {
+            self.open = !self.open;
+            return true;
+        }
+        let mut objects = scene.objects();
+        let n = objects.len();
+        if !self.open || n == 0 {
+            return false;
+        }
+        self.selected = self.selected.min(n - 1);
+        let object = &mut objects[self.selected];
+        match key.code {
+            KeyCode::Up | KeyCode::Down => {
+                self.selected = match key.code {
+                    KeyCode::Up => (self.selected + n - 1) % n,
+                    _ => (self.selected + 1) % n,
+                };
+                self.property = 0;
+            }
+            KeyCode::Char(' ') => {
+                let visible = object.visible();
+                *visible = !*visible;
+            }
+            KeyCode::Left | KeyCode::Right => {
+                let count = object.properties(params).len().max(1);
+                self.property = match key.code {
+                    KeyCode::Left => (self.property + count - 1) % count,
+                    _ => (self.property + 1) % count,
+                };
+            }
+            KeyCode::Char(c @ ('+' | '=' | '-' | '_')) => {
+                let sign = if c == '+' || c == '=' { 1.0 } else { -1.0 };
+                if let Some(p) = object.properties(params).get_mut(self.property) {
+                    *p.value += sign * p.step;
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn draw(&self, target: &mut dyn RenderTarget, scene: &mut Scene, params: &mut Params) {
+        if !self.open {
+            return;
+        }
+        let mut objects = scene.objects();
+        let mut lines = vec![format!(
+            "{} objects | up/down pick, space hide, left/right +/- tweak, tab close",
+            objects.len()
+        )];
+        for (i, object) in objects.iter_mut().enumerate() {
+            let shown = if *object.visible() { 'x' } else { ' ' };
+            lines.push(format!(
+                "{} [{}] {} {}  {}",
+                if i == self.selected { '>' } else { ' ' },
+                shown,
+                object.kind(),
+                i + 1,
+                object.describe()
+            ));
+        }
+        if let Some(object) = objects.get_mut(self.selected) {
+            let properties = object.properties(params);
+            let mut line = String::from("     ");
+            for (i, p) in properties.iter().enumerate() {
+                let (open, close) = if i == self.property {
+                    ('[', ']')
+                } else {
+                    (' ', ' ')
+                };
+                line.push_str(&format!("{}{} {:.2}{}", open, p.name, p.value, close));
+            }
+            lines.push(line);
+        }
+
+        // Below the title, on a backing as wide as the longest line
+        let (sx, sy) = target.dimensions();
+        let width = lines
+            .iter()
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(sx);
+        for (y, line) in (2..sy).zip(&lines) {
+            let line: String = line
+                .chars()
+                .chain(std::iter::repeat(' '))
+                .take(width)
+                .collect();
+            target.put_str(0, y, &line, None);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use forbidden_donut::color::Rgb;
+use forbidden_donut::{Mat4, Params, Vec3};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+// Journals (--journal) keep everything that changed a session as it went, so
+// --replay can go through it again frame for frame. They're text: a header
+// line, the dithering seed, the flags the session was started with one to a
+// line, then entries, each starting with the frame and the seconds since the
+// start:
+//
+//   12 0.600 key char:p 0
+//   40 2.000 mouse drag:left 31 9 0
+//   41 2.050 control set speed 2
+//   41 2.050 params 1 0.45 0.1 -0.05 0.03 ...
+//   90 4.500 end
+//
+// Keys and mouse events go through the console and inspector again and
+// control commands through the console. The params are journaled as they
+// stand once the inputs have had their say (mouse, MIDI, --sysmon, ...),
+// whenever they change, and a replay holds them there until the next.
+const HEADER: &str = "forbidden-donut journal";
+
+pub struct Journal {
+    out: BufWriter<File>,
+    start: Instant,
+    // The params as last written, to write them only when they change
+    params: Option<String>,
+}
+
+impl Journal {
+    pub fn create(path: &Path, seed: u64, args: &[String]) -> std::io::Result<Journal> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "seed {}", seed)?;
+        for arg in args {
+            writeln!(out, "arg {}", arg)?;
+        }
+        Ok(Journal {
+            out,
+            start: Instant::now(),
+            params: None,
+        })
+    }
+
+    fn entry(&mut self, frame: usize, what: &str) {
+        let seconds = self.start.elapsed().as_secs_f32();
+        if let Err(e) = writeln!(self.out, "{} {:.3} {}", frame, seconds, what) {
+            tracing::warn!("journal: {}", e);
+        }
+    }
+
+    pub fn event(&mut self, frame: usize, event: &Event) {
+        if let Some(event) = format_event(event) {
+            self.entry(frame, &event);
+        }
+    }
+
+    pub fn control(&mut self, frame: usize, words: &[&str]) {
+        self.entry(frame, &format!("control {}", words.join(" ")));
+    }
+
+    pub fn params(&mut self, frame: usize, params: &Params) {
+        let line = format_params(params);
+        if self.params.as_ref() != Some(&line) {
+            self.entry(frame, &format!("params {}", line));
+            self.params = Some(line);
+        }
+    }
+
+    pub fn end(&mut self, frame: usize) {
+        self.entry(frame, "end");
+        if let Err(e) = self.out.flush() {
+            tracing::warn!("journal: {}", e);
+        }
+    }
+}
+
+pub enum Entry {
+    Event(Event),
+    Control(Vec<String>),
+    Params(Params),
+    End,
+}
+
+pub struct Replay {
+    pub seed: u64,
+    pub args: Vec<String>,
+    entries: VecDeque<(usize, Entry)>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> std::result::Result<Replay, String> {
+        let err = |line: usize, e: &str| format!("{}:{}: {}", path.display(), line, e);
+        let text = std::fs::read_to_string(path).map_err(|e| err(0, &e.to_string()))?;
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, l)| l) != Some(HEADER) {
+            return Err(err(1, "not a journal"));
+        }
+        let mut replay = Replay {
+            seed: 0,
+            args: Vec::new(),
+            entries: VecDeque::new(),
+        };
+        for (i, line) in lines {
+            let bad = |e: &str| err(i + 1, e);
+            if let Some(seed) = line.strip_prefix("seed ") {
+                replay.seed = seed.parse().map_err(|_| bad("bad seed"))?;
+                continue;
+            }
+            if let Some(arg) = line.strip_prefix("arg ") {
+                replay.args.push(arg.to_string());
+                continue;
+            }
+            let mut words = line.split(' ');
+            let frame = words
+                .next()
+                .and_then(|f| f.parse().ok())
+                .ok_or_else(|| bad("bad frame"))?;
+            // The time is for people reading it
+            words.next();
+            let words: Vec<&str> = words.collect();
+            let entry = match words[..] {
+                ["key", ..] | ["mouse", ..] => {
+                    Entry::Event(parse_event(&words).ok_or_else(|| bad("bad event"))?)
+                }
+                ["control", ref rest @ ..] => {
+                    Entry::Control(rest.iter().map(|w| w.to_string()).collect())
+                }
+                ["params", ref rest @ ..] => {
+                    Entry::Params(parse_params(rest).ok_or_else(|| bad("bad params"))?)
+                }
+                ["end"] => Entry::End,
+                _ => return Err(bad("unknown entry")),
+            };
+            replay.entries.push_back((frame, entry));
+        }
+        Ok(replay)
+    }
+
+    // Everything journaled for this frame, in order.
+    pub fn take(&mut self, frame: usize) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        while self.entries.front().is_some_and(|&(f, _)| f <= frame) {
+            entries.extend(self.entries.pop_front().map(|(_, entry)| entry));
+        }
+        entries
+    }
+}
+
+fn format_key(code: KeyCode) -> Option<String> {
+    Some(match code {
+        // As a number, so spaces don't split it
+        KeyCode::Char(c) => format!("char:{}", c as u32),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::BackTab => "backtab".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        _ => return None,
+    })
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    Some(match name {
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        _ => KeyCode::Char(char::from_u32(name.strip_prefix("char:")?.parse().ok()?)?),
+    })
+}
+
+const BUTTONS: &[(MouseButton, &str)] = &[
+    (MouseButton::Left, "left"),
+    (MouseButton::Right, "right"),
+    (MouseButton::Middle, "middle"),
+];
+
+fn format_event(event: &Event) -> Option<String> {
+    let button = |b: MouseButton| BUTTONS.iter().find(|(x, _)| *x == b).map(|(_, n)| *n);
+    match event {
+        Event::Key(key) if key.kind == crossterm::event::KeyEventKind::Press => Some(format!(
+            "key {} {}",
+            format_key(key.code)?,
+            key.modifiers.bits()
+        )),
+        Event::Mouse(mouse) => {
+            let kind = match mouse.kind {
+                MouseEventKind::Down(b) => format!("down:{}", button(b)?),
+                MouseEventKind::Up(b) => format!("up:{}", button(b)?),
+                MouseEventKind::Drag(b) => format!("drag:{}", button(b)?),
+                MouseEventKind::Moved => "moved".to_string(),
+                MouseEventKind::ScrollDown => "scroll:down".to_string(),
+                MouseEventKind::ScrollUp => "scroll:up".to_string(),
+                MouseEventKind::ScrollLeft => "scroll:left".to_string(),
+                MouseEventKind::ScrollRight => "scroll:right".to_string(),
+            };
+            Some(format!(
+                "mouse {} {} {} {}",
+                kind,
+                mouse.column,
+                mouse.row,
+                mouse.modifiers.bits()
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn parse_event(words: &[&str]) -> Option<Event> {
+    let modifiers = |w: &str| Some(KeyModifiers::from_bits_truncate(w.parse().ok()?));
+    match *words {
+        ["key", code, mods] => Some(Event::Key(KeyEvent::new(
+            parse_key(code)?,
+            modifiers(mods)?,
+        ))),
+        ["mouse", kind, column, row, mods] => {
+            let button = |name: &str| BUTTONS.iter().find(|(_, n)| *n == name).map(|(b, _)| *b);
+            let kind = match kind.split_once(':') {
+                Some(("down", b)) => MouseEventKind::Down(button(b)?),
+                Some(("up", b)) => MouseEventKind::Up(button(b)?),
+                Some(("drag", b)) => MouseEventKind::Drag(button(b)?),
+                Some(("scroll", "down")) => MouseEventKind::ScrollDown,
+                Some(("scroll", "up")) => MouseEventKind::ScrollUp,
+                Some(("scroll", "left")) => MouseEventKind::ScrollLeft,
+                Some(("scroll", "right")) => MouseEventKind::ScrollRight,
+                None if kind == "moved" => MouseEventKind::Moved,
+                _ => return None,
+            };
+            Some(Event::Mouse(MouseEvent {
+                kind,
+                column: column.parse().ok()?,
+                row: row.parse().ok()?,
+                modifiers: modifiers(mods)?,
+            }))
+        }
+        _ => None,
+    }
+}
+
+// Every float in full (Rust prints the shortest that reads back the same),
+// then the tint or "-".
+fn format_params(params: &Params) -> String {
+    let Params {
+        r1,
+        r2,
+        spin,
+        light_dir,
+        cam_dist,
+        fov,
+        orbit,
+        nudge,
+        speed,
+        gain,
+        tint,
+    } = *params;
+    let mut numbers = vec![r1, r2];
+    numbers.extend(spin.iter());
+    numbers.extend(light_dir.iter());
+    numbers.extend([cam_dist, fov, orbit.0, orbit.1, speed, gain]);
+    numbers.extend(nudge.iter());
+    let mut line: Vec<String> = numbers.iter().map(|x| x.to_string()).collect();
+    line.push(match tint {
+        Some(Rgb(r, g, b)) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        None => "-".to_string(),
+    });
+    line.join(" ")
+}
+
+fn parse_params(words: &[&str]) -> Option<Params> {
+    let (tint, numbers) = words.split_last()?;
+    let x: Vec<f32> = numbers
+        .iter()
+        .map(|w| w.parse().ok())
+        .collect::<Option<_>>()?;
+    if x.len() != 30 {
+        return None;
+    }
+    Some(Params {
+        r1: x[0],
+        r2: x[1],
+        spin: Vec3::new(x[2], x[3], x[4]),
+        light_dir: Vec3::new(x[5], x[6], x[7]),
+        cam_dist: x[8],
+        fov: x[9],
+        orbit: (x[10], x[11]),
+        speed: x[12],
+        gain: x[13],
+        nudge: Mat4::from_column_slice(&x[14..]),
+        tint: match *tint {
+            "-" => None,
+            hex => Some(Rgb::from_hex(hex)?),
+        },
+    })
+}
@@ -0,0 +1,51 @@
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+};
+use crossterm::{cursor, terminal, ExecutableCommand};
+use std::time::Duration;
+
+// The keyboard one key at a time (raw mode), and the mouse too for --mouse.
+// Raw mode swallows Ctrl-C, so whoever reads the keys quits on it.
+pub struct Keys {
+    mouse: bool,
+}
+
+impl Keys {
+    pub fn open(mouse: bool) -> std::io::Result<Keys> {
+        terminal::enable_raw_mode()?;
+        let keys = Keys { mouse };
+        if mouse {
+            std::io::stdout().execute(EnableMouseCapture)?;
+        }
+        Ok(keys)
+    }
+
+    // The events since last time, oldest first, without waiting.
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            match event::read() {
+                Ok(event) => events.push(event),
+                Err(_) => break,
+            }
+        }
+        events
+    }
+}
+
+impl Drop for Keys {
+    fn drop(&mut self) {
+        let mut out = std::io::stdout();
+        if self.mouse {
+            let _ = out.execute(DisableMouseCapture);
+        }
+        let _ = out.execute(cursor::Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// q, Esc or Ctrl-C.
+pub fn quits(key: &KeyEvent) -> bool {
+    let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+    ctrl_c || key.code == KeyCode::Char('q') || key.code == KeyCode::Esc
+}
@@ -0,0 +1,52 @@
+// Terminal 3D graphics: a renderer for spinning donuts, and targets to show
+// them on. Without the default `std` feature only the renderer (`core`) is
+// built, for no_std targets with an allocator.
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod caps;
+pub mod core;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+#[cfg(all(feature = "std", unix))]
+pub mod serial;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod spinner;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod terminal;
+#[cfg(feature = "std")]
+mod terminfo;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use crate::core::{
+    ansi, color, framebuffer, image, mesh, metaballs, palette, post, ramp, render, shader, shape,
+    target,
+};
+pub use framebuffer::FrameBuffer;
+pub use image::Image;
+pub use mesh::Mesh;
+pub use metaballs::Metaballs;
+pub use palette::Palette;
+pub use render::{DebugView, Donut, Params};
+pub use shader::{Phong, Shade, Shader};
+pub use shape::{Morph, Shape, Timeline};
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use spinner::DonutSpinner;
+pub use target::{Buffers, Cell, CellBuffer, Rect, RenderTarget};
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use terminal::Terminal;
+
+pub type Vec3 = nalgebra::Vector3<f32>;
+pub type Point = nalgebra::Point3<f32>;
+pub type Mat4 = nalgebra::Matrix4<f32>;
+
+// What presenting a frame can fail with: I/O errors, or without std, whatever
+// the target reports.
+#[cfg(feature = "std")]
+pub type Error = std::io::Error;
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub struct Error;
+pub type Result<T> = ::core::result::Result<T, Error>;
@@ -0,0 +1,122 @@
+use forbidden_donut::RenderTarget;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+// Log lines kept for the pane
+const LINES: usize = 4;
+
+// A tracing subscriber for --log-pane: keeps the last few log lines, and adds
+// up how long each stage (span) takes, to draw over the bottom of the frame.
+#[derive(Clone, Default)]
+pub struct LogPane {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    // Spans are told apart by name only: span ids are indexes in here plus one.
+    names: Vec<&'static str>,
+    // Time in each span since the pane was last drawn, by id
+    spent: Vec<Duration>,
+    // Spans entered and not yet exited, innermost last
+    entered: Vec<(usize, Instant)>,
+    lines: VecDeque<String>,
+}
+
+// Formats an event's message, then any other fields as name=value.
+struct Line(String);
+
+impl Visit for Line {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+impl LogPane {
+    // Draws the log lines then a line of stage timings and `stats`, ending
+    // just above the bottom row, and starts timing afresh.
+    pub fn draw(&self, target: &mut dyn RenderTarget, stats: &str) {
+        let mut state = self.state.lock().unwrap();
+        let mut timings = String::new();
+        for (name, spent) in state.names.iter().zip(&state.spent) {
+            let _ = write!(timings, "{} {:.1}ms ", name, spent.as_secs_f32() * 1000.0);
+        }
+        timings.push_str(stats);
+        state.spent.iter_mut().for_each(|d| *d = Duration::ZERO);
+
+        let (sx, sy) = target.dimensions();
+        let rows = state.lines.iter().chain(Some(&timings));
+        let count = state.lines.len() + 1;
+        for (i, line) in rows.enumerate() {
+            if let Some(y) = (sy + i).checked_sub(count + 1) {
+                let line: String = line
+                    .chars()
+                    .chain(std::iter::repeat(' '))
+                    .take(sx)
+                    .collect();
+                target.put_str(0, y, &line, None);
+            }
+        }
+    }
+}
+
+impl Subscriber for LogPane {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut state = self.state.lock().unwrap();
+        let name = span.metadata().name();
+        let ix = match state.names.iter().position(|&n| n == name) {
+            Some(ix) => ix,
+            None => {
+                state.names.push(name);
+                state.spent.push(Duration::ZERO);
+                state.names.len() - 1
+            }
+        };
+        Id::from_u64(ix as u64 + 1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut line = Line(event.metadata().level().to_string());
+        event.record(&mut line);
+        let mut state = self.state.lock().unwrap();
+        if state.lines.len() == LINES {
+            state.lines.pop_front();
+        }
+        state.lines.push_back(line.0);
+    }
+
+    fn enter(&self, span: &Id) {
+        let ix = span.into_u64() as usize - 1;
+        self.state
+            .lock()
+            .unwrap()
+            .entered
+            .push((ix, Instant::now()));
+    }
+
+    fn exit(&self, span: &Id) {
+        let ix = span.into_u64() as usize - 1;
+        let mut state = self.state.lock().unwrap();
+        if let Some(pos) = state.entered.iter().rposition(|&(i, _)| i == ix) {
+            let (_, start) = state.entered.remove(pos);
+            state.spent[ix] += start.elapsed();
+        }
+    }
+}
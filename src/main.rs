@@ -1,195 +1,500 @@
-use crossterm::{cursor, QueueableCommand};
-use rand::Rng;
-use std::cmp::{max, min};
-use std::io::Write;
+use forbidden_donut::caps::{Capabilities, ColorDepth, Preset};
+#[cfg(unix)]
+use forbidden_donut::serial::{self, Serial};
+use forbidden_donut::{
+    post, ramp, CellBuffer, Donut, FrameBuffer, Image, Mesh, Metaballs, Params, RenderTarget,
+    Result, Terminal, Timeline,
+};
 
-type Vec3 = nalgebra::Vector3<f32>;
-type Point = nalgebra::Point3<f32>;
-type Mat4 = nalgebra::Matrix4<f32>;
-type Result<T> = std::result::Result<T, std::io::Error>;
+#[cfg(feature = "audio")]
+mod audio;
+mod captions;
+mod config;
+mod console;
+mod control;
+mod daylight;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod input;
+mod inspector;
+mod journal;
+mod keys;
+mod logpane;
+mod metrics;
+#[cfg(feature = "midi")]
+mod midi;
+mod mouse;
+mod options;
+mod pacer;
+mod random;
+mod recording;
+mod scene;
+mod server;
+mod swarm;
+mod sysmon;
+mod video;
+use crossterm::event::{Event, KeyEventKind};
+use options::Options;
+use scene::Scene;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
-fn relu(x: f32) -> f32 {
-    if x >= 0.0 {
-        x
-    } else {
-        0.0
-    }
-}
+// With --reduced-motion: how much slower things turn, and how far the
+// brightness can move in a frame, so it fades rather than flashes
+const REDUCED_SPEED: f32 = 0.3;
+const REDUCED_FLASH: f32 = 0.02;
 
-fn dither(i: f32, clip: usize) -> usize {
-    let u = rand::thread_rng().gen::<f32>() - 0.5;
-    let r = (i + u).round();
-    if r < 0.0 {
-        0
-    } else {
-        let r_i = r as usize;
-        if r_i >= clip {
-            clip - 1
-        } else {
-            r_i
-        }
-    }
+fn exit_with(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    std::process::exit(2);
 }
 
-struct FrameBuffer {
-    brightness: Vec<u8>,
-    z_buffer: Vec<f32>,
-    sx: usize,
-    sy: usize,
+#[cfg(unix)]
+fn serial(opts: &Options) -> bool {
+    opts.serial.is_some()
 }
 
-impl FrameBuffer {
-    fn new() -> Result<FrameBuffer> {
-        let (sx_, sy_) = crossterm::terminal::size().unwrap();
-        let sx = sx_ as usize;
-        let sy = sy_ as usize;
-        let size = ((sx + 1) * sy) as usize;
+#[cfg(not(unix))]
+fn serial(_: &Options) -> bool {
+    false
+}
 
-        std::io::stdout().queue(cursor::Hide)?;
+// Drawing into memory rather than onto a display
+fn offscreen(opts: &Options) -> bool {
+    opts.dump_hash || opts.pipe_video.is_some()
+}
 
-        let brightness: Vec<u8> = Vec::with_capacity(size);
-        let z_buffer: Vec<f32> = Vec::with_capacity(size);
-        Ok(FrameBuffer {
-            sx,
-            sy,
-            brightness,
-            z_buffer,
-        })
+fn main() -> Result<()> {
+    let mut opts = Options::from_args().unwrap_or_else(|msg| exit_with(&msg));
+    if opts.save_config {
+        let path = opts.save_config().unwrap_or_else(|msg| exit_with(&msg));
+        println!("saved settings to {}", path.display());
+        return Ok(());
     }
-
-    fn clear(&mut self) {
-        let (sx, sy) = crossterm::terminal::size().unwrap();
-        self.sx = sx as usize;
-        self.sy = sy as usize;
-        let size = self.sy * (self.sx + 1);
-        self.z_buffer.clear();
-        self.z_buffer.resize(size, -1000.0);
-        self.brightness.clear();
-        self.brightness.resize(size, ' ' as u8);
-        for y in 0..self.sy {
-            self.brightness[y * (self.sx + 1) + self.sx] = '\n' as u8;
-        }
+    // asciinema recordings are terminal output already, so go straight out.
+    let cast = opts
+        .play
+        .as_ref()
+        .filter(|path| path.extension().is_some_and(|e| e == "cast"));
+    if let Some(path) = cast {
+        recording::play_cast(path, !opts.headless).unwrap_or_else(|msg| exit_with(&msg));
+        return Ok(());
     }
-
-    fn write(&self) -> Result<()> {
-        let mut stdout = std::io::stdout();
-        stdout.queue(crossterm::terminal::Clear(
-            crossterm::terminal::ClearType::All,
-        ))?;
-        stdout.queue(cursor::MoveTo(0, 0))?;
-        // actually safe
-        let s = unsafe { std::str::from_utf8_unchecked(&self.brightness[0..(self.sx * self.sy)]) };
-        stdout.queue(crossterm::style::Print(&s))?;
-        Ok(())
+    let playing = opts
+        .play
+        .as_ref()
+        .map(|path| recording::Recording::load(path).unwrap_or_else(|msg| exit_with(&msg)));
+    let mut recorder = opts.record.as_ref().map(|path| {
+        recording::Recorder::create(path, opts.fps)
+            .unwrap_or_else(|e| exit_with(&format!("{}: {}", path.display(), e)))
+    });
+    let frames = opts
+        .frames
+        .or(playing.as_ref().map(|r| r.frames_at(opts.fps)));
+    let pane = if opts.log_pane {
+        let pane = logpane::LogPane::default();
+        tracing::subscriber::set_global_default(pane.clone()).ok();
+        Some(pane)
+    } else {
+        None
+    };
+    let mut replay = opts
+        .replay
+        .as_ref()
+        .map(|path| journal::Replay::load(path).unwrap_or_else(|msg| exit_with(&msg)));
+    let captions = opts
+        .captions
+        .as_ref()
+        .map(|path| captions::Captions::load(path).unwrap_or_else(|msg| exit_with(&msg)));
+    let mut params = Params::default();
+    // A replay has the parameters as they were, whatever moved them
+    let mut inputs = match replay {
+        Some(_) => Vec::new(),
+        None => input::open(&opts).unwrap_or_else(|msg| exit_with(&msg)),
+    };
+    let mut caps = if serial(&opts) {
+        // Plain ASCII, whatever the terminal we were started from can do
+        Capabilities {
+            color: ColorDepth::Mono,
+            unicode: false,
+            vt: false,
+        }
+    } else if opts.serve.is_some() {
+        // Anything that can telnet, more or less
+        Capabilities {
+            color: ColorDepth::Ansi256,
+            unicode: false,
+            vt: true,
+        }
+    } else if opts.headless || offscreen(&opts) {
+        Capabilities::probe()
+    } else {
+        Capabilities::probe_interactive()
+    };
+    let preset = opts.preset.unwrap_or_else(|| Preset::auto(&caps));
+    caps.color = opts
+        .color
+        .unwrap_or_else(|| caps.color.min(preset.max_color()));
+    if opts.high_contrast {
+        caps.color = ColorDepth::Mono;
     }
+    tracing::info!(
+        "{:?} color, unicode {}, preset {}",
+        caps.color,
+        caps.unicode,
+        preset.name()
+    );
 
-    fn poke_if(&mut self, x: usize, y: usize, value: f32, z: f32) {
-        let lightlevel_str = "-~+*=;%#$@";
-        let n = lightlevel_str.len();
+    let palette = opts.palette().unwrap_or_else(|msg| exit_with(&msg));
+    let (detail, two_sided, debug_view) = (preset.detail(), opts.two_sided, opts.debug_view);
+    let setup = move |donut: &mut Donut| {
+        donut.n1 = (donut.n1 as f32 * detail) as usize;
+        donut.n2 = (donut.n2 as f32 * detail) as usize;
+        donut.two_sided = two_sided;
+        donut.debug_view = debug_view;
+    };
+    let dressed = palette.clone();
+    let mut donut = Donut::new();
+    setup(&mut donut);
+    donut.palette = palette.clone();
+    let mut scene = match (playing, opts.swarm, opts.metaballs) {
+        (Some(recording), _, _) => Scene::Playing {
+            recording,
+            fps: opts.fps,
+            interpolate: opts.interpolate,
+        },
+        (None, Some(n), _) => {
+            params.cam_dist = swarm::CAM_DIST;
+            Scene::Swarm(swarm::Swarm::new(n, palette, setup))
+        }
+        (None, None, Some(n)) => Scene::Metaballs(Metaballs::new(n), donut),
+        (None, None, None) => match &opts.mesh {
+            Some(path) => {
+                let mesh = std::fs::read(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|bytes| Mesh::parse(&bytes, donut.n1 * donut.n2))
+                    .unwrap_or_else(|e| exit_with(&format!("{}: {}", path.display(), e)));
+                Scene::Mesh(mesh, donut)
+            }
+            None => Scene::Donut(donut, opts.morph.clone().map(Timeline::new)),
+        },
+    };
 
-        let ix = y * (self.sx + 1) + x;
+    let mut gpu = match opts.gpu {
+        #[cfg(feature = "gpu")]
+        true => Some(scene::Gpu::new().unwrap_or_else(|e| exit_with(&format!("--gpu: {}", e)))),
+        #[cfg(not(feature = "gpu"))]
+        true => exit_with("--gpu needs a build with `--features gpu`"),
+        false => None,
+    };
+    let mut framebuffer = FrameBuffer::new();
+    let ramp = match opts.ramp.as_deref() {
+        _ if opts.high_contrast => ramp::HIGH_CONTRAST,
+        Some(ramp) => ramp::lookup(ramp),
+        None => preset.ramp(),
+    };
+    let glyphs = ramp::fallback(ramp, &caps);
+    if glyphs != ramp {
+        tracing::warn!("no unicode, so using the classic ramp");
+    }
+    framebuffer.ramp = glyphs.chars().collect();
+    if opts.highlights && !opts.high_contrast {
+        framebuffer.highlights = Some(ramp::HIGHLIGHTS.chars().collect());
+    }
+    framebuffer.steady = opts.reduced_motion;
 
-        if self.z_buffer[ix] < z {
-            self.z_buffer[ix] = z;
-            let val_ix = dither(value * (n as f32), n);
-            self.brightness[ix] = lightlevel_str.as_bytes()[val_ix];
+    // --dump-hash draws into memory with repeatable dithering, and
+    // --pipe-video into memory and then on into pixels. Journaled sessions
+    // dither the same way again when they're replayed.
+    let mut display: Option<Box<dyn RenderTarget>> = None;
+    let mut cells = None;
+    let mut video = None;
+    let mut stats = None;
+    let mut served = None;
+    if offscreen(&opts) {
+        let size = opts.size.unwrap_or((80, 24));
+        match &opts.pipe_video {
+            Some(dest) => {
+                let mut image = Image::new(size, video::CELL);
+                let pipe = video::Video::open(dest, (image.width, image.height), opts.fps)
+                    .unwrap_or_else(|msg| exit_with(&msg));
+                image.ramp = framebuffer.ramp.clone();
+                image.highlights = framebuffer.highlights.clone();
+                video = Some((pipe, image));
+            }
+            None => cells = Some(CellBuffer::new(size.0, size.1)),
+        }
+    } else if serial(&opts) {
+        #[cfg(unix)]
+        if let (Some(path), Some(size)) = (&opts.serial, opts.size) {
+            let port = serial::open(path, opts.baud)
+                .unwrap_or_else(|e| exit_with(&format!("{}: {}", path.display(), e)));
+            display = Some(Box::new(Serial::new(port, size, opts.newline)));
+        }
+    } else if let (Some(addr), Some(size)) = (&opts.serve, opts.size) {
+        let mut server = server::Server::bind(addr, size, caps.color)
+            .unwrap_or_else(|e| exit_with(&format!("{}: {}", addr, e)));
+        if let Some(max) = opts.per_client {
+            let params = Arc::new(Mutex::new(params));
+            served = Some(params.clone());
+            server.set_per_client(server::PerClient {
+                max,
+                params,
+                donut: Box::new(move || {
+                    let mut donut = Donut::new();
+                    setup(&mut donut);
+                    donut.palette = dressed.clone();
+                    donut
+                }),
+                ramp: framebuffer.ramp.clone(),
+                highlights: framebuffer.highlights.clone(),
+                budget: Duration::from_secs(1) / opts.fps,
+            });
         }
+        stats = Some(server.stats.clone());
+        display = Some(Box::new(server));
+    } else {
+        let mut terminal = Terminal::new(std::io::stdout(), opts.size, caps)?;
+        terminal.set_viewport(opts.viewport);
+        display = Some(Box::new(terminal));
     }
-}
-
-fn main() -> Result<()> {
-    let light_dir = Vec3::new(1.0, 5.0, -3.0).normalize();
-    let cam_pos = Vec3::new(0.0, 0.0, 4.0);
-    // Subdivisions of torus
-    let (n1, n2) = (500, 200);
-    // Radii of torus
-    let (r1, r2) = (1.0, 0.45);
-
-    let two_pi: f32 = 2.0 * 3.1415926535;
-    let mut stdout = std::io::stdout();
-    stdout.queue(cursor::Hide)?;
-
-    let mut global_transform = Mat4::identity();
-
-    let mut framebuffer = FrameBuffer::new()?;
-    loop {
-        framebuffer.clear();
-        let (sx, sy) = (framebuffer.sx, framebuffer.sy);
-
-        let aspect = (min(sx, sy) as f32) / (max(sx, sy) as f32);
-        let screenspace = Mat4::new_translation(&Vec3::new(0.5 * sx as f32, 0.5 * sy as f32, 0.0))
-            * Mat4::new_scaling(0.5 * min(sx, sy) as f32)
-            * Mat4::new_perspective(aspect, 3.141 / 4.0, 0.1, 1000.0)
-            * Mat4::new_translation(&cam_pos);
-
-        // For each voxel, compute screenspace position, lighting, then (maybe) draw.
-        for i1 in 0..n1 {
-            let phi1 = two_pi * (i1 as f32) / (n1 as f32);
-            let rot: Mat4 = Mat4::from_euler_angles(0.0, 0.0, phi1);
-
-            for i2 in 0..n2 {
-                // Compute screenspace position + worldspace normal (for lighting)
-                let (p_world, p_screen, n) = {
-                    let phi2 = two_pi * (i2 as f32) / n2 as f32;
-                    // cp = circle point; cn = circle normal.
-                    let cp = Point::new(r2 * phi2.cos() + r1, 0.0, r2 * phi2.sin());
-                    let cn = Vec3::new(phi2.cos(), 0.0, phi2.sin());
-
-                    // To object space (isometry)
-                    let p1 = rot.transform_point(&cp);
-                    let n1 = rot.transform_vector(&cn);
-
-                    // To world space (isometry)
-                    let p2 = global_transform.transform_point(&p1);
-                    let n2 = global_transform.transform_vector(&n1);
-
-                    // p3 goes to screen space (homogenous)
-                    let p3 = screenspace.transform_point(&p2);
-                    // Technically, n2 should still be normalized
-                    (p2, p3, n2.normalize())
+    let seed = match (&replay, opts.dump_hash) {
+        (Some(replay), _) => replay.seed,
+        (None, true) => 0,
+        (None, false) => SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |t| t.as_nanos() as u64),
+    };
+    if opts.dump_hash || opts.journal.is_some() || replay.is_some() {
+        framebuffer.seed(seed);
+    }
+    let mut journal = opts.journal.as_ref().map(|path| {
+        journal::Journal::create(path, seed, &opts.started_with)
+            .unwrap_or_else(|e| exit_with(&format!("{}: {}", path.display(), e)))
+    });
+    for (name, values) in &opts.scene {
+        console::set(&mut params, name, values).unwrap_or_else(|msg| exit_with(&msg));
+    }
+    let mut control = opts.control.as_ref().map(|path| {
+        control::Control::open(path)
+            .unwrap_or_else(|e| exit_with(&format!("{}: {}", path.display(), e)))
+    });
+    let mut metrics = opts.metrics.as_ref().map(|addr| {
+        metrics::Metrics::bind(addr).unwrap_or_else(|e| exit_with(&format!("{}: {}", addr, e)))
+    });
+    // The keyboard, for the inspector, in a terminal of our own. Raw mode
+    // lasts until `keys` drops, so only once nothing else can exit_with().
+    let mut keys = None;
+    if opts.mouse || (display.is_some() && !opts.headless && !serial(&opts) && opts.serve.is_none())
+    {
+        match keys::Keys::open(opts.mouse) {
+            Ok(k) => keys = Some(k),
+            Err(e) if opts.mouse => exit_with(&format!("--mouse needs a terminal: {}", e)),
+            Err(e) => tracing::warn!("no keyboard: {}", e),
+        }
+    }
+    let mut console = console::Console::default();
+    let mut paused = false;
+    let mut inspector = inspector::Inspector::default();
+    let mut pacer = pacer::Pacer::new(opts.fps);
+    let mut exposure = opts.auto_exposure.then(post::Exposure::default);
+    // The parameters as last journaled, while replaying
+    let mut replayed = None;
+    let mut frame = 0;
+    'frames: loop {
+        let started = Instant::now();
+        let mut events = keys.as_mut().map_or_else(Vec::new, |keys| keys.poll());
+        let mut commands = Vec::new();
+        if let Some(replay) = &mut replay {
+            // Only quitting is up to whoever's watching
+            let quit = |event: &Event| matches!(event, Event::Key(key) if key.kind == KeyEventKind::Press && keys::quits(key));
+            if events.iter().any(quit) {
+                break 'frames;
+            }
+            events.clear();
+            for entry in replay.take(frame) {
+                match entry {
+                    journal::Entry::Event(event) => events.push(event),
+                    journal::Entry::Control(words) => commands.push(words),
+                    journal::Entry::Params(journaled) => replayed = Some(journaled),
+                    journal::Entry::End => break 'frames,
+                }
+            }
+        }
+        for event in &events {
+            if let Some(journal) = &mut journal {
+                journal.event(frame, event);
+            }
+            if let Event::Key(key) = event {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                let mut live = console::Live {
+                    scene: &mut scene,
+                    params: &mut params,
+                    opts: &mut opts,
+                    pacer: &mut pacer,
+                    paused: &mut paused,
+                    framebuffer: &framebuffer,
                 };
-
-                // Unit vector pointing from p_world to the camera
-                let cam_vec = (cam_pos - (p_world - Point::origin())).normalize();
-
-                if !(p_screen.x < 0.0
-                    || p_screen.y < 0.0
-                    || cam_vec.dot(&n) > 0.0
-                    || p_screen.x >= sx as f32
-                    || p_screen.y >= sy as f32)
-                {
-                    let light = {
-                        // Phong shading model
-                        let a = relu(n.dot(&light_dir));
-                        let r = 2.0 * a * n.dot(&cam_vec) - light_dir.dot(&cam_vec);
-                        let light = 0.75 * a + 0.25 * r * r * r;
-                        if light > 0.99 {
-                            0.99
-                        } else {
-                            light
-                        }
-                    };
-                    if light > 0.0 {
-                        let (ix, iy) = (
-                            dither(p_screen.x, sx as usize),
-                            dither(p_screen.y, sy as usize),
-                        );
-                        framebuffer.poke_if(ix, iy, light, p_screen.z);
-                    }
+                match console.key(key, &mut live) {
+                    console::Handled::Yes => continue,
+                    console::Handled::Quit => break 'frames,
+                    console::Handled::No => {}
+                }
+                if inspector.key(key, &mut scene, &mut params) {
+                    continue;
                 }
+                if keys::quits(key) {
+                    break 'frames;
+                }
+            }
+            for input in &mut inputs {
+                input.event(event, &mut params);
+            }
+        }
+        let mut live = console::Live {
+            scene: &mut scene,
+            params: &mut params,
+            opts: &mut opts,
+            pacer: &mut pacer,
+            paused: &mut paused,
+            framebuffer: &framebuffer,
+        };
+        for words in &commands {
+            let words: Vec<&str> = words.iter().map(String::as_str).collect();
+            if let Ok(None) = console::run(&words, &mut live) {
+                break 'frames;
+            }
+        }
+        if let (Some(control), None) = (&mut control, &replay) {
+            let serve = |words: &[&str]| {
+                if let Some(journal) = &mut journal {
+                    journal.control(frame, words);
+                }
+                console::run(words, &mut live)
+            };
+            if control.serve(serve) {
+                break 'frames;
             }
         }
+        let gain = params.gain;
+        for input in &mut inputs {
+            input.apply(&mut params);
+        }
+        if opts.reduced_motion {
+            params.gain = gain + (params.gain - gain).clamp(-REDUCED_FLASH, REDUCED_FLASH);
+        }
+        if let Some(replayed) = replayed {
+            params = replayed;
+        }
+        if let Some(journal) = &mut journal {
+            journal.params(frame, &params);
+        }
 
-        global_transform *= Mat4::from_euler_angles(0.0, 0.0, 0.03);
-        global_transform *= Mat4::from_euler_angles(0.1, -0.05, 0.0);
+        let target: &mut dyn RenderTarget = match (&mut display, &mut cells, &mut video) {
+            (Some(display), _, _) => display.as_mut(),
+            (None, Some(cells), _) => cells,
+            (None, None, Some((_, image))) => image,
+            (None, None, None) => unreachable!(),
+        };
+        let (sx, sy) = target.dimensions();
+        tracing::trace_span!("clear").in_scope(|| framebuffer.clear(sx, sy));
+        if let (Some(fraction), Some(bound)) = (opts.auto_frame, scene.bound(&params)) {
+            params.frame(bound, framebuffer.sx, framebuffer.sy, fraction);
+        }
+        scene.render(frame, &params, &mut framebuffer, gpu.as_mut());
+        let speed = match (paused, opts.reduced_motion) {
+            (true, _) => 0.0,
+            (false, true) => params.speed * REDUCED_SPEED,
+            (false, false) => params.speed,
+        };
+        if !paused {
+            scene.step(&Params { speed, ..params });
+        }
+        // Private scenes on --serve go along with the shared one
+        if let Some(served) = &served {
+            *served.lock().unwrap() = Params { speed, ..params };
+        }
+        if let Some(exposure) = &mut exposure {
+            tracing::trace_span!("exposure").in_scope(|| exposure.apply(&mut framebuffer));
+        }
+        if opts.bloom {
+            tracing::trace_span!("bloom").in_scope(|| {
+                post::bloom(&mut framebuffer, opts.bloom_threshold, opts.bloom_radius)
+            });
+        }
+        if opts.crt {
+            let persistence = opts.motion_blur.unwrap_or(post::PHOSPHOR);
+            tracing::trace_span!("crt").in_scope(|| post::crt(&mut framebuffer, persistence));
+        } else if let Some(persistence) = opts.motion_blur {
+            tracing::trace_span!("motion blur")
+                .in_scope(|| post::motion_blur(&mut framebuffer, persistence));
+        }
+        // Recordings have no depths to go by
+        if let (Some(focus), None) = (opts.focus, &opts.play) {
+            tracing::trace_span!("focus")
+                .in_scope(|| post::depth_of_field(&mut framebuffer, focus));
+        }
+        if let Some(recorder) = &mut recorder {
+            recorder.write(&framebuffer)?;
+        }
+        let seconds = frame as f32 / opts.fps as f32;
+        let caption = captions.as_ref().map_or_else(Vec::new, |c| c.at(seconds));
 
-        framebuffer.write()?;
-        stdout.queue(cursor::MoveTo(sx as u16 / 2 - 14, 1))?;
-        stdout.queue(crossterm::style::Print("F O R B I D D E N D O N U T"))?;
-        stdout.queue(cursor::MoveTo(sx as u16 / 2 - 14, sy as u16 - 1))?;
-        stdout.queue(crossterm::style::Print("F O R B I D D E N D O N U T"))?;
+        let span = tracing::trace_span!("present").entered();
+        framebuffer.blit(target, params.tint);
+        let title = "F O R B I D D E N D O N U T";
+        if sx >= title.len() && sy >= 2 {
+            let x = (sx - title.len()) / 2;
+            target.put_str(x, 1, title, None);
+            target.put_str(x, sy - 1, title, None);
+        }
+        captions::draw(target, &caption);
+        if let Some(pane) = &pane {
+            let stats = match pacer.percentiles() {
+                Some((p50, p99)) => format!(
+                    "p50 {:.1}ms p99 {:.1}ms",
+                    p50.as_secs_f32() * 1000.0,
+                    p99.as_secs_f32() * 1000.0
+                ),
+                None => String::new(),
+            };
+            pane.draw(target, &stats);
+        }
+        inspector.draw(target, &mut scene, &mut params);
+        console.draw(target);
+        target.present()?;
+        drop(span);
+        if let Some((pipe, image)) = &mut video {
+            pipe.write(image)?;
+        }
+        if let (Some(metrics), Some(stats)) = (&mut metrics, &stats) {
+            metrics.record(started.elapsed());
+            metrics.serve(stats);
+        }
+        if let (true, Some(cells)) = (opts.dump_hash, &cells) {
+            println!("{} {:016x}", frame, cells.hash());
+        }
 
-        stdout.flush()?;
-        std::thread::sleep(std::time::Duration::from_millis(50));
+        frame += 1;
+        if frames.is_some_and(|n| frame >= n) {
+            break;
+        }
+        if !opts.headless && !offscreen(&opts) {
+            pacer.wait();
+        }
+    }
+    if let Some(journal) = &mut journal {
+        journal.end(frame);
+    }
+    // Out of raw mode first, for the newlines
+    drop(keys);
+    if let Some((pipe, _)) = video {
+        pipe.finish().unwrap_or_else(|msg| exit_with(&msg));
+    }
+    if let Some(seed) = opts.random {
+        eprintln!("--random {}", seed);
     }
+    Ok(())
 }
@@ -0,0 +1,207 @@
+use crate::server::Stats;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+// Frame times kept for the quantiles
+const HISTORY: usize = 200;
+
+// Scrapes not answered (or not taken) in this long are dropped.
+const GIVE_UP: Duration = Duration::from_secs(5);
+
+// Requests can't be longer than this.
+const LONGEST: usize = 8192;
+
+// A scrape in progress: the request so far, then the response still to go.
+struct Scrape {
+    stream: TcpStream,
+    request: Vec<u8>,
+    response: Vec<u8>,
+    opened: Instant,
+}
+
+impl Scrape {
+    // Reads what's come in: whether the request's all there, or None if the
+    // scraper's gone.
+    fn read(&mut self) -> Option<bool> {
+        let mut buf = [0; 1024];
+        loop {
+            let done = self.request.windows(4).any(|w| w == b"\r\n\r\n");
+            if done || self.request.len() >= LONGEST {
+                return Some(true);
+            }
+            match self.stream.read(&mut buf) {
+                Ok(0) => return None,
+                Ok(n) => self.request.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Some(false),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return None,
+            }
+        }
+    }
+
+    // Writes what it can of the response; false once it's all gone (or
+    // can't go).
+    fn write(&mut self) -> bool {
+        while !self.response.is_empty() {
+            match self.stream.write(&self.response) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.response.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        false
+    }
+}
+
+// --metrics ADDR: how --serve is doing, over HTTP in Prometheus' text format
+// at /metrics: clients connected, frames and bytes sent, and how long frames
+// take to render and send.
+pub struct Metrics {
+    listener: TcpListener,
+    scrapes: Vec<Scrape>,
+    times: VecDeque<Duration>,
+    sum: Duration,
+    count: u64,
+}
+
+impl Metrics {
+    pub fn bind(addr: &str) -> std::io::Result<Metrics> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Metrics {
+            listener,
+            scrapes: Vec::new(),
+            times: VecDeque::with_capacity(HISTORY),
+            sum: Duration::ZERO,
+            count: 0,
+        })
+    }
+
+    pub fn record(&mut self, frame_time: Duration) {
+        if self.times.len() == HISTORY {
+            self.times.pop_front();
+        }
+        self.times.push_back(frame_time);
+        self.sum += frame_time;
+        self.count += 1;
+    }
+
+    // Takes in new scrapes and gets on with those already in, a bit each
+    // frame without waiting on any, answering each once its request is in.
+    pub fn serve(&mut self, stats: &Stats) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.scrapes.push(Scrape {
+                    stream,
+                    request: Vec::new(),
+                    response: Vec::new(),
+                    opened: Instant::now(),
+                });
+            }
+        }
+        let mut scrapes = std::mem::take(&mut self.scrapes);
+        scrapes.retain_mut(|scrape| {
+            if scrape.opened.elapsed() > GIVE_UP {
+                return false;
+            }
+            if scrape.response.is_empty() {
+                match scrape.read() {
+                    Some(true) => scrape.response = self.respond(&scrape.request, stats),
+                    Some(false) => return true,
+                    None => return false,
+                }
+            }
+            scrape.write()
+        });
+        self.scrapes = scrapes;
+    }
+
+    fn respond(&self, request: &[u8], stats: &Stats) -> Vec<u8> {
+        let request = String::from_utf8_lossy(request);
+        let path = request.split_whitespace().nth(1).unwrap_or("");
+        let response = match path {
+            "/metrics" => {
+                let body = self.text(stats);
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            }
+            _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_string(),
+        };
+        response.into_bytes()
+    }
+
+    fn text(&self, stats: &Stats) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+            out.push_str(&value);
+        };
+        let clients = stats.clients.load(Ordering::Relaxed);
+        let frames = stats.frames.load(Ordering::Relaxed);
+        let bytes = stats.bytes.load(Ordering::Relaxed);
+        let name = "donut_clients";
+        metric(
+            name,
+            "gauge",
+            "Clients connected.",
+            format!("{} {}\n", name, clients),
+        );
+        let name = "donut_private_scenes";
+        let private = stats.private.load(Ordering::Relaxed);
+        let help = "Clients with scenes of their own.";
+        metric(name, "gauge", help, format!("{} {}\n", name, private));
+        let name = "donut_frames_total";
+        metric(
+            name,
+            "counter",
+            "Frames sent to clients, counting each client's.",
+            format!("{} {}\n", name, frames),
+        );
+        let name = "donut_bytes_sent_total";
+        metric(
+            name,
+            "counter",
+            "Bytes sent to clients.",
+            format!("{} {}\n", name, bytes),
+        );
+
+        let mut times: Vec<Duration> = self.times.iter().copied().collect();
+        times.sort();
+        let mut summary = String::new();
+        for (q, p) in [("0.5", 50), ("0.99", 99)] {
+            if let Some(t) = times.get(times.len().saturating_sub(1) * p / 100) {
+                let _ = writeln!(
+                    summary,
+                    "donut_frame_seconds{{quantile=\"{}\"}} {}",
+                    q,
+                    t.as_secs_f64()
+                );
+            }
+        }
+        let _ = writeln!(
+            summary,
+            "donut_frame_seconds_sum {}",
+            self.sum.as_secs_f64()
+        );
+        let _ = writeln!(summary, "donut_frame_seconds_count {}", self.count);
+        metric(
+            "donut_frame_seconds",
+            "summary",
+            "Time to render and send a frame.",
+            summary,
+        );
+        out
+    }
+}
@@ -0,0 +1,74 @@
+use crate::input::Input;
+use forbidden_donut::{Params, Vec3};
+use midir::{MidiInput, MidiInputConnection};
+use std::sync::mpsc::{channel, Receiver};
+
+// Live control from the first MIDI input port. Knobs sending CC 21-28 (the
+// default bank on most small controllers) map to:
+//   21-23  rotation speed about x, y, and the torus' axis
+//   24-25  major and minor radius
+//   26-27  light azimuth and elevation
+//   28     camera distance
+// Each parameter keeps its default until its knob is first moved.
+pub struct Midi {
+    _conn: MidiInputConnection<()>,
+    rx: Receiver<(u8, u8)>,
+    knobs: [Option<f32>; 8],
+}
+
+const FIRST_CC: u8 = 21;
+
+impl Midi {
+    pub fn open() -> std::result::Result<Midi, String> {
+        let input = MidiInput::new("forbidden-donut").map_err(|e| e.to_string())?;
+        let ports = input.ports();
+        let port = ports.first().ok_or("no MIDI input ports found")?;
+        let (tx, rx) = channel();
+        let conn = input
+            .connect(
+                port,
+                "forbidden-donut",
+                move |_, msg, _| {
+                    // Control change on any channel
+                    if msg.len() == 3 && msg[0] & 0xf0 == 0xb0 {
+                        let _ = tx.send((msg[1], msg[2]));
+                    }
+                },
+                (),
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(Midi {
+            _conn: conn,
+            rx,
+            knobs: [None; 8],
+        })
+    }
+}
+
+impl Input for Midi {
+    fn apply(&mut self, params: &mut Params) {
+        for (cc, value) in self.rx.try_iter() {
+            if let Some(knob) = self.knobs.get_mut(cc.wrapping_sub(FIRST_CC) as usize) {
+                *knob = Some(value as f32 / 127.0);
+            }
+        }
+        // Knob value k in [0, 1] mapped onto [lo, hi]
+        let k = |i: usize, lo: f32, hi: f32| self.knobs[i].map(|k| lo + k * (hi - lo));
+
+        params.spin.x = k(0, -0.2, 0.2).unwrap_or(params.spin.x);
+        params.spin.y = k(1, -0.2, 0.2).unwrap_or(params.spin.y);
+        params.spin.z = k(2, -0.2, 0.2).unwrap_or(params.spin.z);
+        params.r1 = k(3, 0.3, 1.5).unwrap_or(params.r1);
+        params.r2 = k(4, 0.05, 0.8).unwrap_or(params.r2);
+        if self.knobs[5].is_some() || self.knobs[6].is_some() {
+            let azimuth = k(5, -std::f32::consts::PI, std::f32::consts::PI).unwrap_or(0.3);
+            let elevation = k(6, -1.5, 1.5).unwrap_or(1.0);
+            params.light_dir = Vec3::new(
+                elevation.cos() * azimuth.sin(),
+                elevation.sin(),
+                -elevation.cos() * azimuth.cos(),
+            );
+        }
+        params.cam_dist = k(7, 2.5, 10.0).unwrap_or(params.cam_dist);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::input::Input;
+use crossterm::event::{Event, MouseButton, MouseEventKind};
+use crossterm::terminal;
+use forbidden_donut::{Mat4, Params, Vec3};
+use nalgebra::UnitQuaternion;
+
+// Dragging turns the torus like a trackball under the cursor, and the scroll
+// wheel zooms. The events come from the keyboard's reader (see keys.rs).
+pub struct Mouse {
+    // Where on the screen the trackball is: x, y, width, height
+    area: Option<(usize, usize, usize, usize)>,
+    // Where the drag was at last, on the trackball
+    grab: Option<Vec3>,
+    // The turn in eye space, over all of this frame's drag events
+    turn: UnitQuaternion<f32>,
+}
+
+impl Mouse {
+    pub fn new(viewport: Option<(usize, usize, usize, usize)>) -> Mouse {
+        Mouse {
+            area: viewport,
+            grab: None,
+            turn: UnitQuaternion::identity(),
+        }
+    }
+    // The point on the trackball under a cell, in eye space (x right, y up,
+    // z towards the viewer). Cells are about twice as tall as wide.
+    fn trackball(&self, column: u16, row: u16) -> Vec3 {
+        let (x0, y0, w, h) = self.area.unwrap_or_else(|| {
+            let (w, h) = terminal::size().unwrap_or((80, 24));
+            (0, 0, w as usize, h as usize)
+        });
+        let radius = 0.5 * (w as f32).min(2.0 * h as f32);
+        let x = (column as f32 - x0 as f32 - 0.5 * w as f32) / radius;
+        let y = 2.0 * (y0 as f32 + 0.5 * h as f32 - row as f32) / radius;
+        let d2 = x * x + y * y;
+        if d2 < 1.0 {
+            Vec3::new(x, y, (1.0 - d2).sqrt())
+        } else {
+            Vec3::new(x, y, 0.0).normalize()
+        }
+    }
+}
+
+impl Input for Mouse {
+    fn event(&mut self, event: &Event, params: &mut Params) {
+        let m = match event {
+            Event::Mouse(m) => m,
+            _ => return,
+        };
+        match m.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.grab = Some(self.trackball(m.column, m.row));
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let to = self.trackball(m.column, m.row);
+                if let Some(from) = self.grab {
+                    if let Some(q) = UnitQuaternion::rotation_between(&from, &to) {
+                        self.turn = q * self.turn;
+                    }
+                }
+                self.grab = Some(to);
+            }
+            MouseEventKind::Up(_) => self.grab = None,
+            MouseEventKind::ScrollUp => params.cam_dist = (params.cam_dist - 0.25).clamp(2.5, 12.0),
+            MouseEventKind::ScrollDown => {
+                params.cam_dist = (params.cam_dist + 0.25).clamp(2.5, 12.0)
+            }
+            _ => {}
+        }
+    }
+
+    fn apply(&mut self, params: &mut Params) {
+        // Into world space, through the camera's turn (as in the renderer)
+        let (yaw, pitch) = params.orbit;
+        let view = Mat4::from_euler_angles(0.0, std::f32::consts::PI, 0.0)
+            * Mat4::from_euler_angles(pitch, yaw, 0.0);
+        params.nudge = view.transpose() * self.turn.to_homogeneous() * view;
+        self.turn = UnitQuaternion::identity();
+    }
+}
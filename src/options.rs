@@ -0,0 +1,608 @@
+use crate::config::{self, Config, Value};
+use crate::journal::Replay;
+use crate::random;
+use forbidden_donut::caps::{ColorDepth, Preset};
+use forbidden_donut::color::Rgb;
+use forbidden_donut::{DebugView, Palette, Shape};
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use forbidden_donut::serial::Newline;
+
+pub struct Options {
+    // Render to stdout at a fixed size without touching the terminal.
+    pub headless: bool,
+    pub size: Option<(usize, usize)>,
+    // Draw only in this part of the terminal: x, y, width, height.
+    pub viewport: Option<(usize, usize, usize, usize)>,
+    pub frames: Option<usize>,
+    // Print a hash of each frame instead of drawing it, for golden tests.
+    pub dump_hash: bool,
+    // Encode the frames to this video file with ffmpeg, or "-" for y4m on
+    // stdout.
+    pub pipe_video: Option<String>,
+    pub fps: u32,
+    pub sysmon: bool,
+    // How long a day of the light going round takes, in seconds
+    pub daylight: Option<f32>,
+    pub audio: Option<String>,
+    pub midi: bool,
+    pub gamepad: bool,
+    pub mouse: bool,
+    // Draw the donuts with the GPU
+    pub gpu: bool,
+    // Serve the frames over TCP on this address, and how that's going over
+    // HTTP on another.
+    pub serve: Option<String>,
+    pub metrics: Option<String>,
+    // Up to this many clients get scenes of their own
+    pub per_client: Option<usize>,
+    // Take commands as JSON on this socket (or named pipe, on Windows).
+    pub control: Option<PathBuf>,
+    pub ramp: Option<String>,
+    pub two_sided: bool,
+    // Shapes to morph between, in turn
+    pub morph: Option<Vec<Shape>>,
+    // Blobs instead of the donut
+    pub metaballs: Option<usize>,
+    // A model from an OBJ, STL or PLY file instead of the donut
+    pub mesh: Option<PathBuf>,
+    // Several tori orbiting each other
+    pub swarm: Option<usize>,
+    pub palette: Option<String>,
+    pub debug_view: Option<DebugView>,
+    pub log_pane: bool,
+    // From the [palettes] table of the config file
+    pub palettes: Vec<(String, Palette)>,
+    // From the [scene] table: settings for the console's `set`, e.g.
+    // `light = [1, 2, -1]`
+    pub scene: Vec<(String, Vec<f32>)>,
+    pub highlights: bool,
+    // Slower, steadier and without flashes
+    pub reduced_motion: bool,
+    // Two glyphs, no color
+    pub high_contrast: bool,
+    // Glow around cells brighter than the threshold, spread this many rows
+    pub bloom: bool,
+    pub bloom_threshold: f32,
+    pub bloom_radius: usize,
+    // Scanlines, a curved screen and phosphor glow
+    pub crt: bool,
+    // How much of each frame carries over into the next, for trails
+    pub motion_blur: Option<f32>,
+    // Stretch the brightness the frame covers over the whole ramp
+    pub auto_exposure: bool,
+    // Keep the scene filling this much of the screen, moving the camera
+    pub auto_frame: Option<f32>,
+    // Blur what's nearer or further than this distance from the camera
+    pub focus: Option<f32>,
+    // None for auto
+    pub preset: Option<Preset>,
+    pub color: Option<ColorDepth>,
+    pub config: Option<PathBuf>,
+    pub save_config: bool,
+    // Save the frames' shading, for `play`
+    pub record: Option<PathBuf>,
+    // `play FILE`: show a recording instead of rendering
+    pub play: Option<PathBuf>,
+    // Blend between recorded frames when playing faster than they were
+    // recorded
+    pub interpolate: bool,
+    // Keep the input and parameter changes of the session, for --replay
+    pub journal: Option<PathBuf>,
+    // Go through a journaled session again
+    pub replay: Option<PathBuf>,
+    // Make up the scene from this seed
+    pub random: Option<u64>,
+    // Subtitles to show over the donut, in SubRip form
+    pub captions: Option<PathBuf>,
+    // The flags given, less --journal and sockets, for the journal
+    pub started_with: Vec<String>,
+    // Draw on a character display on this serial port instead.
+    #[cfg(unix)]
+    pub serial: Option<PathBuf>,
+    #[cfg(unix)]
+    pub baud: u32,
+    #[cfg(unix)]
+    pub newline: Newline,
+}
+
+const USAGE: &str = "usage: forbidden-donut [play FILE [--interpolate]] [--record FILE] [--journal FILE] [--replay FILE] [--random [SEED]] [--captions FILE.srt] [--headless] [--size WxH] [--viewport X,Y,W,H] [--frames N] [--dump-hash] [--pipe-video FILE|-] [--fps N] [--sysmon] [--daylight SECONDS] [--audio FILE.wav] [--midi] [--gamepad] [--mouse] [--gpu] [--control SOCKET] [--serve ADDR [--metrics ADDR] [--per-client N]] [--ramp NAME|GLYPHS] [--two-sided] [--swarm N] [--metaballs N] [--mesh FILE] [--morph SHAPE,...] [--palette NAME] [--debug-view normals|depth|uv|overdraw] [--log-pane] [--highlights] [--reduced-motion] [--high-contrast] [--bloom [--bloom-threshold T] [--bloom-radius N]] [--motion-blur AMOUNT] [--crt] [--auto-exposure] [--focus DIST] [--auto-frame FRACTION] [--preset auto|low|medium|ultra] [--color auto|mono|16|256|truecolor] [--config FILE] [--save-config] [--serial PORT [--baud N] [--newline lf|crlf|cr]]";
+
+fn parse_size(s: &str) -> Option<(usize, usize)> {
+    let mut it = s.split('x');
+    let sx = it.next()?.parse().ok()?;
+    let sy = it.next()?.parse().ok()?;
+    if it.next().is_some() || sx == 0 || sy == 0 {
+        return None;
+    }
+    Some((sx, sy))
+}
+
+pub fn parse_shapes(s: &str) -> std::result::Result<Vec<Shape>, String> {
+    s.split(',')
+        .map(|name| Shape::parse(name.trim()).ok_or(format!("unknown shape '{}'", name)))
+        .collect()
+}
+
+fn parse_viewport(s: &str) -> Option<(usize, usize, usize, usize)> {
+    let v: Vec<usize> = s
+        .split(',')
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+    match v[..] {
+        [x, y, w, h] if w > 0 && h > 0 => Some((x, y, w, h)),
+        _ => None,
+    }
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            headless: false,
+            size: None,
+            viewport: None,
+            frames: None,
+            dump_hash: false,
+            pipe_video: None,
+            fps: 20,
+            sysmon: false,
+            daylight: None,
+            audio: None,
+            midi: false,
+            gamepad: false,
+            gpu: false,
+            mouse: false,
+            serve: None,
+            metrics: None,
+            per_client: None,
+            control: None,
+            ramp: None,
+            two_sided: false,
+            swarm: None,
+            metaballs: None,
+            mesh: None,
+            morph: None,
+            palette: None,
+            debug_view: None,
+            log_pane: false,
+            palettes: Vec::new(),
+            scene: Vec::new(),
+            highlights: false,
+            reduced_motion: false,
+            high_contrast: false,
+            focus: None,
+            auto_frame: None,
+            auto_exposure: false,
+            motion_blur: None,
+            crt: false,
+            bloom: false,
+            bloom_threshold: 0.8,
+            bloom_radius: 2,
+            preset: None,
+            color: None,
+            config: config::default_path(),
+            save_config: false,
+            record: None,
+            play: None,
+            interpolate: false,
+            journal: None,
+            replay: None,
+            random: None,
+            captions: None,
+            started_with: Vec::new(),
+            #[cfg(unix)]
+            serial: None,
+            #[cfg(unix)]
+            baud: 9600,
+            #[cfg(unix)]
+            newline: Newline::CrLf,
+        }
+    }
+
+    // Sets one flag, pulling its value (if it takes one) from `value`.
+    fn set(
+        &mut self,
+        flag: &str,
+        mut value: impl FnMut() -> std::result::Result<String, String>,
+    ) -> std::result::Result<(), String> {
+        match flag {
+            "--headless" => self.headless = true,
+            "--size" => {
+                let v = value()?;
+                self.size = Some(parse_size(&v).ok_or(format!("bad size '{}'", v))?);
+            }
+            "--viewport" => {
+                let v = value()?;
+                self.viewport = Some(parse_viewport(&v).ok_or(format!("bad viewport '{}'", v))?);
+            }
+            "--frames" => {
+                let v = value()?;
+                self.frames = Some(v.parse().map_err(|_| format!("bad frame count '{}'", v))?);
+            }
+            "--fps" => {
+                let v = value()?;
+                self.fps = v
+                    .parse()
+                    .ok()
+                    .filter(|&fps| fps > 0)
+                    .ok_or(format!("bad fps '{}'", v))?;
+            }
+            "--dump-hash" => self.dump_hash = true,
+            "--pipe-video" => self.pipe_video = Some(value()?),
+            "--sysmon" => self.sysmon = true,
+            "--daylight" => {
+                let v = value()?;
+                self.daylight = Some(
+                    v.parse()
+                        .ok()
+                        .filter(|&s: &f32| s > 0.0)
+                        .ok_or(format!("bad day length '{}'", v))?,
+                );
+            }
+            "--audio" => self.audio = Some(value()?),
+            "--midi" => self.midi = true,
+            "--gamepad" => self.gamepad = true,
+            "--gpu" => self.gpu = true,
+            "--mouse" => self.mouse = true,
+            "--serve" => self.serve = Some(value()?),
+            "--metrics" => self.metrics = Some(value()?),
+            "--per-client" => {
+                let v = value()?;
+                self.per_client = Some(
+                    v.parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or(format!("bad number of scenes '{}'", v))?,
+                );
+            }
+            "--control" => self.control = Some(PathBuf::from(value()?)),
+            "--ramp" => {
+                let v = value()?;
+                if v.is_empty() {
+                    return Err("--ramp needs at least one glyph".to_string());
+                }
+                self.ramp = Some(v);
+            }
+            "--two-sided" => self.two_sided = true,
+            "--morph" => self.morph = Some(parse_shapes(&value()?)?),
+            "--metaballs" => {
+                let v = value()?;
+                self.metaballs = Some(
+                    v.parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or(format!("bad number of metaballs '{}'", v))?,
+                );
+            }
+            "--mesh" => self.mesh = Some(PathBuf::from(value()?)),
+            "--swarm" => {
+                let v = value()?;
+                self.swarm = Some(
+                    v.parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or(format!("bad swarm size '{}'", v))?,
+                );
+            }
+            "--palette" => self.palette = Some(value()?),
+            "--log-pane" => self.log_pane = true,
+            "--debug-view" => {
+                let v = value()?;
+                self.debug_view =
+                    Some(DebugView::parse(&v).ok_or(format!("unknown debug view '{}'", v))?);
+            }
+            "--highlights" => self.highlights = true,
+            "--reduced-motion" => self.reduced_motion = true,
+            "--high-contrast" => self.high_contrast = true,
+            "--bloom" => self.bloom = true,
+            "--bloom-threshold" => {
+                let v = value()?;
+                self.bloom_threshold = v
+                    .parse()
+                    .ok()
+                    .filter(|t: &f32| (0.0..1.0).contains(t))
+                    .ok_or(format!("bad bloom threshold '{}'", v))?;
+            }
+            "--bloom-radius" => {
+                let v = value()?;
+                self.bloom_radius = v
+                    .parse()
+                    .ok()
+                    .filter(|&r| r > 0)
+                    .ok_or(format!("bad bloom radius '{}'", v))?;
+            }
+            "--crt" => self.crt = true,
+            "--auto-exposure" => self.auto_exposure = true,
+            "--motion-blur" => {
+                let v = value()?;
+                self.motion_blur = Some(
+                    v.parse()
+                        .ok()
+                        .filter(|p: &f32| (0.0..1.0).contains(p))
+                        .ok_or(format!("bad motion blur '{}'", v))?,
+                );
+            }
+            "--auto-frame" => {
+                let v = value()?;
+                self.auto_frame = Some(
+                    v.parse()
+                        .ok()
+                        .filter(|f: &f32| *f > 0.0 && *f <= 1.0)
+                        .ok_or(format!("bad auto-frame fraction '{}'", v))?,
+                );
+            }
+            "--focus" => {
+                let v = value()?;
+                self.focus = Some(
+                    v.parse()
+                        .ok()
+                        .filter(|&d: &f32| d > 0.0)
+                        .ok_or(format!("bad focal distance '{}'", v))?,
+                );
+            }
+            "--preset" => {
+                let v = value()?;
+                self.preset = Preset::parse(&v).ok_or(format!("unknown preset '{}'", v))?;
+            }
+            "--color" => {
+                let v = value()?;
+                self.color = ColorDepth::parse(&v).ok_or(format!("unknown color mode '{}'", v))?;
+            }
+            "--config" => self.config = Some(PathBuf::from(value()?)),
+            "--save-config" => self.save_config = true,
+            "--record" => self.record = Some(PathBuf::from(value()?)),
+            "--interpolate" => self.interpolate = true,
+            "--journal" => self.journal = Some(PathBuf::from(value()?)),
+            "--replay" => self.replay = Some(PathBuf::from(value()?)),
+            "--random" => {
+                let v = value()?;
+                self.random = Some(v.parse().map_err(|_| format!("bad seed '{}'", v))?);
+            }
+            "--captions" => self.captions = Some(PathBuf::from(value()?)),
+            #[cfg(unix)]
+            "--serial" => self.serial = Some(PathBuf::from(value()?)),
+            #[cfg(unix)]
+            "--baud" => {
+                let v = value()?;
+                self.baud = v.parse().map_err(|_| format!("bad baud rate '{}'", v))?;
+            }
+            #[cfg(unix)]
+            "--newline" => {
+                let v = value()?;
+                self.newline = Newline::parse(&v).ok_or(format!("unknown newline '{}'", v))?;
+            }
+            _ => return Err(format!("unknown argument '{}'\n{}", flag, USAGE)),
+        }
+        Ok(())
+    }
+
+    // Config file settings first, then flags on top.
+    pub fn from_args() -> std::result::Result<Options, String> {
+        let mut args: Vec<String> = std::env::args().skip(1).collect();
+        let mut opts = Options::new();
+        // A replay starts with the flags the journaled session was given,
+        // with any given now on top
+        if let Some(ix) = args.iter().position(|a| a == "--replay") {
+            let path = PathBuf::from(args.get(ix + 1).ok_or("--replay needs a value")?);
+            let replay = Replay::load(&path)?;
+            args.drain(ix..ix + 2);
+            args.splice(0..0, replay.args);
+            opts.replay = Some(path);
+        }
+        // --random without a seed gets one, written in so the journal has it
+        let mut seed = None;
+        if let Some(ix) = args.iter().position(|a| a == "--random") {
+            seed = args.get(ix + 1).and_then(|s| s.parse().ok());
+            if seed.is_none() {
+                let made = random::seed();
+                args.insert(ix + 1, made.to_string());
+                seed = Some(made);
+            }
+        }
+        // Less anything a replay would open again: the journal itself, and
+        // sockets still bound by this session
+        let mut given = args.iter();
+        while let Some(arg) = given.next() {
+            match arg.as_str() {
+                "--journal" | "--control" | "--serve" | "--metrics" | "--per-client" => {
+                    given.next();
+                }
+                _ => opts.started_with.push(arg.clone()),
+            }
+        }
+        if args.first().map(String::as_str) == Some("play") {
+            let path = args.get(1).ok_or(format!("play needs a file\n{}", USAGE))?;
+            opts.play = Some(PathBuf::from(path));
+            args.drain(..2);
+        }
+        if let Some(ix) = args.iter().position(|a| a == "--config") {
+            let path = args.get(ix + 1).ok_or("--config needs a value")?;
+            opts.config = Some(PathBuf::from(path));
+        }
+        if let Some(path) = opts.config.clone() {
+            if let Some(config) = Config::load(&path)? {
+                opts.apply_config(&config)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+            }
+        }
+
+        // `random = true` in the config file makes up a seed there, which
+        // the journal needs too
+        if let (None, Some(made)) = (seed, opts.random) {
+            opts.started_with
+                .extend(["--random".to_string(), made.to_string()]);
+        }
+
+        // What it makes up goes over the config file, under the other flags
+        if let Some(seed) = seed.or(opts.random) {
+            random::dress(seed, &mut opts);
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            opts.set(&arg, || {
+                args.next()
+                    .ok_or(format!("{} needs a value\n{}", arg, USAGE))
+            })?;
+        }
+        if opts.headless || opts.dump_hash {
+            opts.size = opts.size.or(Some((80, 24)));
+            opts.frames = opts.frames.or(Some(1));
+        }
+        if opts.pipe_video.is_some() {
+            // Ten seconds
+            opts.frames = opts.frames.or(Some(10 * opts.fps as usize));
+        }
+        if opts.serve.is_some() {
+            opts.size = opts.size.or(Some((80, 24)));
+        } else if opts.metrics.is_some() || opts.per_client.is_some() {
+            return Err("--metrics and --per-client are for --serve".to_string());
+        }
+        #[cfg(unix)]
+        if opts.serial.is_some() {
+            opts.size = opts.size.or(Some((80, 24)));
+        }
+        opts.palette()?;
+        Ok(opts)
+    }
+
+    fn apply_config(&mut self, config: &Config) -> std::result::Result<(), String> {
+        for (key, value) in config.table("") {
+            let flag = format!("--{}", key);
+            match value {
+                Value::Bool(false) => {}
+                Value::Bool(true) if key == "random" => self.random = Some(random::seed()),
+                Value::Bool(true) => self.set(&flag, || Err(format!("{} isn't a switch", key)))?,
+                Value::Str(s) => self.set(&flag, || Ok(s.clone()))?,
+                Value::Int(i) => self.set(&flag, || Ok(i.to_string()))?,
+                Value::Float(x) => self.set(&flag, || Ok(x.to_string()))?,
+                _ => return Err(format!("bad value for {}", key)),
+            }
+        }
+        for (name, value) in config.table("scene") {
+            let number = |v: &Value| match *v {
+                Value::Int(i) => Some(i as f32),
+                Value::Float(x) => Some(x as f32),
+                _ => None,
+            };
+            let values = match value {
+                Value::List(items) => items.iter().map(number).collect(),
+                v => number(v).map(|x| vec![x]),
+            };
+            let values = values.ok_or(format!("bad value for scene {}", name))?;
+            self.scene.push((name.clone(), values));
+        }
+        // name = ["#rrggbb", ...], darkest first
+        for (name, value) in config.table("palettes") {
+            let stops = match value {
+                Value::List(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        Value::Str(s) => Rgb::from_hex(s),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>(),
+                _ => None,
+            };
+            match stops {
+                Some(stops) if !stops.is_empty() => self
+                    .palettes
+                    .push((name.clone(), Palette::gradient(&stops))),
+                _ => {
+                    return Err(format!(
+                        "palette {} should be a list of \"#rrggbb\" colors",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The --palette, from the config file's palettes or else the built in ones.
+    pub fn palette(&self) -> std::result::Result<Option<Palette>, String> {
+        let name = match &self.palette {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        self.palettes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, palette)| palette.clone())
+            .or_else(|| Palette::builtin(name))
+            .map(Some)
+            .ok_or(format!("unknown palette '{}'", name))
+    }
+
+    // Writes the settings worth keeping into the config file, leaving anything
+    // else in there alone.
+    pub fn save_config(&self) -> std::result::Result<PathBuf, String> {
+        let path = self
+            .config
+            .clone()
+            .ok_or("nowhere to save the config (no HOME)")?;
+        let config = Config::load(&path)?.unwrap_or_default();
+        self.write_config(&path, config)?;
+        Ok(path)
+    }
+
+    // As save_config, into `path`, along with what the scene is and its
+    // settings (for the [scene] table), to come back to with --config.
+    pub fn save_scene(
+        &self,
+        path: &Path,
+        scene: &[(&str, Vec<f32>)],
+    ) -> std::result::Result<(), String> {
+        let mut config = Config::load(path)?.unwrap_or_default();
+        if let Some(n) = self.swarm {
+            config.set("", "swarm", Value::Int(n as i64));
+        }
+        if let Some(n) = self.metaballs {
+            config.set("", "metaballs", Value::Int(n as i64));
+        }
+        if let Some(shapes) = &self.morph {
+            let names: Vec<&str> = shapes.iter().map(|s| s.name()).collect();
+            config.set("", "morph", Value::Str(names.join(",")));
+        }
+        config.set("", "two-sided", Value::Bool(self.two_sided));
+        for (name, values) in scene {
+            // Rounded, so 0.45 doesn't come out as 0.44999998807907104
+            let mut values: Vec<Value> = values
+                .iter()
+                .map(|&x| Value::Float((x as f64 * 1e4).round() / 1e4))
+                .collect();
+            let value = match values.len() {
+                1 => values.remove(0),
+                _ => Value::List(values),
+            };
+            config.set("scene", name, value);
+        }
+        self.write_config(path, config)
+    }
+
+    fn write_config(&self, path: &Path, mut config: Config) -> std::result::Result<(), String> {
+        let preset = self.preset.map_or("auto", |p| p.name());
+        config.set("", "preset", Value::Str(preset.to_string()));
+        let color = self.color.map_or("auto", |c| c.name());
+        config.set("", "color", Value::Str(color.to_string()));
+        if let Some(ramp) = &self.ramp {
+            config.set("", "ramp", Value::Str(ramp.clone()));
+        }
+        if let Some(palette) = &self.palette {
+            config.set("", "palette", Value::Str(palette.clone()));
+        }
+        config.set("", "fps", Value::Int(self.fps as i64));
+        config.set("", "midi", Value::Bool(self.midi));
+        config.set("", "gamepad", Value::Bool(self.gamepad));
+        config.set("", "mouse", Value::Bool(self.mouse));
+        config.set("", "reduced-motion", Value::Bool(self.reduced_motion));
+        config.set("", "high-contrast", Value::Bool(self.high_contrast));
+
+        let err = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(err)?;
+        }
+        std::fs::write(path, config.to_string()).map_err(err)
+    }
+}
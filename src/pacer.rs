@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Sleeping overshoots by up to a scheduler tick, so the last stretch before a
+// frame is due is spent spinning instead.
+const SPIN: Duration = Duration::from_millis(2);
+
+// Frame times kept for the percentiles
+const HISTORY: usize = 200;
+
+// Starts frames at a steady rate, however long each one takes to draw.
+pub struct Pacer {
+    interval: Duration,
+    // When the next frame is due
+    next: Instant,
+    last: Instant,
+    times: VecDeque<Duration>,
+}
+
+impl Pacer {
+    pub fn new(fps: u32) -> Pacer {
+        let now = Instant::now();
+        let interval = Duration::from_secs(1) / fps;
+        Pacer {
+            interval,
+            next: now + interval,
+            last: now,
+            times: VecDeque::with_capacity(HISTORY),
+        }
+    }
+
+    pub fn set_fps(&mut self, fps: u32) {
+        self.interval = Duration::from_secs(1) / fps;
+    }
+
+    // Waits until the next frame is due.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if self.next > now + SPIN {
+            std::thread::sleep(self.next - now - SPIN);
+        }
+        while Instant::now() < self.next {
+            std::hint::spin_loop();
+        }
+
+        let now = Instant::now();
+        if self.times.len() == HISTORY {
+            self.times.pop_front();
+        }
+        self.times.push_back(now - self.last);
+        self.last = now;
+        // After a frame that ran long, start over from now rather than
+        // rushing out frames to catch up.
+        self.next += self.interval;
+        if self.next < now {
+            self.next = now + self.interval;
+        }
+    }
+
+    // Median and 99th percentile of the recent frame times.
+    pub fn percentiles(&self) -> Option<(Duration, Duration)> {
+        let mut times: Vec<_> = self.times.iter().copied().collect();
+        times.sort();
+        let at = |p: usize| times[(times.len() - 1) * p / 100];
+        (!times.is_empty()).then(|| (at(50), at(99)))
+    }
+}
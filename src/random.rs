@@ -0,0 +1,107 @@
+use crate::input::Input;
+use crate::options::Options;
+use forbidden_donut::palette::BUILTIN;
+use forbidden_donut::ramp::RAMPS;
+use forbidden_donut::{Params, Shape};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::TAU;
+use std::time::SystemTime;
+
+// --random [SEED]: a scene made up from a seed: the donut's shape and what it
+// morphs through, its palette and ramp, how it spins, where the light is and
+// the path the camera takes round it. The same seed always makes the same
+// scene, and any flags given as well win over what it picks.
+
+// A seed from the clock, for --random without one.
+pub fn seed() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |t| t.as_nanos() as u64)
+}
+
+// Picks the scene for `seed`, over whatever the config file said.
+pub fn dress(seed: u64, opts: &mut Options) {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let palettes: Vec<&str> = BUILTIN.iter().map(|(name, _)| *name).collect();
+    let palettes = [&palettes[..], &["rainbow", "depth"]].concat();
+    // Now and then, plain white
+    opts.palette = rng
+        .gen_bool(0.8)
+        .then(|| palettes.choose(&mut rng).unwrap().to_string());
+    opts.ramp = Some(RAMPS.choose(&mut rng).unwrap().0.to_string());
+    opts.highlights = rng.gen_bool(0.3);
+    opts.morph = rng.gen_bool(0.5).then(|| {
+        let mut shapes = Shape::ALL.to_vec();
+        shapes.shuffle(&mut rng);
+        shapes.truncate(rng.gen_range(2..=Shape::ALL.len()));
+        shapes
+    });
+    // The knot's tube shows its inside as it turns
+    opts.two_sided = opts.morph.is_some();
+
+    let r1 = rng.gen_range(0.7..1.3);
+    let r2 = rng.gen_range(0.2..0.6) * r1;
+    let mut spin = || rng.gen_range(-0.12..0.12);
+    let spin = vec![spin(), spin(), spin()];
+    // From anywhere above the horizon
+    let (around, up) = (rng.gen_range(0.0..TAU), rng.gen_range(0.2..1.3f32));
+    let light = vec![around.cos() * up.cos(), up.sin(), around.sin() * up.cos()];
+    opts.scene.extend([
+        ("r1".to_string(), vec![r1]),
+        ("r2".to_string(), vec![r2]),
+        ("spin".to_string(), spin),
+        ("speed".to_string(), vec![rng.gen_range(0.6..1.5)]),
+        ("gain".to_string(), vec![rng.gen_range(0.8..1.3)]),
+        ("light".to_string(), light),
+        ("dist".to_string(), vec![rng.gen_range(3.5..5.5)]),
+    ]);
+}
+
+// The camera's path: round the donut at a steady rate one way or the other,
+// rising and falling as it goes. It moves the camera by as much as the path
+// does each frame, so the mouse or a gamepad can still turn it too.
+pub struct Path {
+    // Where it starts round, and how far it turns a frame
+    start: f32,
+    step: f32,
+    // How far up and down it goes, and how fast
+    height: f32,
+    bob: f32,
+    frame: f32,
+    // Where the path had the camera last frame
+    at: (f32, f32),
+}
+
+impl Path {
+    // From the same seed as dress(), on a stream of its own.
+    pub fn new(seed: u64, fps: u32) -> Path {
+        let mut rng = StdRng::seed_from_u64(seed ^ 0x9e37_79b9_7f4a_7c15);
+        let fps = fps as f32;
+        // Once round every 20 to 80 seconds
+        let period = rng.gen_range(20.0..80.0) * fps;
+        let direction = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+        Path {
+            start: rng.gen_range(0.0..TAU),
+            step: direction * TAU / period,
+            height: rng.gen_range(0.0..0.6),
+            bob: TAU / (rng.gen_range(8.0..30.0) * fps),
+            frame: 0.0,
+            at: (0.0, 0.0),
+        }
+    }
+}
+
+impl Input for Path {
+    fn apply(&mut self, params: &mut Params) {
+        let yaw = self.start + self.step * self.frame;
+        let pitch = self.height * (self.bob * self.frame).sin();
+        let (orbit_yaw, orbit_pitch) = &mut params.orbit;
+        *orbit_yaw += yaw - self.at.0;
+        *orbit_pitch = (*orbit_pitch + pitch - self.at.1).clamp(-1.5, 1.5);
+        self.at = (yaw, pitch);
+        self.frame += 1.0;
+    }
+}
@@ -0,0 +1,205 @@
+use crate::config::{self, Value};
+use forbidden_donut::color::Rgb;
+use forbidden_donut::{FrameBuffer, Shade};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Recordings (--record) keep each cell's shading rather than its glyph, so
+// playback can blend between frames. The file is "DONUTREC", the frame rate
+// as a u16, then each frame: width and height as u16s, and per cell a
+// brightness byte (0 for empty, else 1 to 255) and an RGB color (unused
+// unless the flag byte after it is 1). Little-endian throughout.
+const MAGIC: &[u8] = b"DONUTREC";
+
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, fps: u32) -> std::io::Result<Recorder> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&(fps.min(u16::MAX as u32) as u16).to_le_bytes())?;
+        Ok(Recorder { out })
+    }
+
+    pub fn write(&mut self, framebuffer: &FrameBuffer) -> std::io::Result<()> {
+        let (sx, sy) = (framebuffer.sx, framebuffer.sy);
+        let mut frame = Vec::with_capacity(4 + 5 * sx * sy);
+        frame.extend_from_slice(&(sx as u16).to_le_bytes());
+        frame.extend_from_slice(&(sy as u16).to_le_bytes());
+        for y in 0..sy {
+            for x in 0..sx {
+                match framebuffer.shading(x, y) {
+                    Some((brightness, color)) => {
+                        frame.push(1 + (brightness.clamp(0.0, 1.0) * 254.0).round() as u8);
+                        let Rgb(r, g, b) = color.unwrap_or(Rgb(0, 0, 0));
+                        frame.extend_from_slice(&[r, g, b, color.is_some() as u8]);
+                    }
+                    None => frame.extend_from_slice(&[0; 5]),
+                }
+            }
+        }
+        self.out.write_all(&frame)?;
+        self.out.flush()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Cell {
+    brightness: f32,
+    color: Option<Rgb>,
+}
+
+struct Frame {
+    sx: usize,
+    sy: usize,
+    cells: Vec<Option<Cell>>,
+}
+
+pub struct Recording {
+    fps: u32,
+    frames: Vec<Frame>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> std::result::Result<Recording, String> {
+        let err = |e: &str| format!("{}: {}", path.display(), e);
+        let data = std::fs::read(path).map_err(|e| err(&e.to_string()))?;
+        let rest = data
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| err("not a recording"))?;
+        let u16_at = |bytes: &[u8], i: usize| bytes[i] as usize | (bytes[i + 1] as usize) << 8;
+        if rest.len() < 2 {
+            return Err(err("truncated"));
+        }
+        let fps = u16_at(rest, 0).max(1) as u32;
+        let mut rest = &rest[2..];
+        let mut frames = Vec::new();
+        while !rest.is_empty() {
+            if rest.len() < 4 {
+                return Err(err("truncated"));
+            }
+            let (sx, sy) = (u16_at(rest, 0), u16_at(rest, 2));
+            let body = rest
+                .get(4..4 + 5 * sx * sy)
+                .ok_or_else(|| err("truncated"))?;
+            let cells = body
+                .chunks(5)
+                .map(|c| {
+                    (c[0] > 0).then(|| Cell {
+                        brightness: (c[0] - 1) as f32 / 254.0,
+                        color: (c[4] == 1).then(|| Rgb(c[1], c[2], c[3])),
+                    })
+                })
+                .collect();
+            frames.push(Frame { sx, sy, cells });
+            rest = &rest[4 + body.len()..];
+        }
+        if frames.is_empty() {
+            return Err(err("no frames"));
+        }
+        Ok(Recording { fps, frames })
+    }
+
+    // How long it runs, in frames at `fps`.
+    pub fn frames_at(&self, fps: u32) -> usize {
+        (self.frames.len() * fps as usize).div_ceil(self.fps as usize)
+    }
+
+    // Shades the framebuffer with the recording `frame` frames in at `fps`,
+    // blending the recorded frames either side when `interpolate` is set.
+    pub fn show(&self, frame: usize, fps: u32, interpolate: bool, framebuffer: &mut FrameBuffer) {
+        let at = frame as f32 * self.fps as f32 / fps as f32;
+        let a = (at as usize).min(self.frames.len() - 1);
+        let b = (a + 1).min(self.frames.len() - 1);
+        let (a, b) = (&self.frames[a], &self.frames[b]);
+        // Frames either side of a resize don't line up, so show the first as is
+        let (b, t) = if interpolate && (a.sx, a.sy) == (b.sx, b.sy) {
+            (b, at.fract())
+        } else {
+            (a, 0.0)
+        };
+
+        // Centered on the screen, cut off if it doesn't fit
+        let (ox, oy) = (
+            framebuffer.sx as isize - a.sx as isize,
+            framebuffer.sy as isize - a.sy as isize,
+        );
+        for y in 0..a.sy {
+            for x in 0..a.sx {
+                let (px, py) = (x as isize + ox / 2, y as isize + oy / 2);
+                if px < 0
+                    || py < 0
+                    || px >= framebuffer.sx as isize
+                    || py >= framebuffer.sy as isize
+                {
+                    continue;
+                }
+                let ix = y * a.sx + x;
+                let cell = match (a.cells[ix], b.cells[ix]) {
+                    (Some(a), Some(b)) => Some(Cell {
+                        brightness: a.brightness + (b.brightness - a.brightness) * t,
+                        color: match (a.color, b.color) {
+                            (Some(ca), Some(cb)) => Some(ca.lerp(cb, t)),
+                            _ => a.color,
+                        },
+                    }),
+                    // Appearing or vanishing: whichever frame is nearer
+                    (a, b) => {
+                        if t < 0.5 {
+                            a
+                        } else {
+                            b
+                        }
+                    }
+                };
+                if let Some(cell) = cell {
+                    let shade = Shade {
+                        brightness: cell.brightness,
+                        specular: 0.0,
+                        color: cell.color,
+                    };
+                    framebuffer.poke_if(px as usize, py as usize, 0.0, shade);
+                }
+            }
+        }
+    }
+}
+
+// Plays an asciinema recording (.cast, version 2) to stdout in real time, or
+// as fast as possible when `realtime` isn't set.
+pub fn play_cast(path: &Path, realtime: bool) -> std::result::Result<(), String> {
+    let err = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let text = std::fs::read_to_string(path).map_err(|e| err(&e))?;
+    let mut lines = text.lines();
+    match lines.next() {
+        Some(header) if header.trim_start().starts_with('{') => {}
+        _ => return Err(err(&"not an asciinema recording")),
+    }
+    let start = Instant::now();
+    let mut stdout = std::io::stdout();
+    for (n, line) in lines.enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        // [seconds, "o", "data"]
+        let event = match config::parse_value(line) {
+            Some(Value::List(items)) => items,
+            _ => return Err(err(&format!("bad event on line {}", n + 2))),
+        };
+        let (time, data) = match &event[..] {
+            [Value::Float(t), Value::Str(kind), Value::Str(data)] if kind == "o" => (*t, data),
+            [Value::Int(t), Value::Str(kind), Value::Str(data)] if kind == "o" => (*t as f64, data),
+            _ => continue,
+        };
+        if realtime {
+            let due = Duration::from_secs_f64(time.max(0.0));
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+        stdout.write_all(data.as_bytes()).map_err(|e| err(&e))?;
+        stdout.flush().map_err(|e| err(&e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use crate::recording::Recording;
+use crate::swarm::{Body, Swarm};
+use forbidden_donut::color::Rgb;
+use forbidden_donut::metaballs::Ball;
+use forbidden_donut::{
+    Donut, FrameBuffer, Mesh, Metaballs, Palette, Params, Point, Shape, Timeline,
+};
+use nalgebra::Rotation3;
+
+#[cfg(feature = "gpu")]
+pub use forbidden_donut::gpu::Gpu;
+
+// Without the gpu feature there's never one to draw with.
+#[cfg(not(feature = "gpu"))]
+pub enum Gpu {}
+
+#[cfg(not(feature = "gpu"))]
+impl Gpu {
+    pub fn draw(&mut self, _: &Donut, _: &Params, _: &mut FrameBuffer) {
+        match *self {}
+    }
+
+    pub fn finish(&mut self, _: &mut FrameBuffer) {
+        match *self {}
+    }
+}
+
+// What's drawn: a recording played back, or one of the live scenes.
+pub enum Scene {
+    Playing {
+        recording: Recording,
+        fps: u32,
+        interpolate: bool,
+    },
+    Swarm(Swarm),
+    // Drawn with the look (shader, palette...) of the donut
+    Metaballs(Metaballs, Donut),
+    // Turned, sized and drawn like the donut
+    Mesh(Mesh, Donut),
+    Donut(Donut, Option<Timeline>),
+}
+
+impl Scene {
+    // With the donuts on `gpu`, if given.
+    pub fn render(
+        &mut self,
+        frame: usize,
+        params: &Params,
+        framebuffer: &mut FrameBuffer,
+        gpu: Option<&mut Gpu>,
+    ) {
+        match self {
+            Scene::Playing {
+                recording,
+                fps,
+                interpolate,
+            } => recording.show(frame, *fps, *interpolate, framebuffer),
+            Scene::Swarm(swarm) => swarm.render(params, framebuffer, gpu),
+            Scene::Metaballs(metaballs, look) => metaballs.render(look, params, framebuffer),
+            Scene::Mesh(mesh, look) => mesh.render(look, params, framebuffer),
+            Scene::Donut(donut, timeline) => {
+                donut.morph = timeline.as_ref().map(|timeline| timeline.at(donut.t));
+                match gpu {
+                    Some(gpu) => {
+                        gpu.draw(donut, params, framebuffer);
+                        gpu.finish(framebuffer);
+                    }
+                    None => donut.render(params, framebuffer),
+                }
+            }
+        }
+    }
+
+    // The radius about the origin everything's inside, for auto-framing, or
+    // None for recordings.
+    pub fn bound(&self, params: &Params) -> Option<f32> {
+        let torus = params.r1.abs() + params.r2.abs();
+        match self {
+            Scene::Playing { .. } => None,
+            Scene::Swarm(swarm) => Some(swarm.bound(params)),
+            Scene::Metaballs(metaballs, _) => Some(metaballs.bound()),
+            // Every shape fits in the torus' bounding sphere, and meshes are
+            // scaled to it
+            Scene::Mesh(..) | Scene::Donut(..) => Some(torus),
+        }
+    }
+
+    pub fn step(&mut self, params: &Params) {
+        match self {
+            Scene::Playing { .. } => {}
+            Scene::Swarm(swarm) => swarm.step(params),
+            Scene::Metaballs(metaballs, _) => metaballs.step(params),
+            Scene::Mesh(_, look) => look.step(params),
+            Scene::Donut(donut, _) => donut.step(params),
+        }
+    }
+
+    // Makes the donut morph through `shapes`, or just be the one.
+    pub fn set_shapes(&mut self, shapes: Vec<Shape>) -> std::result::Result<(), String> {
+        match self {
+            Scene::Donut(_, timeline) => {
+                *timeline = Some(Timeline::new(shapes));
+                Ok(())
+            }
+            _ => Err("only the lone donut changes shape".to_string()),
+        }
+    }
+
+    pub fn set_palette(&mut self, palette: Option<Palette>) {
+        match self {
+            Scene::Playing { .. } => {}
+            Scene::Swarm(swarm) => {
+                for body in &mut swarm.bodies {
+                    body.donut.palette = palette.clone();
+                }
+            }
+            Scene::Metaballs(_, donut) | Scene::Mesh(_, donut) | Scene::Donut(donut, _) => {
+                donut.palette = palette
+            }
+        }
+    }
+
+    // The things in it, for the inspector.
+    pub fn objects(&mut self) -> Vec<Object<'_>> {
+        match self {
+            Scene::Playing { .. } => Vec::new(),
+            Scene::Swarm(swarm) => swarm.bodies.iter_mut().map(Object::Body).collect(),
+            Scene::Metaballs(metaballs, _) => {
+                let centers: Vec<Point> = metaballs
+                    .balls
+                    .iter()
+                    .map(|b| metaballs.center(b))
+                    .collect();
+                metaballs
+                    .balls
+                    .iter_mut()
+                    .zip(centers)
+                    .map(|(ball, center)| Object::Blob(ball, center))
+                    .collect()
+            }
+            Scene::Mesh(_, donut) => vec![Object::Mesh(donut)],
+            Scene::Donut(donut, _) => vec![Object::Donut(donut)],
+        }
+    }
+}
+
+pub enum Object<'a> {
+    // The lone donut, whose size and spin are the scene's
+    Donut(&'a mut Donut),
+    // The look of a mesh, whose transform turns it
+    Mesh(&'a mut Donut),
+    Body(&'a mut Body),
+    // A metaball, and where it is now
+    Blob(&'a mut Ball, Point),
+}
+
+// A number the inspector can turn up and down, by `step` a keypress.
+pub struct Property<'a> {
+    pub name: &'static str,
+    pub value: &'a mut f32,
+    pub step: f32,
+}
+
+impl Object<'_> {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Object::Donut(donut) if donut.morph.is_some() => "morph",
+            Object::Donut(_) | Object::Body(_) => "torus",
+            Object::Mesh(_) => "mesh",
+            Object::Blob(..) => "blob",
+        }
+    }
+
+    pub fn visible(&mut self) -> &mut bool {
+        match self {
+            Object::Donut(donut) | Object::Mesh(donut) => &mut donut.visible,
+            Object::Body(body) => &mut body.donut.visible,
+            Object::Blob(ball, _) => &mut ball.visible,
+        }
+    }
+
+    // Where it is, how it's turned and what it's made of, in a line.
+    pub fn describe(&self) -> String {
+        match self {
+            Object::Donut(donut) | Object::Mesh(donut) => placement(donut),
+            Object::Body(body) => placement(&body.donut),
+            Object::Blob(ball, center) => format!(
+                "at {:+.2} {:+.2} {:+.2}  radius {:.2}",
+                center.x, center.y, center.z, ball.radius
+            ),
+        }
+    }
+
+    pub fn properties<'b>(&'b mut self, params: &'b mut Params) -> Vec<Property<'b>> {
+        match self {
+            Object::Donut(donut) | Object::Mesh(donut) => {
+                // The translation column
+                let at = donut.transform.as_mut_slice()[12..15].iter_mut();
+                named(&["x", "y", "z"], at, 0.1)
+                    .chain(named(
+                        &["spin x", "spin y", "spin z"],
+                        params.spin.iter_mut(),
+                        0.01,
+                    ))
+                    .chain(named(&["r1", "r2"], [&mut params.r1, &mut params.r2], 0.05))
+                    .collect()
+            }
+            Object::Body(body) => named(&["x", "y", "z"], body.position.iter_mut(), 0.1)
+                .chain(named(
+                    &["spin x", "spin y", "spin z"],
+                    body.spin.iter_mut(),
+                    0.01,
+                ))
+                .collect(),
+            Object::Blob(ball, _) => named(&["radius"], [&mut ball.radius], 0.05)
+                .chain(named(
+                    &["reach x", "reach y", "reach z"],
+                    ball.amplitude.iter_mut(),
+                    0.1,
+                ))
+                .collect(),
+        }
+    }
+}
+
+fn named<'a>(
+    names: &'static [&'static str],
+    values: impl IntoIterator<Item = &'a mut f32>,
+    step: f32,
+) -> impl Iterator<Item = Property<'a>> {
+    names
+        .iter()
+        .zip(values)
+        .map(move |(&name, value)| Property { name, value, step })
+}
+
+// A donut's position, turn (as Euler angles, in degrees) and palette.
+fn placement(donut: &Donut) -> String {
+    let t = &donut.transform;
+    let turn = Rotation3::from_matrix_unchecked(t.fixed_view::<3, 3>(0, 0).into_owned());
+    let (roll, pitch, yaw) = turn.euler_angles();
+    let material = match &donut.palette {
+        None => "plain".to_string(),
+        Some(Palette::Gradient(lut)) => {
+            let Rgb(r, g, b) = lut[lut.len() / 2];
+            format!("gradient #{:02x}{:02x}{:02x}", r, g, b)
+        }
+        Some(Palette::Normal) => "normal".to_string(),
+        Some(Palette::Depth) => "depth".to_string(),
+    };
+    format!(
+        "at {:+.2} {:+.2} {:+.2}  turned {:+4.0} {:+4.0} {:+4.0}  {}",
+        t[(0, 3)],
+        t[(1, 3)],
+        t[(2, 3)],
+        roll.to_degrees(),
+        pitch.to_degrees(),
+        yaw.to_degrees(),
+        material
+    )
+}
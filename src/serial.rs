@@ -0,0 +1,128 @@
+// Character displays on a serial port: VFDs, character LCDs, and vintage
+// terminals on RS-232.
+use crate::target::{Cell, CellBuffer, RenderTarget};
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+// Starts every frame. Clears the screen and homes the cursor on most
+// character displays and many terminals.
+const FORM_FEED: u8 = 0x0c;
+
+// What ends each row. Displays wrap, so nothing follows the last one: that
+// would scroll the frame off the top.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Newline {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl Newline {
+    pub fn parse(s: &str) -> Option<Newline> {
+        match s {
+            "lf" => Some(Newline::Lf),
+            "crlf" => Some(Newline::CrLf),
+            "cr" => Some(Newline::Cr),
+            _ => None,
+        }
+    }
+
+    fn bytes(self) -> &'static [u8] {
+        match self {
+            Newline::Lf => b"\n",
+            Newline::CrLf => b"\r\n",
+            Newline::Cr => b"\r",
+        }
+    }
+}
+
+// Opens a serial port raw (8N1, no flow control, no newline translation) at
+// `baud`. The port has to exist; anything that isn't a tty, like a plain
+// file, is written as is.
+pub fn open(path: &Path, baud: u32) -> crate::Result<File> {
+    let speed = match baud {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("unsupported baud rate {}", baud),
+            ))
+        }
+    };
+    let port = std::fs::OpenOptions::new().write(true).open(path)?;
+    let fd = port.as_raw_fd();
+    if unsafe { libc::isatty(fd) } == 1 {
+        unsafe {
+            let mut tio: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut tio) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            libc::cfmakeraw(&mut tio);
+            tio.c_cflag &= !(libc::CSTOPB | libc::PARENB | libc::CRTSCTS);
+            tio.c_cflag |= libc::CLOCAL;
+            libc::cfsetispeed(&mut tio, speed);
+            libc::cfsetospeed(&mut tio, speed);
+            if libc::tcsetattr(fd, libc::TCSANOW, &tio) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(port)
+}
+
+// A fixed-size display fed plain text: no color and no escape sequences, so
+// glyphs outside ASCII come out as '#'.
+pub struct Serial<W: Write> {
+    out: W,
+    newline: Newline,
+    cells: CellBuffer,
+}
+
+impl<W: Write> Serial<W> {
+    pub fn new(out: W, (sx, sy): (usize, usize), newline: Newline) -> Serial<W> {
+        Serial {
+            out,
+            newline,
+            cells: CellBuffer::new(sx, sy),
+        }
+    }
+}
+
+impl<W: Write> RenderTarget for Serial<W> {
+    fn dimensions(&self) -> (usize, usize) {
+        self.cells.dimensions()
+    }
+
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        self.cells.put(x, y, cell);
+    }
+
+    fn present(&mut self) -> crate::Result<()> {
+        let (sx, sy) = self.cells.dimensions();
+        let mut frame = vec![FORM_FEED];
+        for y in 0..sy {
+            if y > 0 {
+                frame.extend_from_slice(self.newline.bytes());
+            }
+            // The tail of a wide glyph is a second '#', keeping the columns
+            frame.extend((0..sx).map(|x| match self.cells.shown(x, y) {
+                Some(glyph) if glyph.is_ascii() && !glyph.is_ascii_control() => glyph as u8,
+                _ => b'#',
+            }));
+        }
+        // One write per frame; at low baud rates it blocks for a good while.
+        self.out.write_all(&frame)?;
+        self.out.flush()?;
+        self.cells.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+use forbidden_donut::ansi;
+use forbidden_donut::caps::ColorDepth;
+use forbidden_donut::target::{Buffers, Cell, RenderTarget};
+use forbidden_donut::{Donut, FrameBuffer, Mat4, Params, Vec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Hides the cursor and clears the screen, for each new client
+const HELLO: &[u8] = b"\x1b[?25l\x1b[2J";
+
+// Asks telnet clients for their window size (IAC DO NAWS)
+const ASK_SIZE: &[u8] = b"\xff\xfd\x1f";
+
+// Telnet: IAC, SB (subnegotiation), SE (its end) and the NAWS option
+const IAC: u8 = 255;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const NAWS: u8 = 31;
+
+// Running totals, for --metrics
+#[derive(Default)]
+pub struct Stats {
+    pub clients: AtomicUsize,
+    pub frames: AtomicU64,
+    pub bytes: AtomicU64,
+    // Clients with scenes of their own
+    pub private: AtomicUsize,
+}
+
+// --per-client N: up to N clients get a donut of their own, started
+// differently and drawn to the size of their window (where telnet says what
+// that is), but otherwise set up like the shared one. The rest share, as do
+// any that connect while the private scenes are taking too long to draw, and
+// the latest private ones go back to sharing for as long as they are.
+pub struct PerClient {
+    pub max: usize,
+    // A donut dressed like the shared one
+    pub donut: Box<dyn Fn() -> Donut>,
+    // The shared scene's parameters, as the render loop leaves them each frame
+    pub params: Arc<Mutex<Params>>,
+    pub ramp: Vec<char>,
+    pub highlights: Option<Vec<char>>,
+    // What drawing them all may take, a frame
+    pub budget: Duration,
+}
+
+struct Private {
+    donut: Donut,
+    // Its own tumbling, in place of the shared scene's
+    spin: Vec3,
+    framebuffer: FrameBuffer,
+    cells: Buffers,
+}
+
+// --serve ADDR: the donut over TCP, for `telnet HOST PORT` or `nc HOST
+// PORT`. Every client gets the same frames, as ANSI at a fixed size: the
+// whole frame to start with, then just what changed. What they send is
+// ignored.
+pub struct Server {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    depth: ColorDepth,
+    cells: Buffers,
+    pub stats: Arc<Stats>,
+    per_client: Option<PerClient>,
+    // Seeds each private scene differently
+    seed: u64,
+    // How long the private scenes took last frame
+    private_time: Duration,
+}
+
+struct Client {
+    stream: TcpStream,
+    // What hasn't gone out yet. A client this far behind misses frames until
+    // it catches up, rather than holding up everyone else.
+    backlog: Vec<u8>,
+    // Missed the last shared frame (or hasn't had one yet), so needs the
+    // whole of the next rather than what changed
+    behind: bool,
+    // What they've sent that might be the start of a window size
+    input: Vec<u8>,
+    size: Option<(usize, usize)>,
+    private: Option<Box<Private>>,
+}
+
+impl Server {
+    pub fn bind(
+        addr: &str,
+        (sx, sy): (usize, usize),
+        depth: ColorDepth,
+    ) -> std::io::Result<Server> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server {
+            listener,
+            clients: Vec::new(),
+            depth,
+            cells: Buffers::new(sx, sy),
+            stats: Arc::default(),
+            per_client: None,
+            seed: 0,
+            private_time: Duration::ZERO,
+        })
+    }
+
+    pub fn set_per_client(&mut self, per_client: PerClient) {
+        self.per_client = Some(per_client);
+    }
+
+    // A scene of its own for a new client, if there's room for one.
+    fn private(&mut self) -> Option<Box<Private>> {
+        let per_client = self.per_client.as_ref()?;
+        let count = self.clients.iter().filter(|c| c.private.is_some()).count();
+        if count >= per_client.max || self.private_time > per_client.budget {
+            return None;
+        }
+        self.seed += 1;
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut donut = (per_client.donut)();
+        let mut turn = || rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI);
+        donut.transform = Mat4::from_euler_angles(turn(), turn(), turn());
+        let mut spin = || rng.gen_range(-0.12..0.12);
+        let spin = Vec3::new(spin(), spin(), spin());
+        let mut framebuffer = FrameBuffer::new();
+        framebuffer.seed(self.seed);
+        framebuffer.ramp = per_client.ramp.clone();
+        framebuffer.highlights = per_client.highlights.clone();
+        Some(Box::new(Private {
+            donut,
+            spin,
+            framebuffer,
+            cells: Buffers::new(0, 0),
+        }))
+    }
+}
+
+impl Client {
+    // Reads what the client's sent, looking for window sizes (IAC SB NAWS
+    // width height IAC SE, each a 16 bit number). False once it's gone.
+    fn read(&mut self) -> bool {
+        let mut buf = [0; 256];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        while let Some(start) = self.input.windows(3).position(|w| w == [IAC, SB, NAWS]) {
+            let rest = &self.input[start + 3..];
+            let end = match rest.windows(2).position(|w| w == [IAC, SE]) {
+                Some(end) => end,
+                None => break,
+            };
+            // A 255 in the numbers comes doubled
+            let mut numbers = Vec::new();
+            let mut bytes = rest[..end].iter();
+            while let Some(&b) = bytes.next() {
+                numbers.push(b);
+                if b == IAC {
+                    bytes.next();
+                }
+            }
+            if let [w1, w0, h1, h0] = numbers[..] {
+                let w = u16::from_be_bytes([w1, w0]) as usize;
+                let h = u16::from_be_bytes([h1, h0]) as usize;
+                if w > 0 && h > 0 {
+                    self.size = Some((w.min(400), h.min(200)));
+                }
+            }
+            self.input.drain(..start + 3 + end + 2);
+        }
+        // Nothing else they send matters
+        let keep = self.input.len().saturating_sub(64);
+        self.input.drain(..keep);
+        true
+    }
+
+    // Sends what it can without waiting, keeping the rest. False once the
+    // client's gone.
+    fn send(&mut self, bytes: &[u8], stats: &Stats) -> bool {
+        self.backlog.extend_from_slice(bytes);
+        while !self.backlog.is_empty() {
+            match self.stream.write(&self.backlog) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.backlog.drain(..n);
+                    stats.bytes.fetch_add(n as u64, Ordering::Relaxed);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+impl RenderTarget for Server {
+    fn dimensions(&self) -> (usize, usize) {
+        self.cells.dimensions()
+    }
+
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        self.cells.put(x, y, cell);
+    }
+
+    fn present(&mut self) -> forbidden_donut::Result<()> {
+        while let Ok((stream, addr)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_err() {
+                continue;
+            }
+            tracing::info!("{} connected", addr);
+            let mut client = Client {
+                stream,
+                backlog: Vec::new(),
+                behind: true,
+                input: Vec::new(),
+                size: None,
+                private: self.private(),
+            };
+            let ask = if client.private.is_some() {
+                ASK_SIZE
+            } else {
+                &[]
+            };
+            if client.send(HELLO, &self.stats) && client.send(ask, &self.stats) {
+                self.clients.push(client);
+            }
+        }
+        let damage = self.cells.swap();
+        let cells = self.cells.front();
+        let mut changes = String::new();
+        ansi::encode_rects(cells, (0, 0), &damage, self.depth, &mut changes);
+        // The whole frame, for clients that are behind, if any
+        let mut whole = None;
+        let started = Instant::now();
+        let (shared, depth, stats) = (cells.dimensions(), self.depth, &self.stats);
+        // Dragging with the mouse turns the shared donut only
+        let params = self.per_client.as_ref().map(|per_client| Params {
+            nudge: Mat4::identity(),
+            ..*per_client.params.lock().unwrap()
+        });
+        self.clients.retain_mut(|client| {
+            if !client.read() {
+                return false;
+            }
+            if !client.backlog.is_empty() {
+                client.behind = true;
+                return client.send(&[], stats);
+            }
+            let sent = match &mut client.private {
+                Some(private) => {
+                    let params = Params {
+                        spin: private.spin,
+                        ..params.unwrap_or_default()
+                    };
+                    let (sx, sy) = client.size.unwrap_or(shared);
+                    let mut own = String::new();
+                    if private.cells.dimensions() != (sx, sy) {
+                        private.cells.resize(sx, sy);
+                        own.push_str("\x1b[2J");
+                    }
+                    private.framebuffer.clear(sx, sy);
+                    private.donut.render(&params, &mut private.framebuffer);
+                    private.donut.step(&params);
+                    private.framebuffer.blit(&mut private.cells, None);
+                    let damage = private.cells.swap();
+                    ansi::encode_rects(private.cells.front(), (0, 0), &damage, depth, &mut own);
+                    client.send(own.as_bytes(), stats)
+                }
+                None if client.behind => {
+                    client.behind = false;
+                    let whole = whole.get_or_insert_with(|| {
+                        let mut frame = String::new();
+                        ansi::encode(cells, depth, &mut frame);
+                        frame
+                    });
+                    client.send(whole.as_bytes(), stats)
+                }
+                None => client.send(changes.as_bytes(), stats),
+            };
+            if sent {
+                stats.frames.fetch_add(1, Ordering::Relaxed);
+            }
+            sent
+        });
+        self.private_time = started.elapsed();
+        if let Some(per_client) = &self.per_client {
+            if self.private_time > per_client.budget {
+                let latest = self.clients.iter_mut().rev().find(|c| c.private.is_some());
+                if let Some(client) = latest {
+                    client.private = None;
+                    client.behind = true;
+                    client.send(b"\x1b[2J", stats);
+                }
+            }
+        }
+        let private = self.clients.iter().filter(|c| c.private.is_some()).count();
+        stats.private.store(private, Ordering::Relaxed);
+        stats.clients.store(self.clients.len(), Ordering::Relaxed);
+        Ok(())
+    }
+}
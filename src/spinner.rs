@@ -0,0 +1,206 @@
+// A spinning donut in place of a CLI progress spinner: drawn at the cursor on
+// stderr, a few lines tall, by a background thread while the program works.
+use crate::ansi;
+use crate::caps::{Capabilities, ColorDepth};
+use crate::target::{is_wide, CellBuffer};
+use crate::{Donut, FrameBuffer, Params};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const FRAME: Duration = Duration::from_millis(50);
+
+// Spinners at least this many cells across get the donut's full detail.
+const DETAILED: f32 = 40.0;
+
+pub struct DonutSpinner {
+    size: (usize, usize),
+    // Shown to the right of the donut, halfway down
+    message: Arc<Mutex<String>>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<crate::Result<()>>>,
+}
+
+impl DonutSpinner {
+    // A spinner `sx` by `sy` cells; 20x10 looks about right.
+    pub fn new(sx: usize, sy: usize) -> DonutSpinner {
+        DonutSpinner {
+            size: (sx.max(1), sy.max(1)),
+            message: Arc::new(Mutex::new(String::new())),
+            running: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        }
+    }
+
+    // Starts spinning, unless it already is.
+    pub fn start(&mut self) {
+        if self.thread.is_some() {
+            return;
+        }
+        self.running.store(true, Ordering::SeqCst);
+        let (size, message, running) = (self.size, self.message.clone(), self.running.clone());
+        self.thread = Some(std::thread::spawn(move || spin(size, &message, &running)));
+    }
+
+    pub fn set_message(&self, message: &str) {
+        *self.message.lock().unwrap() = message.to_string();
+    }
+
+    // Stops spinning, leaving the last frame and message on screen and the
+    // cursor on the line below them.
+    pub fn finish(&mut self) -> crate::Result<()> {
+        self.running.store(false, Ordering::SeqCst);
+        match self.thread.take() {
+            Some(thread) => thread.join().unwrap(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for DonutSpinner {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+fn spin(size: (usize, usize), message: &Mutex<String>, running: &AtomicBool) -> crate::Result<()> {
+    let mut err = std::io::stderr();
+    let caps = Capabilities::probe();
+    // Redrawing in place takes escape sequences; without them (or when stderr
+    // is redirected), only the message is printed, once done.
+    let color = (caps.vt && err.is_terminal()).then_some(caps.color);
+    let width = || crossterm::terminal::size().map_or(usize::MAX, |(w, _)| w as usize);
+    draw(&mut err, color, width, size, message, running)
+}
+
+// Draws frames into `out` until `running` goes false, in `color` on a
+// terminal `width` columns across, or just prints the message at the end for
+// None.
+fn draw(
+    out: &mut dyn Write,
+    color: Option<ColorDepth>,
+    width: impl Fn() -> usize,
+    (sx, sy): (usize, usize),
+    message: &Mutex<String>,
+    running: &AtomicBool,
+) -> crate::Result<()> {
+    let color = match color {
+        Some(color) => color,
+        None => {
+            while running.load(Ordering::SeqCst) {
+                std::thread::sleep(FRAME);
+            }
+            return writeln!(out, "{}", message.lock().unwrap());
+        }
+    };
+
+    let params = Params::default();
+    let mut donut = Donut::new();
+    // The default detail is for a whole screen; a spinner this small needs
+    // far fewer samples to cover its cells
+    let detail = (sx.max(2 * sy) as f32 / DETAILED).min(1.0);
+    donut.n1 = (donut.n1 as f32 * detail) as usize;
+    donut.n2 = (donut.n2 as f32 * detail) as usize;
+    let mut framebuffer = FrameBuffer::new();
+    let mut cells = CellBuffer::new(sx, sy);
+    let mut first = true;
+    write!(out, "\x1b[?25l")?;
+    loop {
+        // Checked before drawing, so the last frame drawn shows the final
+        // message.
+        let last = !running.load(Ordering::SeqCst);
+        framebuffer.clear(sx, sy);
+        donut.render(&params, &mut framebuffer);
+        donut.step(&params);
+        cells.clear();
+        framebuffer.blit(&mut cells, None);
+
+        // The first frame's newlines scroll the screen if need be; after that
+        // each frame goes back up over the previous one.
+        let mut frame = String::new();
+        if !first {
+            frame.push_str(&format!("\x1b[{}A", sy));
+        }
+        // Cut to the line, or wrapping would throw out going back up
+        let mut room = width().saturating_sub(sx + 1);
+        let message: String = message
+            .lock()
+            .unwrap()
+            .chars()
+            .take_while(|&c| {
+                let columns = if is_wide(c) { 2 } else { 1 };
+                let fits = columns <= room;
+                room = room.saturating_sub(columns);
+                fits
+            })
+            .collect();
+        for y in 0..sy {
+            frame.push('\r');
+            ansi::encode_row(&cells, y, color, &mut frame);
+            if y == sy / 2 {
+                frame.push(' ');
+                frame.push_str(&message);
+            }
+            frame.push_str("\x1b[K\r\n");
+        }
+        if last {
+            frame.push_str("\x1b[?25h");
+        }
+        out.write_all(frame.as_bytes())?;
+        out.flush()?;
+        if last {
+            return Ok(());
+        }
+        first = false;
+        std::thread::sleep(FRAME);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_in_place_with_the_message_cut_to_the_line() {
+        let message = Mutex::new("compiling the world".to_string());
+        let running = AtomicBool::new(true);
+        let mut out = Vec::new();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(3 * FRAME);
+                running.store(false, Ordering::SeqCst);
+            });
+            let size = (20, 10);
+            draw(
+                &mut out,
+                Some(ColorDepth::Ansi16),
+                || 30,
+                size,
+                &message,
+                &running,
+            )
+            .unwrap();
+        });
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b[?25l") && out.ends_with("\x1b[?25h"));
+        // Later frames go back up over the first
+        let frames = out.split("\x1b[10A").collect::<Vec<_>>();
+        assert!(frames.len() > 1);
+        for frame in frames {
+            assert_eq!(frame.matches("\r\n").count(), 10);
+            // Nine columns are left of 30 beside the donut and a space
+            assert!(frame.contains(" compiling\x1b[K"));
+            assert!(frame.contains(['@', '#', '*']));
+        }
+    }
+
+    #[test]
+    fn only_prints_the_message_when_redirected() {
+        let (message, running) = (Mutex::new("done".to_string()), AtomicBool::new(false));
+        let mut out = Vec::new();
+        draw(&mut out, None, || 80, (20, 10), &message, &running).unwrap();
+        assert_eq!(out, b"done\n");
+    }
+}
@@ -0,0 +1,134 @@
+use crate::scene::Gpu;
+use forbidden_donut::{Donut, FrameBuffer, Mat4, Palette, Params, Vec3};
+
+// Palettes the tori take turns with, unless --palette picks one for all
+const MATERIALS: &[&str] = &["amber", "green", "synthwave", "rainbow", "depth"];
+
+// How far from the center the tori start, their size, and how far back the
+// camera has to be to see them all
+const ORBIT: f32 = 2.2;
+const SCALE: f32 = 0.45;
+pub const CAM_DIST: f32 = 8.0;
+
+// Gravity, and softening so near misses don't fling tori off to infinity
+const G: f32 = 0.02;
+const SOFTENING: f32 = 0.5;
+
+pub struct Body {
+    pub donut: Donut,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    // Each torus tumbles its own way
+    pub spin: Vec3,
+}
+
+// --swarm N: tori orbiting their common center under each other's gravity.
+pub struct Swarm {
+    pub bodies: Vec<Body>,
+}
+
+impl Swarm {
+    // N tori around a tilted ring, moving just fast enough for a circular
+    // orbit. `setup` fills in each donut's options as for the lone donut.
+    pub fn new(n: usize, palette: Option<Palette>, setup: impl Fn(&mut Donut)) -> Swarm {
+        // For equal masses on a ring, each one's pull towards the center from
+        // all the others
+        let pull: f32 = (1..n)
+            .map(|k| {
+                let d = 2.0 * ORBIT * (std::f32::consts::PI * k as f32 / n as f32).sin();
+                G * d * d / (2.0 * ORBIT) / (d * d + SOFTENING * SOFTENING).powf(1.5)
+            })
+            .sum();
+        let speed = (pull * ORBIT).sqrt();
+        let tilt = 0.4f32;
+        let bodies = (0..n)
+            .map(|i| {
+                let a = std::f32::consts::TAU * i as f32 / n as f32;
+                let (s, c) = a.sin_cos();
+                let position = ORBIT * Vec3::new(c, s * tilt.sin(), s * tilt.cos());
+                let mut donut = Donut::new();
+                setup(&mut donut);
+                // Smaller, so fewer points cover them
+                donut.n1 /= 2;
+                donut.n2 /= 2;
+                donut.transform = Mat4::new_translation(&position);
+                donut.palette = palette
+                    .clone()
+                    .or_else(|| Palette::builtin(MATERIALS[i % MATERIALS.len()]));
+                // Spread the spins out by the golden angle
+                let g = 2.4 * i as f32;
+                Body {
+                    donut,
+                    position,
+                    velocity: speed * Vec3::new(-s, c * tilt.sin(), c * tilt.cos()),
+                    spin: Vec3::new(0.1 * g.cos(), 0.1 * g.sin(), 0.03 + 0.02 * (i % 3) as f32),
+                }
+            })
+            .collect();
+        Swarm { bodies }
+    }
+
+    pub fn render(
+        &self,
+        params: &Params,
+        framebuffer: &mut FrameBuffer,
+        mut gpu: Option<&mut Gpu>,
+    ) {
+        for body in &self.bodies {
+            let params = body_params(params, body);
+            match &mut gpu {
+                Some(gpu) => gpu.draw(&body.donut, &params, framebuffer),
+                None => body.donut.render(&params, framebuffer),
+            }
+        }
+        if let Some(gpu) = gpu {
+            gpu.finish(framebuffer);
+        }
+    }
+
+    // The radius about the origin all the tori are inside.
+    pub fn bound(&self, params: &Params) -> f32 {
+        let size = SCALE * (params.r1.abs() + params.r2.abs());
+        self.bodies
+            .iter()
+            .map(|body| body.position.norm() + size)
+            .fold(0.0, f32::max)
+    }
+
+    // One frame of tumbling, then of gravity (semi-implicit Euler).
+    pub fn step(&mut self, params: &Params) {
+        for body in &mut self.bodies {
+            let params = body_params(params, body);
+            body.donut.step(&params);
+        }
+        let positions: Vec<Vec3> = self.bodies.iter().map(|b| b.position).collect();
+        for (i, body) in self.bodies.iter_mut().enumerate() {
+            let accel: Vec3 = positions
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, &p)| {
+                    let d = p - body.position;
+                    let r2 = d.norm_squared() + SOFTENING * SOFTENING;
+                    d * (G / (r2 * r2.sqrt()))
+                })
+                .sum();
+            body.velocity += accel * params.speed;
+            body.position += body.velocity * params.speed;
+            let t = &mut body.donut.transform;
+            t[(0, 3)] = body.position.x;
+            t[(1, 3)] = body.position.y;
+            t[(2, 3)] = body.position.z;
+        }
+    }
+}
+
+// The scene parameters for one torus: smaller, and with its own spin.
+fn body_params(params: &Params, body: &Body) -> Params {
+    Params {
+        r1: params.r1 * SCALE,
+        r2: params.r2 * SCALE,
+        spin: body.spin,
+        ..*params
+    }
+}
@@ -0,0 +1,70 @@
+use crate::input::Input;
+use forbidden_donut::color::Rgb;
+use forbidden_donut::Params;
+use std::time::{Duration, Instant};
+use sysinfo::System;
+
+// Samples system load for --sysmon with sysinfo, which reads it the way
+// each platform has it. CPU usage is meaningless over short intervals, so
+// it's sampled once a second.
+pub struct SysMon {
+    system: System,
+    last: Instant,
+    // All in [0, 1], except load which is the 1 minute load average per core.
+    pub cpu: f32,
+    pub memory: f32,
+    pub load: f32,
+}
+
+impl SysMon {
+    pub fn new() -> SysMon {
+        let mut sysmon = SysMon {
+            system: System::new(),
+            last: Instant::now(),
+            cpu: 0.0,
+            memory: 0.0,
+            load: 0.0,
+        };
+        sysmon.refresh();
+        sysmon
+    }
+
+    fn refresh(&mut self) {
+        // Usage since the last refresh, so nothing until the second
+        self.system.refresh_cpu_usage();
+        self.cpu = (self.system.global_cpu_usage() / 100.0).clamp(0.0, 1.0);
+        self.system.refresh_memory();
+        let total = self.system.total_memory();
+        if total > 0 {
+            self.memory = 1.0 - self.system.available_memory() as f32 / total as f32;
+        }
+        // Windows has no load average, so it stays at none
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        self.load = System::load_average().one as f32 / cores as f32;
+        self.last = Instant::now();
+        tracing::debug!(
+            "cpu {:.0}%, memory {:.0}%, load {:.2}",
+            100.0 * self.cpu,
+            100.0 * self.memory,
+            self.load
+        );
+    }
+}
+
+impl Input for SysMon {
+    // CPU usage spins the donut, memory usage fattens it, and load takes it
+    // from green when idle, through yellow, to red once every core is busy.
+    fn apply(&mut self, params: &mut Params) {
+        if self.last.elapsed() >= Duration::from_secs(1) {
+            self.refresh();
+        }
+        params.speed = 0.25 + 2.0 * self.cpu;
+        params.r2 = 0.15 + 0.5 * self.memory;
+        let (green, yellow, red) = (Rgb(0, 255, 0), Rgb(255, 255, 0), Rgb(255, 0, 0));
+        params.tint = Some(if self.load < 0.5 {
+            green.lerp(yellow, self.load * 2.0)
+        } else {
+            yellow.lerp(red, self.load * 2.0 - 1.0)
+        });
+    }
+}
@@ -0,0 +1,109 @@
+use crate::ansi;
+use crate::caps::Capabilities;
+use crate::target::{Buffers, Cell, RenderTarget};
+use crossterm::style::Print;
+use crossterm::{cursor, terminal, QueueableCommand};
+use std::io::Write;
+
+// A terminal (or anything taking the same escape sequences, like stdout when
+// running headless). Each present redraws only the cells that changed, or the
+// whole screen the first time and after a resize.
+pub struct Terminal<W: Write> {
+    out: W,
+    // Fixed size, instead of asking the terminal
+    size: Option<(usize, usize)>,
+    // Only draw in this rectangle (x, y, width, height), leaving the rest of
+    // the screen alone
+    viewport: Option<(usize, usize, usize, usize)>,
+    caps: Capabilities,
+    cells: Buffers,
+}
+
+impl<W: Write> Terminal<W> {
+    pub fn new(
+        mut out: W,
+        size: Option<(usize, usize)>,
+        caps: Capabilities,
+    ) -> crate::Result<Terminal<W>> {
+        out.queue(cursor::Hide)?;
+        let mut term = Terminal {
+            out,
+            size,
+            viewport: None,
+            caps,
+            cells: Buffers::new(0, 0),
+        };
+        let (sx, sy) = term.dimensions();
+        term.cells = Buffers::new(sx, sy);
+        Ok(term)
+    }
+
+    pub fn set_viewport(&mut self, viewport: Option<(usize, usize, usize, usize)>) {
+        self.viewport = viewport;
+        let (sx, sy) = self.dimensions();
+        self.cells = Buffers::new(sx, sy);
+    }
+
+    // The whole screen's size.
+    fn screen_size(&self) -> (usize, usize) {
+        match self.size {
+            Some(size) => size,
+            None => {
+                let (sx, sy) = terminal::size().unwrap();
+                (sx as usize, sy as usize)
+            }
+        }
+    }
+
+    fn origin(&self) -> (usize, usize) {
+        self.viewport.map_or((0, 0), |(x, y, _, _)| (x, y))
+    }
+}
+
+impl<W: Write> RenderTarget for Terminal<W> {
+    // The viewport's size, cut down to what's on screen.
+    fn dimensions(&self) -> (usize, usize) {
+        let (sx, sy) = self.screen_size();
+        match self.viewport {
+            Some((x, y, w, h)) => (w.min(sx.saturating_sub(x)), h.min(sy.saturating_sub(y))),
+            None => (sx, sy),
+        }
+    }
+
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        self.cells.put(x, y, cell);
+    }
+
+    fn present(&mut self) -> crate::Result<()> {
+        let (x0, y0) = self.origin();
+        if self.cells.is_stale() && self.viewport.is_none() {
+            self.out.queue(terminal::Clear(terminal::ClearType::All))?;
+        }
+        let damage = self.cells.swap();
+        let cells = self.cells.front();
+        if self.caps.vt {
+            let mut frame = String::new();
+            ansi::encode_rects(cells, (x0, y0), &damage, self.caps.color, &mut frame);
+            self.out.write_all(frame.as_bytes())?;
+        } else {
+            // Without escape sequences: no color, and crossterm positions the
+            // cursor through the console API.
+            for rect in &damage {
+                for y in rect.y..rect.y + rect.h {
+                    let run: String = (rect.x..rect.x + rect.w)
+                        .filter_map(|x| cells.shown(x, y))
+                        .collect();
+                    self.out
+                        .queue(cursor::MoveTo((x0 + rect.x) as u16, (y0 + y) as u16))?;
+                    self.out.queue(Print(run))?;
+                }
+            }
+        }
+        self.out.flush()?;
+
+        // The next frame goes at whatever size the terminal is now.
+        let (sx, sy) = self.dimensions();
+        self.cells.resize(sx, sy);
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+// Just enough of a terminfo reader to find out how many colors a terminal
+// claims to have.
+
+fn find(term: &str) -> Option<Vec<u8>> {
+    let first = term.chars().next()?;
+    let mut dirs: Vec<std::path::PathBuf> = Vec::new();
+    if let Some(dir) = std::env::var_os("TERMINFO") {
+        dirs.push(dir.into());
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(std::path::Path::new(&home).join(".terminfo"));
+    }
+    if let Some(list) = std::env::var_os("TERMINFO_DIRS") {
+        dirs.extend(std::env::split_paths(&list));
+    }
+    for dir in &[
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+    ] {
+        dirs.push(dir.into());
+    }
+    for dir in dirs {
+        // Entries live under their first letter, or its hex code on macOS.
+        for sub in &[first.to_string(), format!("{:x}", first as u32)] {
+            if let Ok(data) = std::fs::read(dir.join(sub).join(term)) {
+                return Some(data);
+            }
+        }
+    }
+    None
+}
+
+// The max_colors numeric capability, 0 if the terminal's entry doesn't have
+// it, or None if there's no usable entry for the terminal at all.
+pub fn colors(term: &str) -> Option<u32> {
+    const MAX_COLORS: usize = 13;
+    let data = find(term)?;
+    let short = |i: usize| -> Option<usize> {
+        let b = data.get(2 * i..2 * i + 2)?;
+        Some(u16::from_le_bytes([b[0], b[1]]) as usize)
+    };
+    // Legacy format has 16 bit numbers, the extended one 32 bit.
+    let width = match short(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names, bools, nums) = (short(1)?, short(2)?, short(3)?);
+    if MAX_COLORS >= nums {
+        return Some(0);
+    }
+    let mut offset = 12 + names + bools;
+    // Numbers start on an even byte
+    offset += offset % 2;
+    let at = offset + MAX_COLORS * width;
+    let bytes = data.get(at..at + width)?;
+    let value = if width == 2 {
+        i16::from_le_bytes([bytes[0], bytes[1]]) as i32
+    } else {
+        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    };
+    Some(value.max(0) as u32)
+}
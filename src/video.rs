@@ -0,0 +1,91 @@
+use forbidden_donut::Image;
+use std::io::Write;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+// Pixels per cell in the video, about the shape of a terminal cell
+pub const CELL: (usize, usize) = (8, 16);
+
+// --pipe-video: frames as raw video, piped into ffmpeg to encode to a file,
+// or as YUV4MPEG2 (y4m) on stdout for `-`.
+pub enum Video {
+    Ffmpeg(Child, ChildStdin),
+    Y4m(std::io::Stdout),
+}
+
+impl Video {
+    pub fn open(
+        dest: &str,
+        (width, height): (usize, usize),
+        fps: u32,
+    ) -> std::result::Result<Video, String> {
+        if dest == "-" {
+            let mut out = std::io::stdout();
+            writeln!(
+                out,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                width, height, fps
+            )
+            .map_err(|e| e.to_string())?;
+            return Ok(Video::Y4m(out));
+        }
+        let mut child = Command::new("ffmpeg")
+            .args([
+                "-loglevel",
+                "error",
+                "-y",
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                "rgb24",
+            ])
+            .args(["-s", &format!("{}x{}", width, height)])
+            .args(["-r", &fps.to_string(), "-i", "-"])
+            // Most players want 4:2:0
+            .args(["-pix_fmt", "yuv420p", dest])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("can't run ffmpeg: {}", e))?;
+        let stdin = child.stdin.take().unwrap();
+        Ok(Video::Ffmpeg(child, stdin))
+    }
+
+    pub fn write(&mut self, image: &Image) -> std::io::Result<()> {
+        match self {
+            Video::Ffmpeg(_, stdin) => stdin.write_all(&image.rgb),
+            Video::Y4m(out) => {
+                // Full frame of Y, then Cb, then Cr (BT.601, studio range)
+                let mut frame = Vec::with_capacity(6 + image.rgb.len());
+                frame.extend_from_slice(b"FRAME\n");
+                let planes: [fn(f32, f32, f32) -> f32; 3] = [
+                    |r, g, b| 16.0 + 0.257 * r + 0.504 * g + 0.098 * b,
+                    |r, g, b| 128.0 - 0.148 * r - 0.291 * g + 0.439 * b,
+                    |r, g, b| 128.0 + 0.439 * r - 0.368 * g - 0.071 * b,
+                ];
+                for plane in planes {
+                    frame.extend(
+                        image
+                            .rgb
+                            .chunks(3)
+                            .map(|p| plane(p[0] as f32, p[1] as f32, p[2] as f32).round() as u8),
+                    );
+                }
+                out.write_all(&frame)
+            }
+        }
+    }
+
+    // Waits for ffmpeg to finish encoding.
+    pub fn finish(self) -> std::result::Result<(), String> {
+        match self {
+            Video::Ffmpeg(mut child, stdin) => {
+                drop(stdin);
+                let status = child.wait().map_err(|e| e.to_string())?;
+                if !status.success() {
+                    return Err(format!("ffmpeg failed ({})", status));
+                }
+                Ok(())
+            }
+            Video::Y4m(mut out) => out.flush().map_err(|e| e.to_string()),
+        }
+    }
+}
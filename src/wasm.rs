@@ -0,0 +1,76 @@
+use crate::ansi;
+use crate::caps::ColorDepth;
+use crate::target::{Buffers, Cell, RenderTarget};
+use crate::{Donut, FrameBuffer, Params};
+use wasm_bindgen::prelude::*;
+
+// Hands each frame, as ANSI text redrawing what changed, to a JS callback
+// (e.g. xterm.js' `term.write`) instead of a terminal.
+struct JsTarget {
+    cells: Buffers,
+    on_frame: js_sys::Function,
+}
+
+impl RenderTarget for JsTarget {
+    fn dimensions(&self) -> (usize, usize) {
+        self.cells.dimensions()
+    }
+
+    fn put(&mut self, x: usize, y: usize, cell: Cell) {
+        self.cells.put(x, y, cell);
+    }
+
+    fn present(&mut self) -> crate::Result<()> {
+        let damage = self.cells.swap();
+        let mut frame = String::new();
+        ansi::encode_rects(
+            self.cells.front(),
+            (0, 0),
+            &damage,
+            ColorDepth::TrueColor,
+            &mut frame,
+        );
+        let _ = self
+            .on_frame
+            .call1(&JsValue::NULL, &JsValue::from_str(&frame));
+        Ok(())
+    }
+}
+
+// The donut for web pages. Call `frame()` on a timer; see web/index.html.
+#[wasm_bindgen]
+pub struct WebDonut {
+    donut: Donut,
+    params: Params,
+    framebuffer: FrameBuffer,
+    target: JsTarget,
+}
+
+#[wasm_bindgen]
+impl WebDonut {
+    #[wasm_bindgen(constructor)]
+    pub fn new(cols: usize, rows: usize, on_frame: js_sys::Function) -> WebDonut {
+        WebDonut {
+            donut: Donut::new(),
+            params: Params::default(),
+            framebuffer: FrameBuffer::new(),
+            target: JsTarget {
+                cells: Buffers::new(cols, rows),
+                on_frame,
+            },
+        }
+    }
+
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        self.target.cells.resize(cols, rows);
+    }
+
+    pub fn frame(&mut self) {
+        let (sx, sy) = self.target.dimensions();
+        self.framebuffer.clear(sx, sy);
+        self.donut.render(&self.params, &mut self.framebuffer);
+        self.donut.step(&self.params);
+        self.framebuffer.blit(&mut self.target, self.params.tint);
+        let _ = self.target.present();
+    }
+}
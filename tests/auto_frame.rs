@@ -0,0 +1,68 @@
+// --auto-frame: the scene fitted to the screen.
+mod common;
+
+use common::{check, spawn, wait, ColorDepth, Profile};
+
+const PROFILE: Profile = Profile {
+    color: ColorDepth::Mono,
+    unicode: false,
+    size: (60, 20),
+};
+
+// The leftmost and rightmost columns, and top and bottom rows, drawn in after
+// `frames`, leaving out the title rows.
+fn extent(mesh: &str, frames: &str, extra: &[&str]) -> (usize, usize, usize, usize) {
+    let mut args = vec!["--mesh", mesh, "--reduced-motion", "--frames", frames];
+    args.extend_from_slice(extra);
+    let screen = check(&PROFILE, &wait(&PROFILE, spawn(&PROFILE, &args)));
+    let (sx, sy) = PROFILE.size;
+    let lit: Vec<(usize, usize)> = (1..sy - 1)
+        .flat_map(|y| (0..sx).map(move |x| (x, y)))
+        .filter(|&(x, y)| screen[y][x] != ' ')
+        .collect();
+    assert!(!lit.is_empty());
+    (
+        lit.iter().map(|&(x, _)| x).min().unwrap(),
+        lit.iter().map(|&(x, _)| x).max().unwrap(),
+        lit.iter().map(|&(_, y)| y).min().unwrap(),
+        lit.iter().map(|&(_, y)| y).max().unwrap(),
+    )
+}
+
+// A long bar, big enough to run off the sides as it turns, stays within the
+// middle of the screen with --auto-frame however it's turned.
+#[test]
+fn auto_frame_keeps_a_large_mesh_on_screen() {
+    let mut obj = String::new();
+    for x in [-20, 20] {
+        for y in [-2, 2] {
+            for z in [-2, 2] {
+                obj.push_str(&format!("v {} {} {}\n", x, y, z));
+            }
+        }
+    }
+    for [a, b, c, d] in [
+        [1, 2, 4, 3],
+        [5, 7, 8, 6],
+        [1, 5, 6, 2],
+        [3, 4, 8, 7],
+        [1, 3, 7, 5],
+        [2, 6, 8, 4],
+    ] {
+        obj.push_str(&format!("f {} {} {}\nf {} {} {}\n", a, b, c, a, c, d));
+    }
+    let path = std::env::temp_dir().join(format!("donut-{}-bar.obj", std::process::id()));
+    std::fs::write(&path, obj).unwrap();
+    let mesh = path.to_str().unwrap();
+
+    // Without, it reaches well past the middle 80%
+    let (left, right, _, _) = extent(mesh, "30", &[]);
+    assert!(right - left > 48, "{} {}", left, right);
+    for frames in ["1", "30", "60", "90"] {
+        let (left, right, top, bottom) = extent(mesh, frames, &["--auto-frame", "0.8"]);
+        // Within the middle 80%, give or take a cell for the glyphs
+        assert!(left >= 5 && right <= 54, "{}: {} {}", frames, left, right);
+        assert!(top >= 1 && bottom <= 18, "{}: {} {}", frames, top, bottom);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,56 @@
+// --captions: SubRip subtitles over the donut.
+mod common;
+
+use common::{check, spawn, wait, ColorDepth, Profile};
+
+// --captions cues come and go with the frames, just above the bottom row.
+#[test]
+fn captions_show_in_time() {
+    let profile = Profile {
+        color: ColorDepth::Mono,
+        unicode: false,
+        size: (40, 12),
+    };
+    let path = std::env::temp_dir().join(format!("donut-{}.srt", std::process::id()));
+    let cues =
+        "1\n00:00:00,000 --> 00:00:00,100\nFirst\n\n2\n00:00:00,100 --> 00:00:05,000\nThe second\n";
+    std::fs::write(&path, cues).unwrap();
+    let captions = path.to_str().unwrap();
+    let args = ["--fps", "10", "--frames", "2", "--captions", captions];
+    let screen = check(&profile, &wait(&profile, spawn(&profile, &args)));
+    std::fs::remove_file(&path).unwrap();
+    let row = |y: usize| screen[y].iter().collect::<String>();
+    assert!(row(10).contains(" The second "));
+    assert!(!(0..12).any(|y| row(y).contains("First")));
+}
+
+// Wide glyphs take two columns: lines of them are centered by how wide they
+// show, and cut to fit the screen rather than running off it.
+#[test]
+fn captions_measure_wide_glyphs() {
+    let profile = Profile {
+        color: ColorDepth::Mono,
+        unicode: true,
+        size: (40, 12),
+    };
+    let path = std::env::temp_dir().join(format!("donut-{}-wide.srt", std::process::id()));
+    let long = "字".repeat(30);
+    let cues = format!("1\n00:00:00,000 --> 00:00:05,000\n字幕です\n{}\n", long);
+    std::fs::write(&path, cues).unwrap();
+    let captions = path.to_str().unwrap();
+    let args = ["--captions", captions];
+    let screen = check(&profile, &wait(&profile, spawn(&profile, &args)));
+    std::fs::remove_file(&path).unwrap();
+    // " 字幕です " is ten columns, so it starts at column 15
+    let glyphs = |y: usize, from: usize, to: usize| {
+        (from..to)
+            .step_by(2)
+            .map(|x| screen[y][x])
+            .collect::<String>()
+    };
+    assert_eq!(glyphs(9, 16, 24), "字幕です");
+    assert_eq!(screen[9][15], ' ');
+    // " 字字…" cut to 19 glyphs after the space, the 39 columns that fit
+    assert_eq!(screen[10][0], ' ');
+    assert_eq!(glyphs(10, 1, 39), "字".repeat(19));
+}
@@ -0,0 +1,253 @@
+// Shared by the integration tests: running the binary under a simulated
+// terminal, checking what it draws, and waiting for its sockets.
+#![allow(dead_code)]
+#[cfg(unix)]
+use std::fs::File;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum ColorDepth {
+    Mono,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+#[derive(Debug)]
+pub struct Profile {
+    pub color: ColorDepth,
+    pub unicode: bool,
+    pub size: (usize, usize),
+}
+
+impl Profile {
+    pub fn env(&self) -> Vec<(&'static str, &'static str)> {
+        let mut env = match self.color {
+            ColorDepth::Mono => vec![("TERM", "vt100")],
+            ColorDepth::Ansi16 => vec![("TERM", "xterm")],
+            ColorDepth::Ansi256 => vec![("TERM", "xterm-256color")],
+            ColorDepth::TrueColor => vec![("TERM", "xterm-256color"), ("COLORTERM", "truecolor")],
+        };
+        env.push(("LANG", if self.unicode { "en_US.UTF-8" } else { "C" }));
+        env
+    }
+}
+
+// The binary, with none of the environment the tests run in.
+pub fn donut() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_forbidden-donut"));
+    command.env_clear();
+    command
+}
+
+// Runs the binary headless in `profile`'s terminal for two frames.
+pub fn spawn(profile: &Profile, extra: &[&str]) -> Child {
+    let size = format!("{}x{}", profile.size.0, profile.size.1);
+    donut()
+        .envs(profile.env())
+        .args(["--headless", "--size", &size, "--frames", "2"])
+        .args(extra)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+// Its output, once it's exited happily.
+pub fn wait(profile: &Profile, child: Child) -> Vec<u8> {
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{:?}: {}",
+        profile,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+fn is_wide(c: char) -> bool {
+    let c = c as u32;
+    (0x1100..=0x115f).contains(&c)
+        || (0x2e80..=0xa4cf).contains(&c)
+        || (0xac00..=0xd7a3).contains(&c)
+        || (0xf900..=0xfaff).contains(&c)
+        || (0xfe30..=0xfe4f).contains(&c)
+        || (0xff00..=0xff60).contains(&c)
+        || (0xffe0..=0xffe6).contains(&c)
+        || (0x1f300..=0x1f64f).contains(&c)
+        || (0x1f900..=0x1f9ff).contains(&c)
+        || (0x20000..=0x3fffd).contains(&c)
+}
+
+fn check_sgr(profile: &Profile, params: &str) {
+    let codes: Vec<u32> = params
+        .split(';')
+        .map(|p| if p.is_empty() { 0 } else { p.parse().unwrap() })
+        .collect();
+    let mut i = 0;
+    while i < codes.len() {
+        let needs = match codes[i] {
+            0 | 1 | 2 | 22 | 7 | 27 => ColorDepth::Mono,
+            30..=37 | 39 | 40..=47 | 49 | 90..=97 | 100..=107 => ColorDepth::Ansi16,
+            38 | 48 if codes.get(i + 1) == Some(&5) => {
+                i += 2;
+                ColorDepth::Ansi256
+            }
+            38 | 48 if codes.get(i + 1) == Some(&2) => {
+                i += 4;
+                ColorDepth::TrueColor
+            }
+            c => panic!("{:?}: unknown SGR code {}", profile, c),
+        };
+        assert!(
+            needs <= profile.color,
+            "{:?}: SGR {} needs {:?}",
+            profile,
+            params,
+            needs
+        );
+        i += 1;
+    }
+}
+
+// Plays the output onto a simulated screen, checking escape sequences, glyphs
+// and that nothing is drawn outside the screen. Returns the final screen.
+pub fn check(profile: &Profile, out: &[u8]) -> Vec<Vec<char>> {
+    let text = match std::str::from_utf8(out) {
+        Ok(text) => text,
+        Err(e) => panic!("{:?}: invalid UTF-8: {}", profile, e),
+    };
+    let (sx, sy) = profile.size;
+    let mut screen = vec![vec![' '; sx]; sy];
+    let (mut x, mut y) = (0, 0);
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            x = 0;
+            y += 1;
+            continue;
+        }
+        if c != '\x1b' {
+            assert!(!c.is_control(), "{:?}: control char {:?}", profile, c);
+            assert!(
+                profile.unicode || c.is_ascii(),
+                "{:?}: non-ASCII glyph {:?}",
+                profile,
+                c
+            );
+            let width = if is_wide(c) { 2 } else { 1 };
+            assert!(
+                x + width <= sx && y < sy,
+                "{:?}: {:?} drawn off screen at {},{}",
+                profile,
+                c,
+                x,
+                y
+            );
+            screen[y][x] = c;
+            x += width;
+            continue;
+        }
+        assert_eq!(chars.next(), Some('['), "{:?}: non-CSI escape", profile);
+        let mut params = String::new();
+        let fin = loop {
+            match chars.next() {
+                Some(c) if ('\x40'..='\x7e').contains(&c) => break c,
+                Some(c) => params.push(c),
+                None => panic!("{:?}: truncated escape", profile),
+            }
+        };
+        match fin {
+            'm' => check_sgr(profile, &params),
+            'H' => {
+                let pos: Vec<usize> = params.split(';').map(|p| p.parse().unwrap_or(1)).collect();
+                y = pos[0] - 1;
+                x = pos.get(1).map_or(0, |x| x - 1);
+                assert!(
+                    x < sx && y < sy,
+                    "{:?}: cursor {} out of bounds",
+                    profile,
+                    params
+                );
+            }
+            'J' | 'K' => {}
+            // The cursor, and mouse reporting for --mouse
+            'l' | 'h' => assert!(
+                ["?25", "?1000", "?1002", "?1003", "?1006", "?1015"].contains(&params.as_str()),
+                "{:?}: unexpected mode {}",
+                profile,
+                params
+            ),
+            _ => panic!("{:?}: unsupported escape {}{}", profile, params, fin),
+        }
+    }
+    screen
+}
+
+// Keeps trying to connect for five seconds, while the binary starts up.
+pub fn connect<T>(mut attempt: impl FnMut() -> std::io::Result<T>) -> T {
+    (0..100)
+        .find_map(|_| {
+            std::thread::sleep(Duration::from_millis(50));
+            attempt().ok()
+        })
+        .unwrap()
+}
+
+// A port nothing's listening on, for now.
+pub fn free_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+// Runs the binary interactively, in a pseudo-terminal the size of
+// `profile`'s, with `typing` at its keyboard until it exits. Returns
+// everything it wrote to the terminal.
+#[cfg(unix)]
+pub fn in_pty(profile: &Profile, args: &[&str], typing: impl FnOnce(&mut File)) -> Vec<u8> {
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    let (sx, sy) = profile.size;
+    let size = libc::winsize {
+        ws_row: sy as u16,
+        ws_col: sx as u16,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let (mut master, mut slave) = (0, 0);
+    let opened = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &size as *const _ as *mut _,
+        )
+    };
+    assert_eq!(opened, 0, "openpty: {}", std::io::Error::last_os_error());
+    let (mut master, slave) = unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) };
+    let mut child = donut()
+        .envs(profile.env())
+        .args(args)
+        .stdin(slave.try_clone().unwrap())
+        .stdout(slave.try_clone().unwrap())
+        .stderr(slave)
+        .spawn()
+        .unwrap();
+    // Read as it goes, or the child blocks once the terminal's buffer fills;
+    // reads fail once it's gone.
+    let mut output = master.try_clone().unwrap();
+    let reader = std::thread::spawn(move || {
+        let (mut out, mut buf) = (Vec::new(), [0; 4096]);
+        while let Ok(n @ 1..) = output.read(&mut buf) {
+            out.extend_from_slice(&buf[..n]);
+        }
+        out
+    });
+    typing(&mut master);
+    assert!(child.wait().unwrap().success(), "{:?} {:?}", profile, args);
+    drop(master);
+    reader.join().unwrap()
+}
@@ -0,0 +1,52 @@
+// --config files.
+mod common;
+
+use common::donut;
+
+// The [scene] table of a --config file (as the console's :save writes) sets
+// up the scene, and bad settings in it are refused.
+#[test]
+fn scene_settings_load_from_config() {
+    let path = std::env::temp_dir().join(format!("donut-{}.toml", std::process::id()));
+    let hash = |scene: &str| {
+        std::fs::write(&path, format!("fps = 20\n\n[scene]\n{}\n", scene)).unwrap();
+        donut()
+            .args(["--config", path.to_str().unwrap(), "--dump-hash"])
+            .output()
+            .unwrap()
+    };
+    let plain = hash("");
+    let tweaked = hash("r1 = 1.3\nlight = [1, 2, -1]");
+    let broken = hash("light = [0, 0]");
+    std::fs::remove_file(&path).unwrap();
+    assert!(plain.status.success() && tweaked.status.success());
+    assert_ne!(plain.stdout, tweaked.stdout);
+    assert!(!broken.status.success());
+}
+
+// What --save-config writes loads again, even with brackets, quotes and
+// backslashes in strings; and float flags load as they do on the command
+// line.
+#[test]
+fn saved_config_loads_again() {
+    let dir = std::env::temp_dir().join(format!("donut-config-{}", std::process::id()));
+    let ramp = " .[\"\\\u{2591}#";
+    let run = |args: &[&str]| {
+        let output = donut()
+            .env("XDG_CONFIG_HOME", &dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        output.stdout
+    };
+    run(&["--ramp", ramp, "--save-config"]);
+    let loaded = run(&["--dump-hash"]);
+    let path = dir.join("forbidden-donut").join("config.toml");
+    std::fs::write(&path, "bloom = true\nbloom-threshold = 0.5\n").unwrap();
+    let floats = run(&["--dump-hash"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(loaded, run(&["--dump-hash", "--ramp", ramp]));
+    let flags = ["--dump-hash", "--bloom", "--bloom-threshold", "0.5"];
+    assert_eq!(floats, run(&flags));
+}
@@ -0,0 +1,49 @@
+// The : command line, typed at the keyboard.
+#![cfg(unix)]
+mod common;
+
+use common::{check, in_pty, ColorDepth, Profile};
+use std::io::Write;
+use std::time::Duration;
+
+const PROFILE: Profile = Profile {
+    color: ColorDepth::Ansi256,
+    unicode: false,
+    size: (60, 24),
+};
+
+// The final screen with `keys` typed early on.
+fn run(keys: &[u8]) -> Vec<String> {
+    let args = ["--reduced-motion", "--fps", "20", "--frames", "30"];
+    let out = in_pty(&PROFILE, &args, |keyboard| {
+        std::thread::sleep(Duration::from_millis(300));
+        keyboard.write_all(keys).unwrap();
+    });
+    let screen = check(&PROFILE, &out);
+    screen.iter().map(|row| row.iter().collect()).collect()
+}
+
+// Rows with a gap between drawn cells: seeing through the hole.
+fn holes(screen: &[String]) -> usize {
+    screen[2..screen.len() - 1]
+        .iter()
+        .filter(|row| {
+            let lit: Vec<_> = row.char_indices().filter(|&(_, c)| c != ' ').collect();
+            lit.windows(2).any(|w| w[1].0 > w[0].0 + 2)
+        })
+        .count()
+}
+
+#[test]
+fn shape_command_changes_the_shape() {
+    let torus = run(b"");
+    assert!(holes(&torus) >= 2, "{:#?}", torus);
+    let sphere = run(b":shape sphere\r");
+    assert_eq!(holes(&sphere), 0, "{:#?}", sphere);
+    assert!(sphere[23].starts_with("shape sphere "), "{:#?}", sphere);
+
+    // Mistakes say what was wrong, and change nothing
+    let cube = run(b":shape cube\r");
+    assert!(holes(&cube) >= 2, "{:#?}", cube);
+    assert!(cube[23].starts_with("unknown shape 'cube'"), "{:#?}", cube);
+}
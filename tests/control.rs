@@ -0,0 +1,101 @@
+// --control: commands over a Unix socket.
+#![cfg(unix)]
+mod common;
+
+use common::{connect, donut};
+use std::process::Stdio;
+
+// --control takes JSON commands on a socket, answering each, until told to
+// quit.
+#[test]
+fn control_socket_drives_the_donut() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let dir = std::env::temp_dir();
+    let socket = dir.join(format!("donut-{}.sock", std::process::id()));
+    let shot = dir.join(format!("donut-{}.txt", std::process::id()));
+    let mut child = donut()
+        .args(["--headless", "--size", "40x16", "--frames", "100000000"])
+        .arg("--control")
+        .arg(&socket)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let stream = connect(|| UnixStream::connect(&socket));
+    let mut replies = BufReader::new(stream.try_clone().unwrap()).lines();
+    let mut send = |command: String| {
+        writeln!(&stream, "{}", command).unwrap();
+        replies.next().unwrap().unwrap()
+    };
+    let reply = send(r#"{"cmd": "set", "name": "speed", "value": 2}"#.to_string());
+    assert!(reply.contains(r#""ok": true"#), "{}", reply);
+    let reply = send(r#"{"cmd": "shape", "shape": "cube"}"#.to_string());
+    assert!(reply.contains(r#""ok": false"#), "{}", reply);
+    let path = shot.to_str().unwrap();
+    send(format!(r#"{{"cmd": "screenshot", "path": {:?}}}"#, path));
+    send(r#"{"cmd": "quit"}"#.to_string());
+    assert!(child.wait().unwrap().success());
+
+    let text = std::fs::read_to_string(&shot).unwrap();
+    std::fs::remove_file(&shot).unwrap();
+    assert_eq!(text.lines().count(), 16);
+    assert!(text.chars().any(|c| "-~+*=;%#$@".contains(c)));
+    assert!(!socket.exists());
+}
+
+// Replies are JSON whatever's in them, and a client sending a line too long
+// to be a command, or not reading its replies, is dropped without holding up
+// anyone else.
+#[test]
+fn control_socket_escapes_replies_and_drops_runaway_clients() {
+    use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let socket = std::env::temp_dir().join(format!("donut-{}-limits.sock", std::process::id()));
+    let mut child = donut()
+        .args(["--headless", "--size", "40x16", "--frames", "100000000"])
+        .arg("--control")
+        .arg(&socket)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let stream = connect(|| UnixStream::connect(&socket));
+    let mut replies = BufReader::new(stream.try_clone().unwrap()).lines();
+    let mut send = |command: &str| {
+        writeln!(&stream, "{}", command).unwrap();
+        replies.next().unwrap().unwrap()
+    };
+    let reply = send(r#"{"cmd": "shape", "shape": "a\"b\\c\u0001\t"}"#);
+    assert_eq!(
+        reply,
+        r#"{"ok": false, "error": "unknown shape 'a\"b\\c\u0001\t'"}"#
+    );
+
+    // A hundred kilobytes and no newline: closed, or reset with some of it
+    // still unread
+    let timeout = Some(std::time::Duration::from_secs(10));
+    let dropped = |result: std::io::Result<()>| match result {
+        Err(e) => !matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut),
+        Ok(()) => true,
+    };
+    let mut long = UnixStream::connect(&socket).unwrap();
+    long.set_read_timeout(timeout).unwrap();
+    long.write_all(&[b'x'; 100 * 1024]).unwrap();
+    let read = long.read(&mut [0; 16]).and_then(|n| match n {
+        0 => Ok(()),
+        _ => Err(ErrorKind::InvalidData.into()),
+    });
+    assert!(dropped(read));
+
+    // Thousands of commands, and no reading the replies
+    let deaf = UnixStream::connect(&socket).unwrap();
+    deaf.set_write_timeout(timeout).unwrap();
+    let commands = r#"{"cmd": "set", "name": "speed", "value": 1}"#.to_string() + "\n";
+    let sent = (0..20000).try_for_each(|_| (&deaf).write_all(commands.as_bytes()));
+    assert!(sent.is_err() && dropped(sent));
+
+    let reply = send(r#"{"cmd": "quit"}"#);
+    assert_eq!(reply, r#"{"ok": true}"#);
+    assert!(child.wait().unwrap().success());
+}
@@ -0,0 +1,63 @@
+// --daylight: the light going round the scene over a day.
+mod common;
+
+use common::{check, spawn, wait, ColorDepth, Profile};
+
+const PROFILE: Profile = Profile {
+    color: ColorDepth::TrueColor,
+    unicode: false,
+    size: (60, 20),
+};
+
+// The tint the last frame was drawn in, and how many of its cells are drawn
+// in the brighter half of the ramp.
+fn after(frames: &str) -> ((u32, u32, u32), usize) {
+    // A day of 40 frames
+    let args = [
+        "--color",
+        "truecolor",
+        "--daylight",
+        "2",
+        "--fps",
+        "20",
+        "--frames",
+        frames,
+    ];
+    let out = wait(&PROFILE, spawn(&PROFILE, &args));
+    let text = String::from_utf8_lossy(&out);
+    let (_, last) = text.rsplit_once("\x1b[38;2;").unwrap();
+    let rgb: Vec<u32> = last
+        .split('m')
+        .next()
+        .unwrap()
+        .split(';')
+        .map(|c| c.parse().unwrap())
+        .collect();
+    let screen = check(&PROFILE, &out);
+    let bright = screen
+        .iter()
+        .skip(2)
+        .take(PROFILE.size.1 - 3)
+        .flatten()
+        .filter(|c| "=;%#$@".contains(**c))
+        .count();
+    ((rgb[0], rgb[1], rgb[2]), bright)
+}
+
+// Mid morning the light is white, at dusk it's warm and at midnight it's
+// cool, and dim.
+#[test]
+fn daylight_turns_warm_at_dusk_and_cool_and_dim_at_night() {
+    let (morning, morning_bright) = after("1");
+    let (dusk, _) = after("18");
+    let (midnight, midnight_bright) = after("28");
+    assert!(morning.0 > 200 && morning.2 > 200, "{:?}", morning);
+    assert!(dusk.0 > dusk.2 + 100, "{:?}", dusk);
+    assert!(midnight.2 > midnight.0 + 100, "{:?}", midnight);
+    assert!(
+        midnight_bright * 2 < morning_bright,
+        "{} {}",
+        midnight_bright,
+        morning_bright
+    );
+}
@@ -0,0 +1,243 @@
+// Runs the headless renderer under simulated terminal capability profiles and
+// checks that every byte it emits is something that terminal could display.
+mod common;
+
+use common::{check, donut, spawn, wait, ColorDepth, Profile};
+use std::process::Stdio;
+
+fn profiles() -> Vec<Profile> {
+    let mut out = Vec::new();
+    for &color in &[
+        ColorDepth::Mono,
+        ColorDepth::Ansi16,
+        ColorDepth::Ansi256,
+        ColorDepth::TrueColor,
+    ] {
+        for &unicode in &[false, true] {
+            for &size in &[(80, 24), (20, 10), (3, 2), (200, 60)] {
+                out.push(Profile {
+                    color,
+                    unicode,
+                    size,
+                });
+            }
+        }
+    }
+    out
+}
+
+// Renders every profile in parallel, since each run is a full debug-build frame.
+fn render_all(extra: &[&str]) -> Vec<(Profile, Vec<u8>)> {
+    let children: Vec<_> = profiles()
+        .into_iter()
+        .map(|p| {
+            let child = spawn(&p, extra);
+            (p, child)
+        })
+        .collect();
+    children
+        .into_iter()
+        .map(|(p, child)| {
+            let out = wait(&p, child);
+            (p, out)
+        })
+        .collect()
+}
+
+// Flags that change what kinds of output the renderer produces.
+const MODES: &[&[&str]] = &[
+    &[],
+    &["--sysmon", "--log-pane"],
+    &[
+        "--ramp",
+        "blocks",
+        "--highlights",
+        "--palette",
+        "synthwave",
+        "--auto-exposure",
+    ],
+    &["--preset", "ultra", "--sysmon"],
+    &["--swarm", "3", "--daylight", "0.1"],
+    &[
+        "--metaballs",
+        "4",
+        "--palette",
+        "rainbow",
+        "--auto-frame",
+        "0.8",
+    ],
+    &["--morph", "knot,sphere", "--two-sided"],
+    &["--ramp", "kanji", "--palette", "amber"],
+    &["--crt", "--bloom", "--focus", "3", "--palette", "rainbow"],
+];
+
+#[test]
+fn output_valid_for_every_profile() {
+    for mode in MODES {
+        for (profile, out) in render_all(mode) {
+            check(&profile, &out);
+        }
+    }
+}
+
+// Legacy Windows consoles get plain ASCII whatever else the environment says.
+#[test]
+fn legacy_console_output_is_plain() {
+    let profile = Profile {
+        color: ColorDepth::Mono,
+        unicode: false,
+        size: (80, 24),
+    };
+    let child = donut()
+        .envs([
+            ("TERM", "xterm-256color"),
+            ("COLORTERM", "truecolor"),
+            ("LANG", "en_US.UTF-8"),
+            ("DONUT_LEGACY_CONSOLE", "1"),
+        ])
+        .args(["--headless", "--sysmon", "--ramp", "blocks"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    check(&profile, &wait(&profile, child));
+}
+
+// NO_COLOR turns color off, however colorful the terminal.
+#[test]
+fn no_color_is_respected() {
+    let child = donut()
+        .envs([
+            ("TERM", "xterm-256color"),
+            ("COLORTERM", "truecolor"),
+            ("NO_COLOR", "1"),
+        ])
+        .args(["--headless", "--palette", "rainbow"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    let out = String::from_utf8_lossy(&out.stdout);
+    assert!(!out.contains("\x1b[38"), "colored output: {:?}", out);
+}
+
+#[test]
+fn output_draws_something() {
+    let profile = Profile {
+        color: ColorDepth::Mono,
+        unicode: false,
+        size: (80, 24),
+    };
+    let screen = check(&profile, &wait(&profile, spawn(&profile, &[])));
+    assert!(screen.iter().flatten().any(|&c| "-~+*=;%#$@".contains(c)));
+}
+
+// --dump-hash renders the same frames every time, and the donut does move.
+#[test]
+fn dump_hash_is_repeatable() {
+    let profile = Profile {
+        color: ColorDepth::Mono,
+        unicode: false,
+        size: (40, 20),
+    };
+    let children: Vec<_> = (0..2)
+        .map(|_| spawn(&profile, &["--dump-hash", "--frames", "3"]))
+        .collect();
+    let outs: Vec<_> = children
+        .into_iter()
+        .map(|child| String::from_utf8(wait(&profile, child)).unwrap())
+        .collect();
+    assert_eq!(outs[0], outs[1]);
+    let hashes: Vec<_> = outs[0].lines().collect();
+    assert_eq!(hashes.len(), 3);
+    assert!(hashes[0] != hashes[1] && hashes[1] != hashes[2]);
+}
+
+// --serial writes plain ASCII frames of exactly the given size, each after a
+// form feed, with rows split by the chosen newline.
+#[cfg(unix)]
+#[test]
+fn serial_frames_are_plain_text() {
+    let profile = Profile {
+        color: ColorDepth::TrueColor,
+        unicode: true,
+        size: (20, 4),
+    };
+    let path = std::env::temp_dir().join(format!("donut-serial-{}", std::process::id()));
+    // Ports are there already; the donut doesn't make them
+    std::fs::write(&path, "").unwrap();
+    let port = path.to_str().unwrap();
+    wait(
+        &profile,
+        spawn(&profile, &["--serial", port, "--newline", "crlf"]),
+    );
+    let out = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let frames: Vec<_> = out.split(|&b| b == 0x0c).skip(1).collect();
+    assert_eq!(frames.len(), 2);
+    for frame in frames {
+        let rows: Vec<_> = std::str::from_utf8(frame).unwrap().split("\r\n").collect();
+        assert_eq!(rows.len(), 4);
+        for row in rows {
+            assert_eq!(row.len(), 20);
+            assert!(row.bytes().all(|b| (b' '..=b'~').contains(&b)), "{:?}", row);
+        }
+    }
+}
+
+// --viewport draws only inside its rectangle, without clearing the screen.
+#[test]
+fn viewport_leaves_the_rest_alone() {
+    let profile = Profile {
+        color: ColorDepth::Ansi256,
+        unicode: true,
+        size: (40, 20),
+    };
+    let out = wait(&profile, spawn(&profile, &["--viewport", "5,3,20,10"]));
+    check(&profile, &out);
+    let text = String::from_utf8(out).unwrap();
+    assert!(!text.contains("\x1b[2J"));
+    let moves: Vec<_> = text
+        .split("\x1b[")
+        .skip(1)
+        .filter_map(|s| s.split_once('H'))
+        .filter(|(params, _)| params.bytes().all(|b| b.is_ascii_digit() || b == b';'))
+        .filter_map(|(params, _)| params.split_once(';'))
+        .map(|(y, x)| (y.parse::<usize>().unwrap(), x.parse::<usize>().unwrap()))
+        .collect();
+    // The whole viewport, row by row, then only what changed inside it
+    let rows: Vec<_> = (4..14).map(|y| (y, 6)).collect();
+    assert_eq!(moves[..10], rows[..]);
+    assert!(moves[10..]
+        .iter()
+        .all(|&(y, x)| (4..14).contains(&y) && (6..26).contains(&x)));
+}
+
+// After the first frame only cells that changed are sent again: not the
+// title, and less than the whole screen.
+#[test]
+fn later_frames_send_only_changes() {
+    let profile = Profile {
+        color: ColorDepth::Mono,
+        unicode: false,
+        size: (80, 24),
+    };
+    let run = |frames: &str| {
+        let child = donut()
+            .envs(profile.env())
+            .args(["--headless", "--size", "80x24", "--reduced-motion"])
+            .args(["--frames", frames])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        wait(&profile, child)
+    };
+    let (one, two) = (run("1"), run("2"));
+    check(&profile, &two);
+    assert!(two.len() - one.len() < one.len());
+    let text = String::from_utf8(two).unwrap();
+    assert_eq!(text.matches("F O R B I D D E N D O N U T").count(), 2);
+}
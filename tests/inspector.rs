@@ -0,0 +1,60 @@
+// The scene inspector, on tab: listing the objects, hiding and tweaking them.
+#![cfg(unix)]
+mod common;
+
+use common::{check, in_pty, ColorDepth, Profile};
+use std::io::Write;
+use std::time::Duration;
+
+const PROFILE: Profile = Profile {
+    color: ColorDepth::Ansi256,
+    unicode: false,
+    size: (100, 30),
+};
+
+// The final screen of a swarm of two, with `keys` typed early on.
+fn run(keys: &[u8]) -> Vec<String> {
+    let args = [
+        "--swarm",
+        "2",
+        "--reduced-motion",
+        "--fps",
+        "20",
+        "--frames",
+        "30",
+    ];
+    let out = in_pty(&PROFILE, &args, |keyboard| {
+        std::thread::sleep(Duration::from_millis(300));
+        keyboard.write_all(keys).unwrap();
+    });
+    let screen = check(&PROFILE, &out);
+    screen.iter().map(|row| row.iter().collect()).collect()
+}
+
+fn lit(screen: &[String]) -> usize {
+    screen
+        .iter()
+        .flat_map(|row| row.chars())
+        .filter(|&c| c != ' ')
+        .count()
+}
+
+#[test]
+fn inspector_lists_hides_and_tweaks() {
+    // Tab opens it, space hides the first object
+    let screen = run(b"\t ");
+    assert!(screen[2].starts_with("2 objects | "), "{:#?}", screen);
+    assert!(screen[3].starts_with("> [ ] torus 1  at "), "{:#?}", screen);
+    assert!(screen[4].starts_with("  [x] torus 2  at "), "{:#?}", screen);
+
+    // ... and closing it again leaves the first torus hidden
+    let both = lit(&run(b"\t\t"));
+    let one = lit(&run(b"\t \t"));
+    assert!(one > 0 && 3 * one < 2 * both, "{} {}", one, both);
+
+    // Down to the second, right to its spin about x, and turned up three
+    // steps from -0.07
+    let screen = run(b"\t\x1b[B\x1b[C\x1b[C\x1b[C+++");
+    assert!(screen[4].starts_with("> [x] torus 2  at "), "{:#?}", screen);
+    assert!(screen[5].contains("[spin x -0.04]"), "{:#?}", screen);
+}
@@ -0,0 +1,46 @@
+// --journal and --replay: sessions recorded and played again.
+#![cfg(unix)]
+mod common;
+
+use common::{connect, donut};
+use std::process::Stdio;
+
+// A session steered over the control socket comes out the same replayed from
+// its journal, dithering and all.
+#[test]
+fn journal_replays_the_session() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let dir = std::env::temp_dir();
+    let socket = dir.join(format!("donut-{}-journal.sock", std::process::id()));
+    let journal = dir.join(format!("donut-{}.journal", std::process::id()));
+    let child = donut()
+        .args(["--headless", "--size", "20x8", "--frames", "100000000"])
+        .args(["--palette", "amber", "--control"])
+        .arg(&socket)
+        .arg("--journal")
+        .arg(&journal)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stream = connect(|| UnixStream::connect(&socket));
+    let mut replies = BufReader::new(stream.try_clone().unwrap()).lines();
+    for command in [
+        r#"{"cmd": "set", "name": "speed", "value": 3}"#,
+        r#"{"cmd": "light", "dir": [1, 0, -1]}"#,
+        r#"{"cmd": "quit"}"#,
+    ] {
+        writeln!(&stream, "{}", command).unwrap();
+        replies.next().unwrap().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    let session = child.wait_with_output().unwrap();
+    assert!(session.status.success());
+
+    let replay = donut().arg("--replay").arg(&journal).output().unwrap();
+    std::fs::remove_file(&journal).unwrap();
+    assert!(replay.status.success());
+    assert!(!session.stdout.is_empty());
+    assert!(session.stdout == replay.stdout);
+}
@@ -0,0 +1,74 @@
+// --mesh: models loaded from OBJ, STL and PLY files.
+mod common;
+
+use common::{check, spawn, wait, ColorDepth, Profile};
+
+// The same tetrahedron as OBJ, STL (text and binary) and PLY all load and draw.
+#[test]
+fn meshes_load_in_every_format() {
+    let profile = Profile {
+        color: ColorDepth::Mono,
+        unicode: false,
+        size: (40, 16),
+    };
+    let corners = [
+        [1.0f32, 1.0, 1.0],
+        [-1.0, -1.0, 1.0],
+        [-1.0, 1.0, -1.0],
+        [1.0, -1.0, -1.0],
+    ];
+    let faces = [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]];
+    let mut obj = String::new();
+    let mut stl = String::from("solid tetra\n");
+    let mut binary = vec![0u8; 80];
+    binary.extend_from_slice(&(faces.len() as u32).to_le_bytes());
+    let mut ply = String::from(
+        "ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\nelement face 4\nproperty list uchar int vertex_indices\nend_header\n",
+    );
+    for [x, y, z] in corners {
+        obj.push_str(&format!("v {} {} {}\n", x, y, z));
+        ply.push_str(&format!("{} {} {}\n", x, y, z));
+    }
+    for face in faces {
+        obj.push_str(&format!(
+            "f {} {} {}\n",
+            face[0] + 1,
+            face[1] + 1,
+            face[2] + 1
+        ));
+        ply.push_str(&format!("3 {} {} {}\n", face[0], face[1], face[2]));
+        stl.push_str("facet normal 0 0 0\nouter loop\n");
+        binary.extend_from_slice(&[0; 12]);
+        for &i in &face {
+            let [x, y, z] = corners[i];
+            stl.push_str(&format!("vertex {} {} {}\n", x, y, z));
+            for c in [x, y, z] {
+                binary.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        stl.push_str("endloop\nendfacet\n");
+        binary.extend_from_slice(&[0; 2]);
+    }
+    stl.push_str("endsolid tetra\n");
+    let files = [
+        ("obj", obj.into_bytes()),
+        ("stl", stl.into_bytes()),
+        ("binary.stl", binary),
+        ("ply", ply.into_bytes()),
+    ];
+    for (ext, bytes) in files {
+        let path = std::env::temp_dir().join(format!("donut-{}.{}", std::process::id(), ext));
+        std::fs::write(&path, bytes).unwrap();
+        let out = wait(
+            &profile,
+            spawn(&profile, &["--mesh", path.to_str().unwrap()]),
+        );
+        std::fs::remove_file(&path).unwrap();
+        let screen = check(&profile, &out);
+        assert!(
+            screen.iter().flatten().any(|&c| "-~+*=;%#$@".contains(c)),
+            "{}: nothing drawn",
+            ext
+        );
+    }
+}
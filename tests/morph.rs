@@ -0,0 +1,47 @@
+// --morph: the donut turning into other shapes and back.
+mod common;
+
+use common::{check, spawn, wait, ColorDepth, Profile};
+
+const PROFILE: Profile = Profile {
+    color: ColorDepth::Mono,
+    unicode: false,
+    size: (60, 24),
+};
+
+fn screen(args: &[&str]) -> Vec<Vec<char>> {
+    check(&PROFILE, &wait(&PROFILE, spawn(&PROFILE, args)))
+}
+
+// Rows, below the title, with a gap between drawn cells: seeing through the
+// hole.
+fn holes(screen: &[Vec<char>]) -> usize {
+    screen[2..PROFILE.size.1 - 1]
+        .iter()
+        .filter(|row| {
+            let lit: Vec<_> = (0..row.len()).filter(|&x| row[x] != ' ').collect();
+            lit.windows(2).any(|w| w[1] > w[0] + 2)
+        })
+        .count()
+}
+
+// At the default 20fps each shape holds for three seconds then blends into
+// the next over two: a torus, then a sphere with no hole through it, then
+// the torus again.
+#[test]
+fn morphs_from_torus_to_sphere_and_back() {
+    let at = |frames: &str| {
+        let screen = screen(&["--morph", "torus,sphere", "--frames", frames]);
+        let lit = screen.iter().flatten().filter(|&&c| c != ' ').count();
+        (holes(&screen), lit)
+    };
+    let (torus, sphere, again) = (at("1"), at("110"), at("210"));
+    assert!(torus.0 >= 5, "{:?}", torus);
+    assert!(
+        sphere.0 <= 2 && sphere.1 > torus.1 + 50,
+        "{:?} {:?}",
+        torus,
+        sphere
+    );
+    assert!(again.0 >= 5, "{:?}", again);
+}
@@ -0,0 +1,49 @@
+// --mouse: dragging turns the donut and scrolling zooms.
+#![cfg(unix)]
+mod common;
+
+use common::{check, in_pty, ColorDepth, Profile};
+use std::io::Write;
+use std::time::Duration;
+
+const PROFILE: Profile = Profile {
+    color: ColorDepth::Ansi256,
+    unicode: false,
+    size: (60, 20),
+};
+
+// The final screen after two seconds with the mouse doing `mouse` (SGR mouse
+// reports) early on. Steady dithering makes the same events draw the same.
+fn run(mouse: &[u8]) -> Vec<Vec<char>> {
+    let args = [
+        "--mouse",
+        "--reduced-motion",
+        "--fps",
+        "20",
+        "--frames",
+        "40",
+    ];
+    let out = in_pty(&PROFILE, &args, |keyboard| {
+        std::thread::sleep(Duration::from_millis(300));
+        keyboard.write_all(mouse).unwrap();
+    });
+    check(&PROFILE, &out)
+}
+
+#[test]
+fn dragging_turns_and_scrolling_zooms() {
+    let still = run(b"");
+    assert_eq!(run(b""), still);
+    let drag = b"\x1b[<0;30;10M\x1b[<32;40;10M\x1b[<32;50;12M\x1b[<0;50;12m";
+    assert_ne!(run(drag), still);
+
+    // Closer up, the donut covers more of the screen
+    let lit = |screen: &[Vec<char>]| screen.iter().flatten().filter(|&&c| c != ' ').count();
+    let zoomed = run(&b"\x1b[<64;30;10M".repeat(4));
+    assert!(
+        lit(&zoomed) > lit(&still),
+        "{} {}",
+        lit(&zoomed),
+        lit(&still)
+    );
+}
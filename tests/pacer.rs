@@ -0,0 +1,47 @@
+// Frame pacing: frames start at --fps however long they take to draw, and the
+// log pane shows how long they took.
+mod common;
+
+use common::{check, donut, ColorDepth, Profile};
+use std::time::{Duration, Instant};
+
+#[test]
+fn frames_are_paced_at_the_frame_rate() {
+    let profile = Profile {
+        color: ColorDepth::Ansi16,
+        unicode: false,
+        size: (160, 20),
+    };
+    let started = Instant::now();
+    let output = donut()
+        .envs(profile.env())
+        .args([
+            "--size",
+            "160x20",
+            "--frames",
+            "11",
+            "--fps",
+            "20",
+            "--log-pane",
+        ])
+        .output()
+        .unwrap();
+    let elapsed = started.elapsed();
+    assert!(output.status.success());
+    // Ten waits of 50ms between eleven frames, without rushing or dawdling
+    assert!(elapsed >= Duration::from_millis(500), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+
+    let screen = check(&profile, &output.stdout);
+    let stats = screen
+        .iter()
+        .map(|row| row.iter().collect::<String>())
+        .find_map(|row| row.find("p50 ").map(|at| row[at..].to_string()))
+        .expect("frame time percentiles on screen");
+    let ms = |label: &str| -> f32 {
+        let rest = &stats[stats.find(label).unwrap() + label.len()..];
+        rest[..rest.find("ms").unwrap()].parse().unwrap()
+    };
+    assert!((ms("p50 ") - 50.0).abs() < 5.0, "{}", stats);
+    assert!(ms("p99 ") >= ms("p50 "), "{}", stats);
+}
@@ -0,0 +1,150 @@
+// --serve --per-client: scenes of their own for the first clients.
+mod common;
+
+use common::{connect, donut, free_port};
+use std::process::Stdio;
+
+// With --per-client, the first clients get scenes of their own, at the
+// window size telnet reports, and the rest share.
+#[test]
+fn per_client_scenes_fit_their_windows() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let port = free_port();
+    let mut child = donut()
+        .arg("--serve")
+        .arg(format!("127.0.0.1:{}", port))
+        .args(["--per-client", "1", "--size", "40x12", "--fps", "50"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let connect = || connect(|| TcpStream::connect(("127.0.0.1", port)));
+    // IAC SB NAWS 30x8 IAC SE
+    let mut own = connect();
+    own.write_all(&[255, 250, 31, 0, 30, 0, 8, 255, 240])
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    let mut shared = connect();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    // The bottom row each is drawn down to
+    let rows = |stream: &mut TcpStream| {
+        let mut buf = vec![0; 1 << 16];
+        let n = stream.read(&mut buf).unwrap();
+        let text = String::from_utf8_lossy(&buf[..n]).into_owned();
+        text.split("\x1b[")
+            .filter_map(|s| s.split_once(";1H"))
+            .filter_map(|(row, _)| row.parse::<usize>().ok())
+            .max()
+    };
+    let (own_rows, shared_rows) = (rows(&mut own), rows(&mut shared));
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(own_rows, Some(8));
+    assert_eq!(shared_rows, Some(12));
+}
+
+// Everything a client is sent over the next `millis`.
+fn drain(stream: &mut std::net::TcpStream, millis: u64) -> String {
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+
+    stream
+        .set_read_timeout(Some(Duration::from_millis(50)))
+        .unwrap();
+    let (started, mut out) = (Instant::now(), Vec::new());
+    let mut buf = vec![0; 1 << 16];
+    while started.elapsed() < Duration::from_millis(millis) {
+        if let Ok(n) = stream.read(&mut buf) {
+            out.extend_from_slice(&buf[..n]);
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// The bottom row `text` draws down to.
+fn bottom(text: &str) -> Option<usize> {
+    text.split("\x1b[")
+        .filter_map(|s| s.split_once(";1H"))
+        .filter_map(|(row, _)| row.parse::<usize>().ok())
+        .max()
+}
+
+// A private scene that takes longer than a frame to draw goes back to
+// sharing: the screen is cleared and the shared frame follows.
+#[test]
+fn private_scenes_over_budget_share_again() {
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    let port = free_port();
+    let mut child = donut()
+        .arg("--serve")
+        .arg(format!("127.0.0.1:{}", port))
+        .args(["--per-client", "1", "--size", "40x12", "--fps", "1000"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut own = connect(|| TcpStream::connect(("127.0.0.1", port)));
+    // IAC SB NAWS 200x60 IAC SE: far too big to draw in a millisecond
+    own.write_all(&[255, 250, 31, 0, 200, 0, 60, 255, 240])
+        .unwrap();
+    let text = drain(&mut own, 1000);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(bottom(&text), Some(60));
+    let (_, since) = text.rsplit_once("\x1b[2J").unwrap();
+    assert_eq!(bottom(since), Some(12));
+}
+
+// Private scenes are set up like the shared one, and follow it as it's
+// changed: with the gain down to nothing they go dark too.
+#[cfg(unix)]
+#[test]
+fn private_scenes_follow_the_shared_parameters() {
+    use common::{check, ColorDepth, Profile};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+    use std::os::unix::net::UnixStream;
+
+    let port = free_port();
+    let socket = std::env::temp_dir().join(format!("donut-{}-served.sock", std::process::id()));
+    let mut child = donut()
+        .arg("--serve")
+        .arg(format!("127.0.0.1:{}", port))
+        .args(["--per-client", "1", "--size", "40x12", "--fps", "50"])
+        .arg("--control")
+        .arg(&socket)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut own = connect(|| TcpStream::connect(("127.0.0.1", port)));
+    own.write_all(&[255, 250, 31, 0, 30, 0, 8, 255, 240])
+        .unwrap();
+    // The screen it's left with, less the telnet asking its size
+    let profile = Profile {
+        color: ColorDepth::TrueColor,
+        unicode: true,
+        size: (30, 8),
+    };
+    let mut text = String::new();
+    let mut screen = |more: String| {
+        text.push_str(&more.replace(['\u{fffd}', '\x1f'], ""));
+        check(&profile, text.as_bytes())
+    };
+    let lit = |screen: &[Vec<char>]| screen.iter().flatten().any(|&c| c != ' ');
+    assert!(lit(&screen(drain(&mut own, 500))));
+
+    let control = connect(|| UnixStream::connect(&socket));
+    writeln!(&control, r#"{{"cmd": "set", "name": "gain", "value": 0}}"#).unwrap();
+    let reply = BufReader::new(&control).lines().next().unwrap().unwrap();
+    assert!(reply.contains(r#""ok": true"#), "{}", reply);
+    let dark = screen(drain(&mut own, 500));
+    child.kill().unwrap();
+    child.wait().unwrap();
+    std::fs::remove_file(&socket).ok();
+    assert!(!lit(&dark), "{:?}", dark);
+}
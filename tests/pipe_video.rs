@@ -0,0 +1,19 @@
+// --pipe-video: frames as video.
+mod common;
+
+use common::donut;
+
+// --pipe-video - writes y4m: a header, then whole frames of 8x16 pixel cells.
+#[test]
+fn pipe_video_writes_y4m() {
+    let output = donut()
+        .args(["--pipe-video", "-", "--size", "20x10", "--frames", "2"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let header = b"YUV4MPEG2 W160 H160 F20:1 Ip A1:1 C444\n";
+    assert!(output.stdout.starts_with(header));
+    let frame = 6 + 3 * 160 * 160;
+    assert_eq!(output.stdout.len(), header.len() + 2 * frame);
+    assert_eq!(&output.stdout[header.len()..header.len() + 6], b"FRAME\n");
+}
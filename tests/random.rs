@@ -0,0 +1,32 @@
+// --random: scenes made up from a seed.
+mod common;
+
+use common::donut;
+
+// --random makes the same scene from the same seed, and says what the seed
+// was when it's left to pick one.
+#[test]
+fn random_scenes_come_back_from_their_seed() {
+    let run = |args: &[&str]| {
+        let output = donut()
+            .args(["--dump-hash", "--size", "40x16", "--frames", "3"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let seed = String::from_utf8(output.stderr).unwrap();
+        (String::from_utf8(output.stdout).unwrap(), seed)
+    };
+    let (picked, said) = run(&["--random"]);
+    let seed = said.trim().strip_prefix("--random ").unwrap();
+    assert_eq!(run(&["--random", seed]).0, picked);
+    assert_ne!(run(&["--random", "1"]).0, run(&["--random", "2"]).0);
+
+    // `random = true` in a config file picks one just the same
+    let path = std::env::temp_dir().join(format!("donut-random-{}.toml", std::process::id()));
+    std::fs::write(&path, "random = true\n").unwrap();
+    let (picked, said) = run(&["--config", path.to_str().unwrap()]);
+    std::fs::remove_file(&path).unwrap();
+    let seed = said.trim().strip_prefix("--random ").unwrap();
+    assert_eq!(run(&["--random", seed]).0, picked);
+}
@@ -0,0 +1,92 @@
+// Playing back --record'ed runs and asciinema recordings.
+mod common;
+
+use common::{check, donut, spawn, wait, ColorDepth, Profile};
+use std::process::Stdio;
+
+// A --record'ed run plays back, blended to a higher frame rate, as valid output.
+#[test]
+fn recording_plays_back() {
+    let profile = Profile {
+        color: ColorDepth::TrueColor,
+        unicode: true,
+        size: (40, 16),
+    };
+    let path = std::env::temp_dir().join(format!("donut-{}.rec", std::process::id()));
+    let rec = path.to_str().unwrap();
+    let args = ["--fps", "10", "--palette", "synthwave", "--record", rec];
+    wait(&profile, spawn(&profile, &args));
+    let child = donut()
+        .envs(profile.env())
+        .args(["play", rec, "--interpolate", "--fps", "40"])
+        .args(["--headless", "--size", "40x16", "--frames", "8"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let screen = check(&profile, &wait(&profile, child));
+    std::fs::remove_file(&path).unwrap();
+    assert!(screen.iter().flatten().any(|&c| "-~+*=;%#$@".contains(c)));
+}
+
+// A run recorded across a resize plays back, not blending frames of
+// different sizes.
+#[cfg(unix)]
+#[test]
+fn recording_across_a_resize_plays_back() {
+    use std::os::unix::io::AsRawFd;
+
+    let profile = Profile {
+        color: ColorDepth::Ansi256,
+        unicode: false,
+        size: (40, 16),
+    };
+    let path = std::env::temp_dir().join(format!("donut-{}-resized.rec", std::process::id()));
+    let rec = path.to_str().unwrap();
+    let args = ["--fps", "20", "--frames", "20", "--record", rec];
+    common::in_pty(&profile, &args, |terminal| {
+        std::thread::sleep(std::time::Duration::from_millis(400));
+        let smaller = libc::winsize {
+            ws_row: 12,
+            ws_col: 30,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        assert_eq!(
+            unsafe { libc::ioctl(terminal.as_raw_fd(), libc::TIOCSWINSZ, &smaller) },
+            0
+        );
+    });
+    // Shorter than twenty frames at the starting size: the later ones are
+    // smaller
+    let recorded = std::fs::metadata(&path).unwrap().len() as usize;
+    assert!(recorded < 10 + 20 * (4 + 5 * 40 * 16));
+
+    let child = donut()
+        .envs(profile.env())
+        .args(["play", rec, "--interpolate", "--fps", "40"])
+        .args(["--headless", "--size", "40x16", "--frames", "40"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let screen = check(&profile, &wait(&profile, child));
+    std::fs::remove_file(&path).unwrap();
+    assert!(screen.iter().flatten().any(|&c| c != ' '));
+}
+
+// asciinema .cast files play their output as is, emoji (JSON surrogate
+// pairs) and all.
+#[test]
+fn cast_files_play_as_recorded() {
+    let path = std::env::temp_dir().join(format!("donut-{}.cast", std::process::id()));
+    let cast = "{\"version\": 2, \"width\": 20, \"height\": 4}\n[0.0, \"o\", \"donut \\ud83c\\udf69\\r\\n\"]\n[0.1, \"i\", \"q\"]\n";
+    std::fs::write(&path, cast).unwrap();
+    let output = donut()
+        .args(["play", path.to_str().unwrap(), "--headless"])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, "donut \u{1f369}\r\n".as_bytes());
+}
@@ -0,0 +1,57 @@
+// --serve and its --metrics.
+mod common;
+
+use common::{connect, donut, free_port};
+use std::process::Stdio;
+
+// --serve sends every client the frames, and --metrics counts them.
+#[test]
+fn serve_reports_metrics() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (serve, metrics) = (free_port(), free_port());
+    let mut child = donut()
+        .arg("--serve")
+        .arg(format!("127.0.0.1:{}", serve))
+        .arg("--metrics")
+        .arg(format!("127.0.0.1:{}", metrics))
+        .args(["--size", "40x12", "--fps", "50"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let connect = |port| connect(|| TcpStream::connect(("127.0.0.1", port)));
+    // Scrapes can come in a bit at a time, over several frames
+    let scrape = || {
+        let mut scrape = connect(metrics);
+        scrape.write_all(b"GET /metrics HTTP/1.1\r\n").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        scrape.write_all(b"\r\n").unwrap();
+        let mut response = String::new();
+        scrape.read_to_string(&mut response).unwrap();
+        response
+    };
+    let alone = scrape();
+    let mut client = connect(serve);
+    let mut frames = [0; 4096];
+    let n = client.read(&mut frames).unwrap();
+    assert!(frames[..n].starts_with(b"\x1b[?25l\x1b[2J"));
+
+    let response = scrape();
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(alone.contains("\ndonut_frames_total 0\n"), "{}", alone);
+    assert!(
+        !response.contains("\ndonut_frames_total 0\n"),
+        "{}",
+        response
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.contains("\ndonut_clients 1\n"), "{}", response);
+    assert!(
+        response.contains("donut_frame_seconds_count "),
+        "{}",
+        response
+    );
+}
@@ -0,0 +1,36 @@
+// DonutSpinner, through the library: with stderr redirected it draws nothing,
+// and says where it got to once finished.
+use forbidden_donut::DonutSpinner;
+use std::process::Command;
+use std::time::Duration;
+
+// Run again as a child of the test, with stderr piped, to spin.
+const CHILD: &str = "DONUT_SPINNER_CHILD";
+
+#[test]
+fn redirected_spinner_prints_only_its_last_message() {
+    if std::env::var_os(CHILD).is_some() {
+        let mut spinner = DonutSpinner::new(20, 10);
+        spinner.start();
+        for step in ["resolving", "downloading", "done"] {
+            spinner.set_message(step);
+            std::thread::sleep(Duration::from_millis(60));
+        }
+        spinner.finish().unwrap();
+        return;
+    }
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "redirected_spinner_prints_only_its_last_message",
+            "--nocapture",
+            "--test-threads",
+            "1",
+        ])
+        .env(CHILD, "1")
+        .env("TERM", "xterm-256color")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "done\n");
+}
@@ -0,0 +1,62 @@
+// --swarm N: tori orbiting each other under gravity.
+mod common;
+
+use common::{check, spawn, wait, ColorDepth, Profile};
+
+const PROFILE: Profile = Profile {
+    color: ColorDepth::Ansi256,
+    unicode: false,
+    size: (80, 30),
+};
+
+// The centers of the separate shapes drawn on the screen, leaving out the
+// title rows.
+fn shapes(screen: &[Vec<char>]) -> Vec<(f32, f32)> {
+    let (sx, sy) = PROFILE.size;
+    let mut seen = vec![vec![false; sx]; sy];
+    let mut centers = Vec::new();
+    for y in 2..sy - 1 {
+        for x in 0..sx {
+            if seen[y][x] || screen[y][x] == ' ' {
+                continue;
+            }
+            let (mut sum, mut cells) = ((0.0, 0.0), 0.0);
+            let mut stack = vec![(x, y)];
+            while let Some((x, y)) = stack.pop() {
+                if y < 2 || y >= sy - 1 || x >= sx || seen[y][x] || screen[y][x] == ' ' {
+                    continue;
+                }
+                seen[y][x] = true;
+                sum = (sum.0 + x as f32, sum.1 + y as f32);
+                cells += 1.0;
+                for (dx, dy) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+                    stack.push((x.wrapping_add(dx as usize), y.wrapping_add(dy as usize)));
+                    stack.push((x.wrapping_sub(dx as usize), y.wrapping_sub(dy as usize)));
+                }
+            }
+            centers.push((sum.0 / cells, sum.1 / cells));
+        }
+    }
+    centers
+}
+
+fn pair_after(frames: &str) -> Vec<(f32, f32)> {
+    let args = ["--swarm", "2", "--frames", frames];
+    shapes(&check(&PROFILE, &wait(&PROFILE, spawn(&PROFILE, &args))))
+}
+
+// Two tori swing round their common center, which stays put in the middle of
+// the screen, give or take perspective.
+#[test]
+fn swarm_tori_orbit_their_center() {
+    let pairs: Vec<_> = ["1", "50", "100"].map(pair_after).to_vec();
+    for pair in &pairs {
+        assert_eq!(pair.len(), 2, "{:?}", pairs);
+        let middle = ((pair[0].0 + pair[1].0) / 2.0, (pair[0].1 + pair[1].1) / 2.0);
+        assert!((middle.0 - 40.0).abs() < 5.0, "{:?}", pairs);
+        assert!((middle.1 - 15.0).abs() < 3.0, "{:?}", pairs);
+    }
+    // Further apart across the screen at first than some way round
+    let across = |pair: &[(f32, f32)]| (pair[0].0 - pair[1].0).abs();
+    assert!(across(&pairs[0]) > across(&pairs[1]) + 10.0, "{:?}", pairs);
+}